@@ -493,29 +493,6 @@ A non-nil INTERACTIVE argument means to run the `post-self-insert-hook'."
 	(put-text-property from (point) 'rear-nonsticky
 			   (cons 'hard sticky)))))
 
-(defun open-line (n)
-  "Insert a newline and leave point before it.
-If there is a fill prefix and/or a `left-margin', insert them on
-the new line if the line would have been blank.
-With arg N, insert N newlines."
-  (interactive "*p")
-  (let* ((do-fill-prefix (and fill-prefix (bolp)))
-	 (do-left-margin (and (bolp) (> (current-left-margin) 0)))
-	 (loc (point-marker))
-         ;; Don't expand an abbrev before point.
-	 (abbrev-mode nil))
-    (newline n)
-    (goto-char loc)
-    (while (> n 0)
-      (cond ((bolp)
-	     (if do-left-margin (indent-to (current-left-margin)))
-	     (if do-fill-prefix (insert-and-inherit fill-prefix))))
-      (forward-line 1)
-      (setq n (1- n)))
-    (goto-char loc)
-    ;; Necessary in case a margin or prefix was inserted.
-    (end-of-line)))
-
 (defun split-line (&optional arg)
   "Split current line, moving portion beyond point vertically down.
 If the current line starts with `fill-prefix', insert it on the new
@@ -540,26 +517,6 @@ When called from Lisp code, ARG may be a prefix string to copy."
     (indent-to col 0)
     (goto-char pos)))
 
-(defun delete-indentation (&optional arg)
-  "Join this line to previous and fix up whitespace at join.
-If there is a fill prefix, delete it from the beginning of this line.
-With argument, join this line to following line."
-  (interactive "*P")
-  (beginning-of-line)
-  (if arg (forward-line 1))
-  (if (eq (preceding-char) ?\n)
-      (progn
-	(delete-region (point) (1- (point)))
-	;; If the second line started with the fill prefix,
-	;; delete the prefix.
-	(if (and fill-prefix
-		 (<= (+ (point) (length fill-prefix)) (point-max))
-		 (string= fill-prefix
-			  (buffer-substring (point)
-					    (+ (point) (length fill-prefix)))))
-	    (delete-region (point) (+ (point) (length fill-prefix))))
-	(fixup-whitespace))))
-
 (defalias 'join-line #'delete-indentation) ; easier to find
 
 (defun delete-blank-lines ()
@@ -1213,14 +1170,7 @@ If called from Lisp, return the number of words between START and
 END, without printing any message."
   (interactive (list nil nil))
   (cond ((not (called-interactively-p 'any))
-	 (let ((words 0))
-	   (save-excursion
-	     (save-restriction
-	       (narrow-to-region start end)
-	       (goto-char (point-min))
-	       (while (forward-word-strictly 1)
-		 (setq words (1+ words)))))
-	   words))
+	 (internal-count-words start end))
 	((use-region-p)
 	 (call-interactively 'count-words-region))
 	(t
@@ -5711,29 +5661,6 @@ Does not set point.  Does nothing if mark ring is empty."
 
 (define-obsolete-function-alias
   'exchange-dot-and-mark 'exchange-point-and-mark "23.3")
-(defun exchange-point-and-mark (&optional arg)
-  "Put the mark where point is now, and point where the mark is now.
-This command works even when the mark is not active,
-and it reactivates the mark.
-
-If Transient Mark mode is on, a prefix ARG deactivates the mark
-if it is active, and otherwise avoids reactivating it.  If
-Transient Mark mode is off, a prefix ARG enables Transient Mark
-mode temporarily."
-  (interactive "P")
-  (let ((omark (mark t))
-	(temp-highlight (eq (car-safe transient-mark-mode) 'only)))
-    (if (null omark)
-        (user-error "No mark set in this buffer"))
-    (set-mark (point))
-    (goto-char omark)
-    (cond (temp-highlight
-	   (setq-local transient-mark-mode (cons 'only transient-mark-mode)))
-	  ((or (and arg (region-active-p)) ; (xor arg (not (region-active-p)))
-	       (not (or arg (region-active-p))))
-	   (deactivate-mark))
-	  (t (activate-mark)))
-    nil))
 
 (defcustom shift-select-mode t
   "When non-nil, shifted motion keys activate the mark momentarily.
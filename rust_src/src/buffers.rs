@@ -1,42 +1,69 @@
 //! Functions operating on buffers.
 
 use libc::{self, c_char, c_int, c_uchar, c_void, ptrdiff_t};
-use std::{self, mem, ptr};
+use std::{self, cmp::Ordering, mem, ptr, slice};
 
 use remacs_macros::lisp_fn;
 
 use crate::{
     character::char_head_p,
     chartable::LispCharTableRef,
-    data::Lisp_Fwd,
-    editfns::point,
+    data::{set, set_buffer_local, Lisp_Fwd, Lisp_Fwd_Buffer_Obj},
+    editfns::{insert_lisp, point},
+    eval::run_hook,
     frames::LispFrameRef,
     lisp::defsubr,
     lisp::{ExternalPtr, LispObject, LiveBufferIter},
-    lists::{car, cdr, list, member},
-    marker::{marker_buffer, marker_position_lisp, set_marker_both, LispMarkerRef},
-    multibyte::{multibyte_length_by_head, string_char},
-    numbers::MOST_POSITIVE_FIXNUM,
+    lists::{assq, car, cdr, delq, get, list, member, memq, plist_get, rassq, setcar},
+    marker::{
+        build_marker, marker_buffer, marker_position_lisp, set_marker, set_marker_both,
+        LispMarkerRef,
+    },
+    multibyte::{
+        is_ascii, multibyte_char_at, multibyte_chars_in_text, multibyte_length,
+        multibyte_length_by_head, raw_byte_codepoint, raw_byte_from_codepoint, string_char,
+        write_codepoint, Codepoint, LispStringRef, MAX_MULTIBYTE_LENGTH,
+    },
+    numbers::{random, MOST_POSITIVE_FIXNUM},
+    obarray::intern,
+    process::get_buffer_process,
     remacs_sys::{
-        allocate_misc, bset_update_mode_line, buffer_local_flags, buffer_local_value,
-        buffer_window_count, del_range, delete_all_overlays, drop_overlay, globals,
-        last_per_buffer_idx, set_buffer_internal_1, specbind, unbind_to, unchain_both,
-        update_mode_lines,
+        alloc_buffer_text, allocate_buffer, allocate_misc, bset_redisplay, bset_update_mode_line,
+        buffer_local_flags, buffer_local_value, buffer_memory_full, buffer_window_count,
+        clear_charpos_cache, clone_per_buffer_values, concat2, del_range, del_range_2,
+        delete_all_overlays, do_yes_or_no_p, drop_overlay, frames_discard_buffer, globals,
+        insert_1_both, internal_delete_file, invalidate_buffer_caches, kill_buffer_attachments,
+        last_per_buffer_idx, lock_file, minibuf_window, move_gap_both, nconc2, noninteractive,
+        recenter_overlay_lists, record_unwind_current_buffer, record_unwind_protect,
+        release_buffer_text, replace_buffer_in_windows, replace_buffer_in_windows_safely,
+        replace_range, reset_buffer, reset_buffer_local_variables, set_buffer_internal_1,
+        set_intervals_multibyte, setup_process_coding_systems, specbind,
+        swap_out_buffer_local_variables, thread_check_current_buffer, unbind_to, unchain_both,
+        unlock_buffer, unlock_file, update_mode_lines,
     },
     remacs_sys::{
-        pvec_type, EmacsInt, Lisp_Buffer, Lisp_Buffer_Local_Value, Lisp_Misc_Type, Lisp_Overlay,
-        Lisp_Type, Vbuffer_alist,
+        buffer_text, pvec_type, symbol_redirect, EmacsInt, Lisp_Buffer, Lisp_Buffer_Local_Value,
+        Lisp_Misc_Type, Lisp_Overlay, Lisp_Type, Vbuffer_alist,
     },
     remacs_sys::{
-        windows_or_buffers_changed, Fcopy_sequence, Fexpand_file_name, Ffind_file_name_handler,
-        Fget_text_property, Fnconc, Fnreverse, Foverlay_get, Fwiden,
+        windows_or_buffers_changed, Fbuffer_local_variables, Fcopy_sequence, Fexpand_file_name,
+        Ffind_file_name_handler, Fformat, Fget, Fget_text_property, Fmake_marker, Fnconc,
+        Fnext_window, Fnreverse, Fother_buffer, Frun_hook_with_args_until_failure,
+        Fset_visited_file_modtime, Fsubstring_no_properties, Fvariable_binding_locus,
+        Fverify_visited_file_modtime, Fwiden,
     },
     remacs_sys::{
-        Qafter_string, Qbefore_string, Qbuffer_read_only, Qbufferp, Qget_file_buffer,
-        Qinhibit_quit, Qinhibit_read_only, Qnil, Qoverlayp, Qt, Qunbound, Qvoid_variable,
+        QCautomatically_local, QClocus, QCpermanent_local, Qafter_string, Qapply, Qascii,
+        Qbefore_string, Qbuffer_list_update_hook, Qbuffer_read_only, Qbuffer_undo_list, Qbufferp,
+        Qcategory, Qdisplay, Qerror, Qevaporate, Qface, Qfirst_change_hook, Qget_file_buffer,
+        Qinhibit_modification_hooks, Qinhibit_quit, Qinhibit_read_only, Qintegerp, Qinvisible,
+        Qkill_buffer_hook, Qkill_buffer_query_functions, Qnil, Qoverlayp, Qpermanent_local,
+        Qpriority, Qt, Qunbound, Qutf_8, Qvoid_variable, Qwindow,
     },
     strings::string_equal,
+    symbols::{keywordp, symbol_value, LispSymbolRef},
     threads::{c_specpdl_index, ThreadState},
+    windows::selected_window,
 };
 
 pub const BEG: ptrdiff_t = 1;
@@ -92,6 +119,20 @@ pub const BUF_BYTES_MAX: ptrdiff_t = buf_bytes_max();
 pub type LispBufferRef = ExternalPtr<Lisp_Buffer>;
 pub type LispOverlayRef = ExternalPtr<Lisp_Overlay>;
 
+/// The char tables a buffer refers to, for use with
+/// `LispBufferRef::per_buffer_char_table`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(not(test), allow(dead_code))]
+pub enum BufferCharTableSlot {
+    Syntax,
+    Category,
+    Display,
+    Downcase,
+    Upcase,
+    CaseCanon,
+    CaseEqv,
+}
+
 impl LispBufferRef {
     pub fn as_lisp_obj(self) -> LispObject {
         LispObject::tag_ptr(self, Lisp_Type::Lisp_Vectorlike)
@@ -174,16 +215,68 @@ impl LispBufferRef {
         self.case_fold_search_
     }
 
+    pub fn buffer_file_coding_system(self) -> LispObject {
+        self.buffer_file_coding_system_
+    }
+
     // Check if buffer is live
     pub fn is_live(self) -> bool {
         self.name_.is_not_nil()
     }
 
+    /// The bounds of this buffer's restriction, or None if it is not
+    /// narrowed.  A buffer sharing its text that isn't current keeps its
+    /// up-to-date bounds in its BEGV and ZV markers, so those are used.
+    pub fn narrowed_region(self) -> Option<(ptrdiff_t, ptrdiff_t)> {
+        let markers = (self.begv_marker().as_marker(), self.zv_marker().as_marker());
+        let (begv, zv) = match markers {
+            (Some(begv), Some(zv)) if self != ThreadState::current_buffer() => {
+                (begv.charpos_or_error(), zv.charpos_or_error())
+            }
+            _ => (self.begv, self.zv),
+        };
+
+        if begv == self.beg() && zv == self.z() {
+            None
+        } else {
+            Some((begv, zv))
+        }
+    }
+
+    /// Whether this buffer is shown in some window.  An indirect buffer
+    /// counts as shown when its base buffer is.
+    pub fn is_displayed(mut self) -> bool {
+        unsafe { buffer_window_count(self.as_mut()) > 0 }
+    }
+
+    /// Whether this is a special buffer, meant for internal use.  The
+    /// names of special buffers start with a space.
+    pub fn is_special(self) -> bool {
+        self.name_
+            .as_string()
+            .map_or(false, |name| name.as_slice().first() == Some(&b' '))
+    }
+
     pub fn set_pt_both(&mut self, charpos: ptrdiff_t, byte: ptrdiff_t) {
         self.pt = charpos;
         self.pt_byte = byte;
     }
 
+    /// Give this buffer markers recording its point and narrowing, as
+    /// needed by a buffer that shares its text with another one.  The
+    /// ZV marker advances on insertion, so that text inserted at the end
+    /// of the accessible portion stays accessible.
+    pub fn set_narrowing_markers(&mut self) {
+        unsafe {
+            self.pt_marker_ = build_marker(self.as_mut(), self.pt, self.pt_byte);
+            self.begv_marker_ = build_marker(self.as_mut(), self.begv, self.begv_byte);
+            self.zv_marker_ = build_marker(self.as_mut(), self.zv, self.zv_byte);
+        }
+        self.zv_marker_
+            .as_marker_or_error()
+            .set_insertion_type(true);
+    }
+
     pub fn set_begv_both(&mut self, charpos: ptrdiff_t, byte: ptrdiff_t) {
         self.begv = charpos;
         self.begv_byte = byte;
@@ -194,6 +287,35 @@ impl LispBufferRef {
         self.zv_byte = byte;
     }
 
+    /// Return the char table this buffer uses for SLOT, or `None` if the
+    /// slot does not currently hold a char table (a buffer needn't have a
+    /// display table, for instance).
+    pub fn per_buffer_char_table(self, slot: BufferCharTableSlot) -> Option<LispCharTableRef> {
+        let table = match slot {
+            BufferCharTableSlot::Syntax => self.syntax_table_,
+            BufferCharTableSlot::Category => self.category_table_,
+            BufferCharTableSlot::Display => self.display_table_,
+            BufferCharTableSlot::Downcase => self.downcase_table_,
+            BufferCharTableSlot::Upcase => self.upcase_table_,
+            BufferCharTableSlot::CaseCanon => self.case_canon_table_,
+            BufferCharTableSlot::CaseEqv => self.case_eqv_table_,
+        };
+        table.as_char_table()
+    }
+
+    /// Return the undo list of this buffer's text.  An indirect buffer
+    /// shares its text, and thus its undo list, with its base buffer.
+    /// The up-to-date list is kept in the current buffer if that shares
+    /// this buffer's text, and in the base buffer otherwise.
+    pub fn text_undo_list(self) -> LispObject {
+        let current = ThreadState::current_buffer();
+        if current.text == self.text {
+            current.undo_list_
+        } else {
+            self.base_buffer().unwrap_or(self).undo_list_
+        }
+    }
+
     pub fn set_syntax_table(&mut self, table: LispCharTableRef) {
         self.syntax_table_ = LispObject::from(table);
     }
@@ -254,6 +376,15 @@ impl LispBufferRef {
         }
     }
 
+    /// Whether the character just before point is C.  This is never the
+    /// case when point is at the start of the accessible portion.
+    pub fn char_before_point_is(self, c: Codepoint) -> bool {
+        if self.pt <= self.begv {
+            return false;
+        }
+        self.fetch_char(self.dec_pos(self.pt_byte)) as Codepoint == c
+    }
+
     /// Return character code of multi-byte form at byte position POS.  If POS
     /// doesn't point the head of valid multi-byte form, only the byte at
     /// POS is returned.  No range checking.
@@ -285,20 +416,147 @@ impl LispBufferRef {
         }
     }
 
-    // Same as the BUF_INC_POS c macro
+    /// Return the number of the line containing byte position BYTEPOS,
+    /// counting from 1 at the beginning of the buffer and ignoring any
+    /// narrowing.  This buffer need not be current, so it can be used
+    /// when reporting errors from buffer primitives.
+    pub fn line_at_byte(self, bytepos: ptrdiff_t) -> EmacsInt {
+        let bytepos = bytepos.max(self.beg_byte()).min(self.z_byte());
+        let count_newlines = |from: *const c_uchar, len: ptrdiff_t| {
+            let bytes = unsafe { slice::from_raw_parts(from, len as usize) };
+            bytes.iter().filter(|&&b| b == b'\n').count()
+        };
+
+        let gpt_byte = self.gpt_byte();
+        let mut newlines = count_newlines(self.beg_addr(), bytepos.min(gpt_byte) - self.beg_byte());
+        if bytepos > gpt_byte {
+            newlines += count_newlines(self.gap_end_addr(), bytepos - gpt_byte);
+        }
+        newlines as EmacsInt + 1
+    }
+
+    /// Make the markers of this buffer point nowhere and take them off
+    /// the chain of its text.  An indirect buffer only does this for its
+    /// own markers, leaving those of its base buffer and of the base's
+    /// other indirect buffers alone.
+    fn unchain_markers(mut self) {
+        let own = self.as_mut();
+        let indirect = !self.base_buffer.is_null();
+        let mut prev: Option<LispMarkerRef> = None;
+        let mut tail = self.markers();
+        while let Some(marker) = tail {
+            tail = marker.next();
+            if indirect && marker.buffer != own {
+                prev = Some(marker);
+                continue;
+            }
+            let next = tail.map_or(ptr::null_mut(), |mut m| m.as_mut());
+            marker.set_buffer(ptr::null_mut());
+            marker.set_next(ptr::null_mut());
+            match prev {
+                Some(p) => p.set_next(next),
+                None => unsafe { (*self.text).markers = next },
+            }
+        }
+    }
+
+    /// Return a symbol hinting at how this buffer's text could be
+    /// saved: `ascii' if every byte is below 128, `utf-8' if the bytes
+    /// form valid UTF-8, and `raw' otherwise.  Raw bytes and characters
+    /// outside Unicode make a multibyte buffer `raw'.
+    pub fn text_encoding_hint(self) -> LispObject {
+        let gpt_byte = self.gpt_byte();
+        let (before, after) = unsafe {
+            (
+                slice::from_raw_parts(self.beg_addr(), (gpt_byte - self.beg_byte()) as usize),
+                slice::from_raw_parts(self.gap_end_addr(), (self.z_byte() - gpt_byte) as usize),
+            )
+        };
+
+        if before.is_ascii() && after.is_ascii() {
+            Qascii
+        } else if is_valid_utf8_split(before, after) {
+            Qutf_8
+        } else {
+            intern("raw").into()
+        }
+    }
+
+    /// Whether the text between positions BEG and END holds a character
+    /// that isn't ASCII, which is the case iff one of its bytes is above
+    /// 127.  This tells whether the text can be written as pure ASCII.
+    pub fn region_is_multibyte(mut self, beg: ptrdiff_t, end: ptrdiff_t) -> bool {
+        let beg_byte = self.charpos_to_bytepos(beg);
+        let end_byte = self.charpos_to_bytepos(end);
+        let gpt_byte = self.gpt_byte();
+
+        let bytes = |from: ptrdiff_t, to: ptrdiff_t| {
+            let addr = self.byte_pos_addr(from) as *const u8;
+            let offset = if from >= gpt_byte { self.gap_size() } else { 0 };
+            unsafe { slice::from_raw_parts(addr.offset(offset), (to - from).max(0) as usize) }
+        };
+        let before = bytes(beg_byte, end_byte.min(gpt_byte));
+        let after = bytes(beg_byte.max(gpt_byte), end_byte);
+
+        !before.is_ascii() || !after.is_ascii()
+    }
+
+    /// Return the whole text of this buffer, ignoring any narrowing, as
+    /// a Rust string.  The text of a multibyte buffer is decoded, with
+    /// characters that have no Unicode equivalent, such as raw bytes,
+    /// replaced by U+FFFD.  In a unibyte buffer each byte becomes the
+    /// Latin-1 character with the same code, so no information is lost.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn as_rust_string(self) -> String {
+        let gpt_byte = self.gpt_byte();
+        let (before, after) = unsafe {
+            (
+                slice::from_raw_parts(self.beg_addr(), (gpt_byte - BEG_BYTE) as usize),
+                slice::from_raw_parts(self.gap_end_addr(), (self.z_byte() - gpt_byte) as usize),
+            )
+        };
+
+        if !self.multibyte_characters_enabled() {
+            return before.iter().chain(after).map(|&b| char::from(b)).collect();
+        }
+
+        // The gap is always at a character boundary, so each side of it
+        // can be decoded on its own.
+        let mut text = String::with_capacity(before.len() + after.len());
+        for &side in &[before, after] {
+            let mut bytes = side;
+            while !bytes.is_empty() {
+                let (cp, len) = multibyte_char_at(bytes);
+                text.push(std::char::from_u32(cp).unwrap_or(std::char::REPLACEMENT_CHARACTER));
+                bytes = &bytes[len..];
+            }
+        }
+        text
+    }
+
+    // Same as the BUF_INC_POS c macro, except that it also works in
+    // unibyte buffers.
     /// Increment the buffer byte position POS_BYTE of the the buffer to
     /// the next character boundary.  This macro relies on the fact that
     /// *GPT_ADDR and *Z_ADDR are always accessible and the values are
     /// '\0'.  No range checking of POS_BYTE.
     pub fn inc_pos(self, pos_byte: isize) -> isize {
+        // In a unibyte buffer, every byte is a character.
+        if !self.multibyte_characters_enabled() {
+            return pos_byte + 1;
+        }
         let chp = self.buf_byte_address(pos_byte);
         pos_byte + multibyte_length_by_head(chp) as isize
     }
 
-    // Same as the BUF_DEC_POS c macro
+    // Same as the BUF_DEC_POS c macro, except that it also works in
+    // unibyte buffers.
     /// Decrement the buffer byte position POS_BYTE of the buffer to
     /// the previous character boundary.  No range checking of POS_BYTE.
     pub fn dec_pos(self, pos_byte: isize) -> isize {
+        if !self.multibyte_characters_enabled() {
+            return pos_byte - 1;
+        }
         let mut new_pos = pos_byte - 1;
         let mut offset = new_pos - self.beg_byte();
         offset += self.pos_within_range(new_pos);
@@ -313,6 +571,66 @@ impl LispBufferRef {
         new_pos
     }
 
+    /// Iterate over the characters of this buffer from byte position
+    /// START_BYTE, which must be at a character boundary, to the end of
+    /// the text, ignoring any narrowing.  See `BufferCharIter`.
+    pub fn chars(self, start_byte: ptrdiff_t) -> BufferCharIter {
+        BufferCharIter {
+            buffer: self,
+            charpos: self.bytepos_to_charpos(start_byte),
+            bytepos: start_byte,
+        }
+    }
+
+    /// Iterate backward over the characters of this buffer before byte
+    /// position START_BYTE, down to the beginning of the text, ignoring
+    /// any narrowing.  If START_BYTE is in the middle of a multibyte
+    /// character, that character comes first.  See `BufferCharRevIter`.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn chars_rev(self, start_byte: ptrdiff_t) -> BufferCharRevIter {
+        let start_byte = if self.multibyte_characters_enabled() {
+            self.advance_to_char_boundary(start_byte)
+        } else {
+            start_byte
+        };
+        BufferCharRevIter {
+            buffer: self,
+            charpos: self.bytepos_to_charpos(start_byte),
+            bytepos: start_byte,
+        }
+    }
+
+    /// Advance BYTE_POS up to a character boundary and return the
+    /// adjusted position.  A byte that isn't part of a multibyte sequence
+    /// is a boundary by itself.
+    pub fn advance_to_char_boundary(self, byte_pos: ptrdiff_t) -> ptrdiff_t {
+        // Beginning of buffer is always a character boundary.
+        if byte_pos == self.beg_byte() || char_head_p(self.fetch_byte(byte_pos)) {
+            return byte_pos;
+        }
+
+        let mut head = byte_pos - 1;
+        while !char_head_p(self.fetch_byte(head)) && head > self.beg_byte() {
+            head -= 1;
+        }
+        self.inc_pos(head).max(byte_pos)
+    }
+
+    /// Return the character position of byte position BYTEPOS, found by
+    /// counting the multibyte sequences before it.  Unlike
+    /// `buf_bytepos_to_charpos', this uses neither the markers nor the
+    /// position cache, so it is safe while those are being recomputed.
+    pub fn scan_charpos(self, bytepos: ptrdiff_t) -> ptrdiff_t {
+        let gpt_byte = self.gpt_byte();
+        unsafe {
+            if bytepos > gpt_byte {
+                multibyte_chars_in_text(self.gap_end_addr(), bytepos - gpt_byte) + self.gpt()
+            } else {
+                multibyte_chars_in_text(self.beg_addr(), bytepos - self.beg_byte()) + self.beg()
+            }
+        }
+    }
+
     // Methods for accessing struct buffer_text fields
 
     pub fn beg_addr(self) -> *mut c_uchar {
@@ -345,11 +663,122 @@ impl LispBufferRef {
         unsafe { (*self.text).save_modiff }
     }
 
+    /// Record that the buffer's current text has been saved, so that
+    /// it no longer counts as modified.
+    pub fn record_save(&mut self) {
+        unsafe { (*self.text).save_modiff = self.modifications() };
+    }
+
+    /// Reset the modification state of this buffer after its file has
+    /// been read into it: the text counts as unmodified, the visited
+    /// file's modtime is recorded, and undo starts afresh unless it is
+    /// disabled.
+    pub(crate) fn mark_freshly_loaded(&mut self) {
+        self.record_save();
+        // `set-visited-file-modtime' works on the current buffer, and
+        // needs a file name to look at.
+        if self.filename().is_not_nil() {
+            let _guard = CurrentBufferGuard::save();
+            unsafe {
+                set_buffer_internal_1(self.as_mut());
+                Fset_visited_file_modtime(Qnil);
+            }
+        }
+        if !self.undo_list_.eq(Qt) {
+            self.undo_list_ = Qnil;
+        }
+    }
+
+    /// Compare the buffer's modification count with TICK, a value of it
+    /// saved earlier.  See `tick_cmp`.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn modiff_cmp(self, tick: EmacsInt) -> Ordering {
+        tick_cmp(self.modifications(), tick)
+    }
+
+    /// Start of the text affected by the most recent change to the buffer,
+    /// or 0 if it has never been changed.
+    pub fn last_change_beg(self) -> ptrdiff_t {
+        self.last_change_beg
+    }
+
+    /// End of the text affected by the most recent change to the buffer,
+    /// or 0 if it has never been changed.  This equals
+    /// `last_change_beg` if the change was a deletion.
+    pub fn last_change_end(self) -> ptrdiff_t {
+        self.last_change_end
+    }
+
+    /// The earliest position changed since the redisplay start hint was
+    /// last taken, or `None` if the text hasn't changed since.  Text
+    /// before this position is the same as when it was last displayed.
+    pub fn first_change_position(self) -> Option<ptrdiff_t> {
+        match unsafe { (*self.text).redisplay_start_hint } {
+            0 => None,
+            pos => Some(pos),
+        }
+    }
+
+    /// Return `first_change_position` and start tracking changes anew.
+    pub fn take_redisplay_start_hint(&mut self) -> Option<ptrdiff_t> {
+        let hint = self.first_change_position();
+        unsafe { (*self.text).redisplay_start_hint = 0 };
+        hint
+    }
+
+    /// Return true if the buffer was modified since it was last
+    /// auto-saved.
+    pub fn is_autosave_stale(self) -> bool {
+        self.auto_save_modified < self.modifications()
+    }
+
+    /// Record that the buffer's current text has been auto-saved.
+    pub fn record_auto_save(&mut self) {
+        self.auto_save_modified = self.modifications();
+    }
+
     /// Number of modifications to the buffer's characters.
     pub fn char_modifications(self) -> EmacsInt {
         unsafe { (*self.text).chars_modiff }
     }
 
+    /// Number of modifications to the buffer's overlays.
+    pub fn overlay_modifications(self) -> EmacsInt {
+        unsafe { (*self.text).overlay_modiff }
+    }
+
+    /// Whether an overlay in the buffer has been created, moved, deleted
+    /// or had a property changed since its overlay tick was TICK.
+    pub fn overlays_changed_since(self, tick: EmacsInt) -> bool {
+        tick_cmp(self.overlay_modifications(), tick) == Ordering::Greater
+    }
+
+    /// Mark the text between START and END as needing redisplay because
+    /// the overlays covering it have changed.
+    pub fn modify_overlay(mut self, start: ptrdiff_t, end: ptrdiff_t) {
+        let (start, end) = if start > end {
+            (end, start)
+        } else {
+            (start, end)
+        };
+
+        unsafe {
+            let text = &mut *self.text;
+            if text.unchanged_modified == text.modiff
+                && text.overlay_unchanged_modified == text.overlay_modiff
+            {
+                text.beg_unchanged = start - BEG;
+                text.end_unchanged = text.z - end;
+            } else {
+                text.end_unchanged = text.end_unchanged.min(text.z - end);
+                text.beg_unchanged = text.beg_unchanged.min(start - BEG);
+            }
+
+            bset_redisplay(self.as_mut());
+            text.overlay_modiff += 1;
+        }
+    }
+
     pub fn z_byte(self) -> ptrdiff_t {
         unsafe { (*self.text).z_byte }
     }
@@ -366,6 +795,64 @@ impl LispBufferRef {
         unsafe { self.overlays_after.as_ref().map(|m| mem::transmute(m)) }
     }
 
+    /// Iterate over all the overlays of this buffer, in no particular order.
+    pub fn overlays(self) -> impl Iterator<Item = LispOverlayRef> {
+        let before = self
+            .overlays_before()
+            .into_iter()
+            .flat_map(LispOverlayRef::iter);
+        let after = self
+            .overlays_after()
+            .into_iter()
+            .flat_map(LispOverlayRef::iter);
+        before.chain(after)
+    }
+
+    /// Return the start, end and number of properties of each of this
+    /// buffer's overlays, ordered by start and then by end.
+    pub fn overlay_summary(self) -> Vec<(EmacsInt, EmacsInt, usize)> {
+        let mut summary: Vec<(EmacsInt, EmacsInt, usize)> = self
+            .overlays()
+            .filter_map(|ov| {
+                let start = overlay_start(ov)?;
+                let end = overlay_end(ov)?;
+                Some((start, end, ov.plist.iter_tails_plist().count() / 2))
+            })
+            .collect();
+        summary.sort_unstable();
+        summary
+    }
+
+    /// Call F with this buffer's overlay index, building the index first
+    /// if there is no up-to-date one.  Return `None` without calling F if
+    /// the buffer has fewer overlays than `overlay-index-threshold'.
+    fn with_overlay_index<T>(mut self, f: impl FnOnce(&OverlayIndex) -> T) -> Option<T> {
+        let threshold = unsafe { globals.overlay_index_threshold };
+
+        let stale = unsafe { (self.overlay_index as *const OverlayIndex).as_ref() }
+            .map_or(false, |index| {
+                index.overlay_modiff != self.overlay_modifications()
+            });
+        if stale {
+            invalidate_overlay_index(self.as_mut());
+        }
+        // The overlays are counted only when the index is created, so a
+        // buffer with few of them isn't walked again on every query.
+        if self.overlay_index.is_null() {
+            let index = Box::new(OverlayIndex::new(self));
+            self.overlay_index = Box::into_raw(index) as *mut c_void;
+        }
+        let index = unsafe { &mut *(self.overlay_index as *mut OverlayIndex) };
+
+        if threshold > 0 && (index.count as EmacsInt) < threshold {
+            return None;
+        }
+        if index.modiff != Some(self.modifications()) {
+            index.build(self);
+        }
+        Some(f(index))
+    }
+
     pub fn as_live(self) -> Option<LispBufferRef> {
         if self.is_live() {
             Some(self)
@@ -470,6 +957,68 @@ impl LispOverlayRef {
             current: Some(self),
         }
     }
+
+    /// Return the value of property PROP of the overlay.  A property the
+    /// overlay lacks is looked up on the symbol that is the value of its
+    /// `category' property, then under the aliases given for PROP in
+    /// `char-property-alias-alist'.
+    pub fn get(self, prop: LispObject) -> LispObject {
+        let mut fallback = Qnil;
+        let mut tail = self.plist;
+        while let Some(cons) = tail.as_cons() {
+            let value = car(cons.cdr());
+            if cons.car().eq(prop) {
+                return value;
+            }
+            // Only the category symbol's own plist is consulted, never
+            // its `category' property, so a category naming itself
+            // cannot send us round in circles.
+            if cons.car().eq(Qcategory) {
+                if let Some(category) = value.as_symbol() {
+                    fallback = get(category, prop);
+                }
+            }
+            tail = cdr(cons.cdr());
+        }
+
+        if fallback.is_not_nil() {
+            return fallback;
+        }
+
+        // Check for alternative properties.
+        let aliases = assq(prop, unsafe { globals.Vchar_property_alias_alist });
+        aliases
+            .as_cons()
+            .and_then(|aliases| {
+                aliases
+                    .cdr()
+                    .iter_cars_safe()
+                    .map(|alias| plist_get(self.plist, alias))
+                    .find(|value| value.is_not_nil())
+            })
+            .unwrap_or(Qnil)
+    }
+
+    /// Whether the overlay has a `before-string' or `after-string'
+    /// property.  Such strings may contain newlines, which redisplay
+    /// optimizations don't expect.
+    pub fn has_boundary_strings(self) -> bool {
+        self.get(Qbefore_string).is_not_nil() || self.get(Qafter_string).is_not_nil()
+    }
+
+    /// Return the (PRIMARY, SECONDARY) priority of the overlay, read
+    /// from its `priority' property the same way `sort_overlays' does.
+    /// A missing or non-integer priority counts as zero.
+    pub fn priority(self) -> (EmacsInt, EmacsInt) {
+        let prio = self.get(Qpriority);
+        match prio.as_cons() {
+            Some(cons) => (
+                cons.car().as_fixnum().unwrap_or(0),
+                cons.cdr().as_fixnum().unwrap_or(0),
+            ),
+            None => (prio.as_fixnum().unwrap_or(0), 0),
+        }
+    }
 }
 
 pub struct LispOverlayIter {
@@ -491,6 +1040,59 @@ impl Iterator for LispOverlayIter {
     }
 }
 
+/// An iterator over the characters of a buffer, yielding the character
+/// position, byte position and code of each.  The gap is skipped over
+/// transparently.  This works in unibyte buffers too, where each byte
+/// is a character.
+pub struct BufferCharIter {
+    buffer: LispBufferRef,
+    charpos: ptrdiff_t,
+    bytepos: ptrdiff_t,
+}
+
+impl Iterator for BufferCharIter {
+    type Item = (ptrdiff_t, ptrdiff_t, c_int);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.bytepos >= self.buffer.z_byte() {
+            return None;
+        }
+        let item = (
+            self.charpos,
+            self.bytepos,
+            self.buffer.fetch_char(self.bytepos),
+        );
+        self.charpos += 1;
+        self.bytepos = self.buffer.inc_pos(self.bytepos);
+        Some(item)
+    }
+}
+
+/// The reverse of `BufferCharIter`: an iterator over the characters of
+/// a buffer from some position back to its beginning.
+pub struct BufferCharRevIter {
+    buffer: LispBufferRef,
+    charpos: ptrdiff_t,
+    bytepos: ptrdiff_t,
+}
+
+impl Iterator for BufferCharRevIter {
+    type Item = (ptrdiff_t, ptrdiff_t, c_int);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.bytepos <= BEG_BYTE {
+            return None;
+        }
+        self.charpos -= 1;
+        self.bytepos = self.buffer.dec_pos(self.bytepos);
+        Some((
+            self.charpos,
+            self.bytepos,
+            self.buffer.fetch_char(self.bytepos),
+        ))
+    }
+}
+
 impl LispObject {
     /// Return SELF as a struct buffer pointer, defaulting to the current buffer.
     /// Same as the decode_buffer function in buffer.h
@@ -627,6 +1229,18 @@ pub fn buffer_list(frame: Option<LispFrameRef>) -> LispObject {
     }
 }
 
+/// Return the buffer at index N of `buffer-list', counting from 0.
+/// Return nil if N is negative or there are not that many live buffers.
+/// This is a convenience for poking at buffers interactively.
+#[lisp_fn]
+pub fn buffer_by_index(n: EmacsInt) -> Option<LispBufferRef> {
+    if n < 0 {
+        None
+    } else {
+        LiveBufferIter::new().nth(n as usize)
+    }
+}
+
 /// Return t if OBJECT is an overlay.
 #[lisp_fn]
 pub fn overlayp(object: LispObject) -> bool {
@@ -661,6 +1275,171 @@ pub fn get_buffer(buffer_or_name: LispBufferOrName) -> Option<LispBufferRef> {
     buffer_or_name.into()
 }
 
+/// Whether some live buffer is named NAME, ignoring text properties.
+fn buffer_name_in_use(name: LispObject) -> bool {
+    LiveBufferIter::new().any(|buf| string_equal(buf.name_, name))
+}
+
+/// Return a string that is the name of no existing buffer based on NAME.
+/// If there is no live buffer named NAME, then return NAME.
+/// Otherwise modify name by appending `<NUMBER>', incrementing NUMBER
+/// (starting at 2) until an unused name is found, and then return that name.
+/// Optional second argument IGNORE specifies a name that is okay to use (if
+/// it is in the sequence to be tried) even if a buffer with that name exists.
+///
+/// If NAME begins with a space (i.e., a buffer that is not normally
+/// visible to users), then if buffer NAME already exists a random number
+/// is first appended to NAME, to speed up finding a non-existent buffer.
+#[lisp_fn(min = "1")]
+pub fn generate_new_buffer_name(name: LispStringRef, ignore: LispObject) -> LispObject {
+    let is_available = |candidate: LispObject| {
+        (ignore.is_not_nil() && string_equal(candidate, ignore)) || !buffer_name_in_use(candidate)
+    };
+
+    let name = LispObject::from(name);
+    if is_available(name) {
+        return name;
+    }
+
+    // See bug#1229.
+    let genbase = if name.as_string_or_error().as_slice()[0] != b' ' {
+        name
+    } else {
+        let number = random(LispObject::from(1_000_000)).as_fixnum_or_error();
+        let genbase = unsafe { concat2(name, LispObject::from(format!("-{}", number).as_str())) };
+        if !buffer_name_in_use(genbase) {
+            return genbase;
+        }
+        genbase
+    };
+
+    (2..)
+        .map(|count: EmacsInt| unsafe {
+            concat2(genbase, LispObject::from(format!("<{}>", count).as_str()))
+        })
+        .find(|&candidate| is_available(candidate))
+        .unwrap()
+}
+
+/// Return the buffer specified by BUFFER-OR-NAME, creating a new one if needed.
+/// If BUFFER-OR-NAME is a string and a live buffer with that name exists,
+/// return that buffer.  If no such buffer exists, create a new buffer with
+/// that name and return it.  If BUFFER-OR-NAME starts with a space, the new
+/// buffer does not keep undo information.
+///
+/// If BUFFER-OR-NAME is a buffer instead of a string, return it as given,
+/// even if it is dead.  The return value is never nil.
+#[lisp_fn]
+pub fn get_buffer_create(buffer_or_name: LispBufferOrName) -> LispBufferRef {
+    if let Some(buffer) = get_buffer(buffer_or_name) {
+        return buffer;
+    }
+
+    let name = LispObject::from(buffer_or_name).as_string_or_error();
+    if name.len_chars() == 0 {
+        error!("Empty string for buffer name is not allowed");
+    }
+
+    let mut b = LispBufferRef::from_ptr(unsafe { allocate_buffer() } as *mut c_void)
+        .unwrap_or_else(|| panic!("Invalid buffer reference."));
+
+    // An ordinary buffer uses its own struct buffer_text.
+    let own_text: *mut buffer_text = &mut b.own_text;
+    b.text = own_text;
+    b.base_buffer = ptr::null_mut();
+    // No one shares the text with us now.
+    b.indirections = 0;
+    // No one shows us now.
+    b.window_count = 0;
+
+    let gap_size = 20;
+    // We allocate extra 1-byte at the tail and keep it always '\0' for
+    // anchoring a search.
+    unsafe { alloc_buffer_text(b.as_mut(), gap_size + 1) };
+    if b.beg_addr().is_null() {
+        unsafe { buffer_memory_full(gap_size + 1) };
+    }
+
+    unsafe {
+        let text = &mut *b.text;
+        text.gap_size = gap_size;
+        text.gpt = BEG;
+        text.gpt_byte = BEG_BYTE;
+        text.z = BEG;
+        text.z_byte = BEG_BYTE;
+        text.modiff = 1;
+        text.chars_modiff = 1;
+        text.overlay_modiff = 1;
+        text.save_modiff = 1;
+        text.compact = 1;
+        text.intervals = ptr::null_mut();
+        text.unchanged_modified = 1;
+        text.overlay_unchanged_modified = 1;
+        text.end_unchanged = 0;
+        text.beg_unchanged = 0;
+        text.redisplay_start_hint = 0;
+        // Put an anchor '\0'.
+        *text.beg = 0;
+        *text.beg.offset(gap_size) = 0;
+        text.set_inhibit_shrinking(false);
+        text.set_redisplay(false);
+    }
+
+    b.set_pt_both(BEG, BEG_BYTE);
+    b.set_begv_both(BEG, BEG_BYTE);
+    b.set_zv_both(BEG, BEG_BYTE);
+
+    b.newline_cache = ptr::null_mut();
+    b.width_run_cache = ptr::null_mut();
+    b.bidi_paragraph_cache = ptr::null_mut();
+    b.width_table_ = Qnil;
+    b.set_prevent_redisplay_optimizations_p(true);
+
+    // An ordinary buffer normally doesn't need markers to handle BEGV
+    // and ZV.
+    b.pt_marker_ = Qnil;
+    b.begv_marker_ = Qnil;
+    b.zv_marker_ = Qnil;
+
+    let name = unsafe { Fsubstring_no_properties(name.into(), Qnil, Qnil) };
+    b.name_ = name;
+
+    b.overlay_index = ptr::null_mut();
+    b.text_hash = ptr::null_mut();
+    unsafe {
+        reset_buffer(b.as_mut());
+        reset_buffer_local_variables(b.as_mut(), true);
+        init_buffer_name_defaults(b.as_mut());
+    }
+
+    b.mark_ = unsafe { Fmake_marker() };
+    unsafe { (*b.text).markers = ptr::null_mut() };
+
+    // Put this in the alist of all live buffers.
+    let buffer = b.as_lisp_obj();
+    unsafe { Vbuffer_alist = nconc2(Vbuffer_alist, list!(LispObject::cons(name, buffer))) };
+    // And run buffer-list-update-hook.
+    run_buffer_list_update_hook();
+
+    b
+}
+
+/// Return the buffer named NAME, creating it with CONTENT if there is none.
+/// If a buffer named NAME already exists, it is returned untouched;
+/// CONTENT is inserted only into a freshly created buffer, even if that
+/// buffer starts out read-only.  This makes it easy to populate help or
+/// output buffers idempotently.
+#[lisp_fn]
+pub fn get_buffer_create_with_content(name: LispObject, content: LispStringRef) -> LispBufferRef {
+    if let Some(buffer) = get_buffer(name.into()) {
+        return buffer;
+    }
+
+    let buffer = get_buffer_create(name.into());
+    buffer.with_inhibit_read_only(|| insert_lisp(&[content.into()]));
+    buffer
+}
+
 /// Return the current buffer as a Lisp object.
 #[lisp_fn]
 pub fn current_buffer() -> LispObject {
@@ -676,57 +1455,542 @@ pub fn buffer_file_name(buffer: LispBufferOrCurrent) -> LispObject {
     buf.filename_
 }
 
-/// Return t if BUFFER was modified since its file was last read or saved.
-/// No argument or nil as argument means use current buffer as BUFFER.
+/// Return the value of `mode-line-format' in BUFFER.
+/// No argument or nil as argument means use the current buffer.
 #[lisp_fn(min = "0")]
-pub fn buffer_modified_p(buffer: LispBufferOrCurrent) -> bool {
-    let buf = buffer.unwrap();
-    buf.modifications_since_save() < buf.modifications()
+pub fn buffer_mode_line_format(buffer: LispBufferOrCurrent) -> LispObject {
+    buffer.unwrap().mode_line_format_
 }
 
-/// Return the name of BUFFER, as a string.
-/// BUFFER defaults to the current buffer.
-/// Return nil if BUFFER has been killed.
+/// Return the value of `header-line-format' in BUFFER.
+/// No argument or nil as argument means use the current buffer.
 #[lisp_fn(min = "0")]
-pub fn buffer_name(buffer: LispBufferOrCurrent) -> LispObject {
-    let buf = buffer.unwrap();
-    buf.name_
+pub fn buffer_header_line_format(buffer: LispBufferOrCurrent) -> LispObject {
+    buffer.unwrap().header_line_format_
 }
 
-/// Return BUFFER's tick counter, incremented for each change in text.
-/// Each buffer has a tick counter which is incremented each time the
-/// text in that buffer is changed.  It wraps around occasionally.
+/// Return t if BUFFER was modified since its file was last read or saved.
 /// No argument or nil as argument means use current buffer as BUFFER.
 #[lisp_fn(min = "0")]
-pub fn buffer_modified_tick(buffer: LispBufferOrCurrent) -> EmacsInt {
-    let buf = buffer.unwrap();
-    buf.modifications()
-}
-
-/// Return BUFFER's character-change tick counter.
-/// Each buffer has a character-change tick counter, which is set to the
-/// value of the buffer's tick counter (see `buffer-modified-tick'), each
-/// time text in that buffer is inserted or deleted.  By comparing the
-/// values returned by two individual calls of `buffer-chars-modified-tick',
-/// you can tell whether a character change occurred in that buffer in
-/// between these calls.  No argument or nil as argument means use current
-/// buffer as BUFFER.
-#[lisp_fn(min = "0")]
-pub fn buffer_chars_modified_tick(buffer: LispBufferOrCurrent) -> EmacsInt {
+pub fn buffer_modified_p(buffer: LispBufferOrCurrent) -> bool {
     let buf = buffer.unwrap();
-    buf.char_modifications()
+    buf.modifications_since_save() < buf.modifications()
 }
 
-/// Return the position at which OVERLAY starts.
+/// Mark current buffer as modified or unmodified according to FLAG.
+/// A non-nil FLAG means mark the buffer modified.
 #[lisp_fn]
-pub fn overlay_start(overlay: LispOverlayRef) -> Option<EmacsInt> {
-    marker_position_lisp(overlay.start.into())
+pub fn set_buffer_modified_p(flag: LispObject) -> LispObject {
+    // Marking an unmodified buffer modified is its first change, so run
+    // `first-change-hook' now rather than on the next edit.
+    if flag.is_not_nil()
+        && unsafe { !globals.inhibit_modification_hooks }
+        && first_change_pending_p()
+        && unsafe { globals.Vfirst_change_hook }.is_not_nil()
+    {
+        let _inhibit = SpecbindGuard::inhibit_modification_hooks();
+        run_hook(Qfirst_change_hook);
+    }
+
+    // If buffer becoming modified, lock the file.  If buffer becoming
+    // unmodified, `restore-buffer-modified-p' unlocks it.
+    if let Some(truename) = visited_file_truename() {
+        if flag.is_not_nil() && !buffer_modified_p(Qnil.into()) {
+            unsafe { lock_file(truename) };
+        }
+    }
+
+    restore_buffer_modified_p(flag);
+
+    // Set update_mode_lines only if buffer is displayed in some window.
+    // Packages like jit-lock or lazy-lock preserve a buffer's modified
+    // state by recording/restoring the state around blocks of code.
+    // Setting update_mode_lines makes redisplay consider all windows (on
+    // all frames).  Stealth fontification of buffers not displayed would
+    // incur additional redisplay costs if we'd set update_modes_lines
+    // unconditionally.
+    force_mode_line_update(false).into()
 }
 
-/// Return the position at which OVERLAY ends.
+/// Like `set-buffer-modified-p', with a difference concerning redisplay.
+/// FLAG non-nil means mark the current buffer modified and nil means mark
+/// it unmodified.  The visited file is unlocked if the buffer becomes
+/// unmodified, but it is never locked.  If FLAG is an integer, it should
+/// be a value of `buffer-modified-tick' from earlier: the buffer then
+/// counts as modified only if it changed after that tick.  The mode line
+/// is updated only if the modified state of the buffer actually changes.
+/// Use with care.
 #[lisp_fn]
-pub fn overlay_end(overlay: LispOverlayRef) -> Option<EmacsInt> {
-    marker_position_lisp(overlay.end.into())
+pub fn restore_buffer_modified_p(flag: LispObject) -> LispObject {
+    let buffer = ThreadState::current_buffer();
+    let was_modified = buffer_modified_p(Qnil.into());
+    let modiff = buffer.modifications();
+
+    if flag.is_nil() && was_modified {
+        if let Some(truename) = visited_file_truename() {
+            unsafe { unlock_file(truename) };
+        }
+    }
+
+    // Here we have a problem.  SAVE_MODIFF is used here to encode
+    // buffer-modified-p (as SAVE_MODIFF<MODIFF) as well as
+    // recent-auto-save-p (as SAVE_MODIFF<auto_save_modified).  So if we
+    // modify SAVE_MODIFF to affect one, we may affect the other as well.
+    // E.g. if FLAG is nil we need to set SAVE_MODIFF to MODIFF, but if
+    // SAVE_MODIFF<auto_save_modified that means we risk changing
+    // recent-auto-save-p from t to nil.  Vice versa, if FLAG is non-nil
+    // and SAVE_MODIFF>=auto_save_modified we risk changing
+    // recent-auto-save-p from nil to t.
+    let save_modiff = if let Some(tick) = flag.as_fixnum() {
+        tick.min(modiff)
+    } else if flag.is_nil() {
+        // FIXME: This unavoidably sets recent-auto-save-p to nil.
+        modiff
+    } else if was_modified {
+        // Let's try to preserve recent-auto-save-p.
+        buffer.modifications_since_save()
+    } else {
+        // If SAVE_MODIFF == auto_save_modified == MODIFF, we can either
+        // decrease SAVE_MODIFF and auto_save_modified or increase MODIFF.
+        unsafe { (*buffer.text).modiff += 1 };
+        modiff
+    };
+    unsafe { (*buffer.text).save_modiff = save_modiff };
+
+    if buffer_modified_p(Qnil.into()) != was_modified {
+        force_mode_line_update(false);
+    }
+    flag
+}
+
+/// The truename of the file the current buffer visits, which is the name
+/// its lock is made under, or `None` if it visits no file.
+fn visited_file_truename() -> Option<LispObject> {
+    let buffer = ThreadState::current_buffer().text_owner();
+    let truename = buffer.truename();
+    // Test buffer-file-name so that binding it to nil is effective.
+    if truename.is_not_nil() && buffer.filename().is_not_nil() {
+        Some(truename)
+    } else {
+        None
+    }
+}
+
+/// Return true if the current buffer has not been modified since it was
+/// last saved or marked unmodified, so that the next change to it is the
+/// one that should run `first-change-hook'.  Once that change is made,
+/// the hook stays quiet until the buffer is saved again.
+#[no_mangle]
+pub extern "C" fn first_change_pending_p() -> bool {
+    !buffer_modified_p(Qnil.into())
+}
+
+/// Return the region affected by the most recent change to BUFFER.
+/// The value is a cons (BEG . END) of the positions the changed text
+/// occupied right after the change; BEG equals END if text was only
+/// deleted.  Return nil if BUFFER has never been changed.
+/// No argument or nil as argument means use current buffer as BUFFER.
+#[lisp_fn(min = "0")]
+pub fn buffer_last_change_region(buffer: LispBufferOrCurrent) -> LispObject {
+    let buf = buffer.unwrap();
+    if buf.last_change_beg() == 0 {
+        Qnil
+    } else {
+        LispObject::cons(
+            LispObject::from(buf.last_change_beg()),
+            LispObject::from(buf.last_change_end()),
+        )
+    }
+}
+
+/// Return the earliest position changed in BUFFER since the last call.
+/// Text before the returned position has not changed since this function
+/// was last called for BUFFER, so redisplay need not rescan it.  Return
+/// nil if BUFFER has not changed since.  Calling this function resets
+/// the hint.  The hint belongs to the text, so an indirect buffer shares
+/// it with its base buffer.  No argument or nil as argument means use
+/// current buffer as BUFFER.
+#[lisp_fn(min = "0")]
+pub fn buffer_redisplay_start_hint(buffer: LispBufferOrCurrent) -> Option<EmacsInt> {
+    buffer
+        .unwrap()
+        .take_redisplay_start_hint()
+        .map(|pos| pos as EmacsInt)
+}
+
+/// Return true if the file visited by BUFFER changed on disk since
+/// BUFFER last read or wrote it.  Buffers that don't visit a file, or
+/// whose recorded modtime is unknown, are never stale.
+pub fn buffer_stale_p(buffer: LispBufferRef) -> bool {
+    buffer.filename().is_string()
+        && unsafe { Fverify_visited_file_modtime(buffer.as_lisp_obj()) }.is_nil()
+}
+
+/// Arrange for CALLBACK to be called when BUFFER becomes stale.
+/// BUFFER is stale when the file it visits has changed on disk since
+/// BUFFER last read or wrote it.  The check happens in `run-stale-checks',
+/// which calls CALLBACK with BUFFER as its only argument the first time
+/// it finds BUFFER stale, and again only after BUFFER has been fresh in
+/// between, for instance because it was reverted.
+#[lisp_fn]
+pub fn register_stale_observer(buffer: LispBufferRef, callback: LispObject) {
+    let entry = list!(buffer.as_lisp_obj(), callback, Qnil);
+    unsafe { globals.Vstale_observers = LispObject::cons(entry, globals.Vstale_observers) };
+}
+
+/// Notify the observers of buffers that have become stale.
+/// Call the callback of each observer registered with
+/// `register-stale-observer' whose buffer has become stale since the
+/// last check.  Observers of killed buffers are forgotten.
+#[lisp_fn]
+pub fn run_stale_checks() {
+    let observers: Vec<LispObject> = unsafe { globals.Vstale_observers }
+        .iter_cars_safe()
+        .filter(|&entry| car(entry).as_buffer().map_or(false, LispBufferRef::is_live))
+        .collect();
+    unsafe { globals.Vstale_observers = list(&observers) };
+
+    for entry in observers {
+        let buffer = car(entry).as_buffer_or_error();
+        let callback = car(cdr(entry));
+        let notified = cdr(cdr(entry)).as_cons_or_error();
+
+        if !buffer_stale_p(buffer) {
+            notified.set_car(Qnil);
+        } else if notified.car().is_nil() {
+            notified.set_car(Qt);
+            call!(callback, buffer.as_lisp_obj());
+        }
+    }
+}
+
+/// Whether a buffer's visited file changed on disk behind its back.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SupersessionState {
+    /// The file is as the buffer last read or wrote it.
+    Fresh,
+    /// The file changed on disk since the buffer last read or wrote it,
+    /// so saving the buffer would overwrite someone else's changes.
+    Superseded,
+}
+
+impl LispBufferRef {
+    /// Check whether saving this buffer would supersede a newer version
+    /// of its visited file.  Save code uses this to decide whether to ask
+    /// the user before writing.
+    pub fn check_supersession(self) -> SupersessionState {
+        if buffer_stale_p(self) {
+            SupersessionState::Superseded
+        } else {
+            SupersessionState::Fresh
+        }
+    }
+}
+
+/// Return t if BUFFER's visited file changed on disk since BUFFER last
+/// read or wrote it, so that saving BUFFER would overwrite those changes.
+/// Unlike `revert-needed-p', this does not care whether BUFFER is modified.
+/// No argument or nil as argument means use current buffer as BUFFER.
+#[lisp_fn(min = "0")]
+pub fn buffer_superseded_p(buffer: LispBufferOrCurrent) -> bool {
+    buffer.unwrap().check_supersession() == SupersessionState::Superseded
+}
+
+/// Return t if BUFFER should be reverted from its visited file.
+/// That is the case when BUFFER visits a file, is not modified, and the
+/// file has changed on disk since it was last read or saved.  A modified
+/// buffer is never considered safe to revert automatically.
+/// No argument or nil as argument means use current buffer as BUFFER.
+#[lisp_fn(min = "0")]
+pub fn revert_needed_p(buffer: LispBufferOrCurrent) -> bool {
+    let buf = buffer.unwrap();
+    !buffer_modified_p(buf.as_lisp_obj().into()) && buffer_stale_p(buf)
+}
+
+/// Return t if saving BUFFER would write anything.
+/// That is the case when BUFFER visits a file and has been modified since
+/// it was last read or saved; this is the check `save-buffer' makes before
+/// writing.
+/// No argument or nil as argument means use current buffer as BUFFER.
+#[lisp_fn(min = "0")]
+pub fn buffer_save_needed_p(buffer: LispBufferOrCurrent) -> bool {
+    let buf = buffer.unwrap();
+    buffer_file_name(buf.as_lisp_obj().into()).is_not_nil()
+        && buffer_modified_p(buf.as_lisp_obj().into())
+}
+
+/// Return the name of BUFFER, as a string.
+/// BUFFER defaults to the current buffer.
+/// Return nil if BUFFER has been killed.
+#[lisp_fn(min = "0")]
+pub fn buffer_name(buffer: LispBufferOrCurrent) -> LispObject {
+    let buf = buffer.unwrap();
+    buf.name_
+}
+
+/// Return the restriction of BUFFER as a cons (BEGV . ZV).
+/// BUFFER defaults to the current buffer.  BEGV and ZV are the bounds of
+/// the accessible portion of BUFFER, as set by `narrow-to-region'.
+/// Return nil if BUFFER is not narrowed.
+#[lisp_fn(min = "0")]
+pub fn buffer_narrowing(buffer: LispBufferOrCurrent) -> LispObject {
+    buffer
+        .unwrap()
+        .narrowed_region()
+        .map_or(Qnil, |(begv, zv)| {
+            LispObject::cons(LispObject::from(begv), LispObject::from(zv))
+        })
+}
+
+/// Return t if BUFFER is displayed in some window.
+/// BUFFER defaults to the current buffer.  An indirect buffer counts as
+/// displayed when its base buffer is.  This is cheaper than asking
+/// `get-buffer-window-list' and testing for a non-nil result.
+#[lisp_fn(min = "0")]
+pub fn buffer_displayed_p(buffer: LispBufferOrCurrent) -> bool {
+    buffer.unwrap().is_displayed()
+}
+
+/// Return BUFFER's tick counter, incremented for each change in text.
+/// Each buffer has a tick counter which is incremented each time the
+/// text in that buffer is changed.  It wraps around occasionally.
+/// No argument or nil as argument means use current buffer as BUFFER.
+#[lisp_fn(min = "0")]
+pub fn buffer_modified_tick(buffer: LispBufferOrCurrent) -> EmacsInt {
+    let buf = buffer.unwrap();
+    buf.modifications()
+}
+
+/// Return BUFFER's character-change tick counter.
+/// Each buffer has a character-change tick counter, which is set to the
+/// value of the buffer's tick counter (see `buffer-modified-tick'), each
+/// time text in that buffer is inserted or deleted.  By comparing the
+/// values returned by two individual calls of `buffer-chars-modified-tick',
+/// you can tell whether a character change occurred in that buffer in
+/// between these calls.  No argument or nil as argument means use current
+/// buffer as BUFFER.
+#[lisp_fn(min = "0")]
+pub fn buffer_chars_modified_tick(buffer: LispBufferOrCurrent) -> EmacsInt {
+    let buf = buffer.unwrap();
+    buf.char_modifications()
+}
+
+/// Return BUFFER's overlay tick counter.
+/// The counter is incremented each time an overlay in BUFFER is created,
+/// moved or deleted, or has one of its properties changed.  Edits to the
+/// text do not affect it.  No argument or nil as argument means use
+/// current buffer as BUFFER.
+#[lisp_fn(min = "0")]
+pub fn buffer_overlay_modified_tick(buffer: LispBufferOrCurrent) -> EmacsInt {
+    let buf = buffer.unwrap();
+    buf.overlay_modifications()
+}
+
+/// Return t if the current buffer's overlays changed since TICK.
+/// TICK is a value returned earlier by `buffer-overlay-modified-tick'.
+/// An overlay counts as changed when it is created, moved or deleted, or
+/// when one of its properties is given a new value.
+#[lisp_fn]
+pub fn overlays_modified_since(tick: EmacsInt) -> bool {
+    ThreadState::current_buffer().overlays_changed_since(tick)
+}
+
+/// Return the line number of byte position BYTEPOS in BUFFER.
+/// Lines are counted from 1 at the beginning of BUFFER, ignoring any
+/// narrowing.  BUFFER defaults to the current buffer.
+#[lisp_fn(min = "1")]
+pub fn line_at_byte(bytepos: EmacsInt, buffer: LispBufferOrCurrent) -> EmacsInt {
+    let buf = buffer.unwrap();
+    let bytepos = bytepos as ptrdiff_t;
+    if bytepos < buf.beg_byte() || bytepos > buf.z_byte() {
+        args_out_of_range!(LispObject::from(bytepos), buf.beg_byte(), buf.z_byte());
+    }
+    buf.line_at_byte(bytepos)
+}
+
+/// Whether BEFORE followed by AFTER is valid UTF-8.  A sequence may
+/// straddle the two slices, as happens when they surround the gap.
+fn is_valid_utf8_split(before: &[u8], after: &[u8]) -> bool {
+    let after = match std::str::from_utf8(before) {
+        Ok(_) => after,
+        Err(e) if e.error_len().is_none() => {
+            // BEFORE ends with a truncated but so far valid sequence;
+            // complete it with the first bytes of AFTER.
+            let tail = &before[e.valid_up_to()..];
+            let len = match tail[0] {
+                0xC0..=0xDF => 2,
+                0xE0..=0xEF => 3,
+                _ => 4,
+            };
+            let needed = len - tail.len();
+            if after.len() < needed {
+                return false;
+            }
+            let mut sequence = tail.to_vec();
+            sequence.extend_from_slice(&after[..needed]);
+            if std::str::from_utf8(&sequence).is_err() {
+                return false;
+            }
+            &after[needed..]
+        }
+        Err(_) => return false,
+    };
+    std::str::from_utf8(after).is_ok()
+}
+
+/// Return a hint for the coding system to use when saving BUFFER.
+/// The value is `ascii' if BUFFER's text is pure ASCII, `utf-8' if it
+/// is valid UTF-8, and `raw' otherwise.  BUFFER defaults to the current
+/// buffer.
+#[lisp_fn(min = "0")]
+pub fn buffer_text_encoding_hint(buffer: LispBufferOrCurrent) -> LispObject {
+    buffer.unwrap().text_encoding_hint()
+}
+
+/// Get the property of overlay OVERLAY with property name PROP.
+#[lisp_fn]
+pub fn overlay_get(overlay: LispOverlayRef, prop: LispObject) -> LispObject {
+    overlay.get(prop)
+}
+
+/// Return t if the region between BEG and END contains a non-ASCII character.
+/// This only looks at the bytes of the text, so it is cheaper than
+/// decoding it.  A nil value means the region can be written as pure
+/// ASCII.  BEG and END may be given in either order.
+#[lisp_fn(min = "2")]
+pub fn region_multibyte_p(beg: EmacsInt, end: EmacsInt) -> bool {
+    let buffer = ThreadState::current_buffer();
+    let (beg, end) = (beg.min(end) as ptrdiff_t, beg.max(end) as ptrdiff_t);
+    if beg < buffer.begv || end > buffer.zv {
+        args_out_of_range!(LispObject::from(beg), LispObject::from(end));
+    }
+    buffer.region_is_multibyte(beg, end)
+}
+
+/// Return VALUE, an overlay `priority' property, in canonical form: an
+/// integer, or a cons (PRIMARY . SECONDARY) of two integers, where nil
+/// in either part of a cons stands for 0.  Signal an error if VALUE is
+/// none of these.
+fn normalize_overlay_priority(value: LispObject) -> LispObject {
+    if value.is_nil() || value.is_fixnum() {
+        return value;
+    }
+
+    let part = |part: LispObject| {
+        if part.is_nil() {
+            LispObject::from(0)
+        } else {
+            part.as_fixnum_or_error();
+            part
+        }
+    };
+
+    match value.as_cons() {
+        Some(cons) => LispObject::cons(part(cons.car()), part(cons.cdr())),
+        None => wrong_type!(Qintegerp, value),
+    }
+}
+
+/// Return VALUE, the `face' or `mouse-face' property of an overlay, in
+/// the canonical form redisplay merges: nil elements are dropped from a
+/// list of faces, and a list holding a single face is replaced by that
+/// face.  Face attribute lists such as (:weight bold) and other values
+/// are returned unchanged.  The property itself is stored as given, so
+/// that `overlay-get' returns what `overlay-put' was passed.
+#[no_mangle]
+pub extern "C" fn normalize_overlay_face(value: LispObject) -> LispObject {
+    if value.as_cons().map_or(true, |cons| keywordp(cons.car())) {
+        return value;
+    }
+
+    let mut faces = Vec::new();
+    let mut tail = value;
+    while let Some(cons) = tail.as_cons() {
+        if cons.car().is_not_nil() {
+            faces.push(cons.car());
+        }
+        tail = cons.cdr();
+    }
+
+    // Not a proper list, e.g. (foreground-color . "red").
+    if tail.is_not_nil() {
+        return value;
+    }
+
+    match faces.as_slice() {
+        [face] => *face,
+        _ => list(&faces),
+    }
+}
+
+/// Set one property of overlay OVERLAY: give property PROP value VALUE.
+/// A `priority' must be an integer or a cons of two integers, and is
+/// stored with nil parts of a cons replaced by 0.  VALUE will be returned.
+#[lisp_fn]
+pub fn overlay_put(mut overlay: LispOverlayRef, prop: LispObject, value: LispObject) -> LispObject {
+    let stored = if prop.eq(Qpriority) {
+        normalize_overlay_priority(value)
+    } else {
+        value
+    };
+
+    let mut changed = None;
+    let mut tail = overlay.plist;
+    while let Some(cons) = tail.as_cons() {
+        let rest = match cons.cdr().as_cons() {
+            Some(rest) => rest,
+            None => break,
+        };
+        if cons.car().eq(prop) {
+            changed = Some(!rest.car().eq(stored));
+            rest.set_car(stored);
+            break;
+        }
+        tail = rest.cdr();
+    }
+    let changed = changed.unwrap_or_else(|| {
+        // It wasn't in the list, so add it to the front.
+        overlay.plist = LispObject::cons(prop, LispObject::cons(stored, overlay.plist));
+        stored.is_not_nil()
+    });
+
+    if let Some(mut buffer) = marker_buffer(overlay.start.as_marker_or_error()) {
+        let start = overlay.start.as_marker_or_error().charpos_or_error();
+        let end = overlay.end.as_marker_or_error().charpos_or_error();
+
+        if changed {
+            buffer.modify_overlay(start, end);
+
+            // As in `delete-overlay', display strings and the like may
+            // contain newlines, which redisplay optimizations don't
+            // expect.
+            let display_props = [Qbefore_string, Qafter_string, Qdisplay, Qface, Qinvisible];
+            if display_props
+                .iter()
+                .any(|&display_prop| display_prop.eq(prop))
+            {
+                buffer.set_prevent_redisplay_optimizations_p(true);
+            }
+        }
+
+        if prop.eq(Qevaporate) && value.is_not_nil() && start == end {
+            delete_overlay(overlay.as_lisp_obj());
+        }
+    }
+
+    value
+}
+
+/// Return the position at which OVERLAY starts.
+#[lisp_fn]
+pub fn overlay_start(overlay: LispOverlayRef) -> Option<EmacsInt> {
+    marker_position_lisp(overlay.start.into())
+}
+
+/// Return the position at which OVERLAY ends.
+#[lisp_fn]
+pub fn overlay_end(overlay: LispOverlayRef) -> Option<EmacsInt> {
+    marker_position_lisp(overlay.end.into())
 }
 
 /// Return the buffer OVERLAY belongs to.
@@ -736,6 +2000,196 @@ pub fn overlay_buffer(overlay: LispOverlayRef) -> Option<LispBufferRef> {
     marker_buffer(overlay.start.into())
 }
 
+/// Return a list of the overlays that contain the character at POS.
+/// An overlay ending at POS doesn't contain it.
+/// If SORTED is non-nil, then sort them by decreasing precedence, as
+/// redisplay does: higher priority first; among overlays of equal
+/// priority, one nested inside another comes before it, and otherwise
+/// the one with the higher secondary priority comes first.  If SORTED
+/// is a window, overlays whose `window' property names another window
+/// are left out.
+#[lisp_fn(min = "1")]
+pub fn overlays_at(pos: LispObject, sorted: LispObject) -> LispObject {
+    let pos = pos.as_fixnum_coerce_marker_or_error() as ptrdiff_t;
+    let buffer = ThreadState::current_buffer();
+
+    let mut overlays: Vec<LispOverlayRef> = buffer
+        .with_overlay_index(|index| index.overlays_at(pos))
+        .map(|found| found.into_iter().map(LispOverlayRef::from).collect())
+        .unwrap_or_else(|| {
+            buffer
+                .overlays()
+                .filter(|&ov| match (overlay_start(ov), overlay_end(ov)) {
+                    (Some(start), Some(end)) => start <= pos as EmacsInt && (pos as EmacsInt) < end,
+                    _ => false,
+                })
+                .collect()
+        });
+
+    if sorted.is_not_nil() {
+        if sorted.is_window() {
+            overlays.retain(|ov| {
+                let window = ov.get(Qwindow);
+                window.is_nil() || window.eq(sorted)
+            });
+        }
+        overlays.sort_by(|&a, &b| compare_overlays(b, a));
+    }
+
+    let overlays: Vec<LispObject> = overlays.into_iter().map(LispObject::from).collect();
+    list(&overlays)
+}
+
+/// Compare the precedence of overlays A and B, which both contain some
+/// position, like the C function `compare_overlays': primary priority
+/// decides first, then an overlay nested inside the other takes
+/// precedence, then secondary priority.
+fn compare_overlays(a: LispOverlayRef, b: LispOverlayRef) -> Ordering {
+    let (priority_a, spriority_a) = a.priority();
+    let (priority_b, spriority_b) = b.priority();
+    let (beg_a, end_a) = (overlay_start(a), overlay_end(a));
+    let (beg_b, end_b) = (overlay_start(b), overlay_end(b));
+
+    priority_a.cmp(&priority_b).then_with(|| {
+        if beg_a < beg_b {
+            if end_a < end_b && spriority_a > spriority_b {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            }
+        } else if beg_a > beg_b {
+            if end_a > end_b && spriority_a < spriority_b {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            }
+        } else if end_a != end_b {
+            end_b.cmp(&end_a)
+        } else {
+            spriority_a.cmp(&spriority_b)
+        }
+    })
+}
+
+/// Return a list of the overlays that overlap the region BEG ... END.
+/// Overlap means that at least one character is contained within the overlay
+/// and also contained within the specified region.
+/// Empty overlays are included in the result if they are located at BEG,
+/// between BEG and END, or at END provided END denotes the position at the
+/// end of the buffer.
+#[lisp_fn]
+pub fn overlays_in(beg: LispObject, end: LispObject) -> LispObject {
+    let beg = beg.as_fixnum_coerce_marker_or_error() as ptrdiff_t;
+    let end = end.as_fixnum_coerce_marker_or_error() as ptrdiff_t;
+    let buffer = ThreadState::current_buffer();
+    let end_is_z = end == buffer.z();
+
+    let overlays: Vec<LispObject> = buffer
+        .with_overlay_index(|index| index.overlays_in(beg, end, end_is_z))
+        .unwrap_or_else(|| {
+            buffer
+                .overlays()
+                .filter(|&ov| match (overlay_start(ov), overlay_end(ov)) {
+                    (Some(start), Some(stop)) => {
+                        overlaps_region(start as ptrdiff_t, stop as ptrdiff_t, beg, end, end_is_z)
+                    }
+                    _ => false,
+                })
+                .map(LispObject::from)
+                .collect()
+        });
+
+    list(&overlays)
+}
+
+/// Return the next position after POS where an overlay starts or ends.
+/// If there are no overlay boundaries from POS to (point-max),
+/// the value is (point-max).
+#[lisp_fn]
+pub fn next_overlay_change(pos: LispObject) -> EmacsInt {
+    let pos = pos.as_fixnum_coerce_marker_or_error();
+    let buffer = ThreadState::current_buffer();
+    let mut next = buffer.zv as EmacsInt;
+
+    for ov in buffer.overlays() {
+        // An overlay starting after POS changes first at its start; one
+        // starting at or before POS changes next at its end, if that is
+        // after POS.
+        let boundary = match (overlay_start(ov), overlay_end(ov)) {
+            (Some(start), _) if start > pos => start,
+            (_, Some(end)) if end > pos => end,
+            _ => continue,
+        };
+        if boundary < next {
+            next = boundary;
+            if next == pos + 1 {
+                break;
+            }
+        }
+    }
+    next
+}
+
+/// Return the previous position before POS where an overlay starts or ends.
+/// If there are no overlay boundaries from (point-min) to POS,
+/// the value is (point-min).
+#[lisp_fn]
+pub fn previous_overlay_change(pos: LispObject) -> EmacsInt {
+    let pos = pos.as_fixnum_coerce_marker_or_error();
+    let buffer = ThreadState::current_buffer();
+    let mut prev = buffer.begv as EmacsInt;
+
+    if pos == prev {
+        return pos;
+    }
+
+    for ov in buffer.overlays() {
+        // An overlay ending before POS changed last at its end; one
+        // ending at or after POS changed last at its start, if that is
+        // before POS.
+        let boundary = match (overlay_start(ov), overlay_end(ov)) {
+            (_, Some(end)) if end < pos => end,
+            (Some(start), _) if start < pos => start,
+            _ => continue,
+        };
+        if boundary > prev {
+            prev = boundary;
+            if prev == pos - 1 {
+                break;
+            }
+        }
+    }
+    prev
+}
+
+/// Return the overlay with the highest priority covering point.
+/// If PROP is non-nil, only overlays whose PROP property is non-nil are
+/// considered.  Overlays with equal priority are ranked by their start,
+/// so that the innermost overlay wins.  Return nil if there is none.
+#[lisp_fn(min = "0")]
+pub fn overlay_at_point(prop: LispObject) -> Option<LispOverlayRef> {
+    let overlays = overlays_at(point().into(), Qnil);
+    let has_prop = |ov: LispOverlayRef| prop.is_nil() || ov.get(prop).is_not_nil();
+
+    overlays
+        .iter_cars_safe()
+        .filter_map(LispObject::as_overlay)
+        .filter(|&ov| has_prop(ov))
+        .max_by_key(|&ov| (ov.priority(), overlay_start(ov)))
+}
+
+/// Return an overlay in the current buffer whose PROP property is VALUE.
+/// Properties are looked up as with `overlay-get' and compared with
+/// `eq'.  If several overlays match, which one is returned is
+/// unspecified, so this is meant for properties holding a unique key.
+/// Return nil if there is no such overlay.
+#[lisp_fn]
+pub fn find_overlay_by_property(prop: LispObject, value: LispObject) -> Option<LispOverlayRef> {
+    ThreadState::current_buffer()
+        .overlays()
+        .find(|ov| ov.get(prop).eq(value))
+}
+
 /// Return a list of the properties on OVERLAY.
 /// This is a copy of OVERLAY's plist; modifying its conses has no
 /// effect on OVERLAY.
@@ -749,192 +2203,1581 @@ pub unsafe extern "C" fn validate_region(b: *mut LispObject, e: *mut LispObject)
     let start = *b;
     let stop = *e;
 
-    let mut beg = start.as_fixnum_coerce_marker_or_error();
-    let mut end = stop.as_fixnum_coerce_marker_or_error();
+    let mut beg = start.as_fixnum_coerce_marker_or_error();
+    let mut end = stop.as_fixnum_coerce_marker_or_error();
+
+    if beg > end {
+        mem::swap(&mut beg, &mut end);
+    }
+
+    *b = LispObject::from(beg);
+    *e = LispObject::from(end);
+
+    let buf = ThreadState::current_buffer();
+    let begv = buf.begv as EmacsInt;
+    let zv = buf.zv as EmacsInt;
+
+    if !(begv <= beg && end <= zv) {
+        args_out_of_range!(current_buffer(), start, stop);
+    }
+}
+
+/// Make buffer BUFFER-OR-NAME current for editing operations.
+/// BUFFER-OR-NAME may be a buffer or the name of an existing buffer.
+/// See also `with-current-buffer' when you want to make a buffer current
+/// temporarily.  This function does not display the buffer, so its effect
+/// ends when the current command terminates.  Use `switch-to-buffer' or
+/// `pop-to-buffer' to switch buffers permanently.
+/// The return value is the buffer made current.
+#[lisp_fn]
+pub fn set_buffer(buffer_or_name: LispBufferOrName) -> LispBufferRef {
+    let mut buffer: LispBufferRef = buffer_or_name.into();
+    if !buffer.is_live() {
+        error!("Selecting deleted buffer");
+    };
+    unsafe { set_buffer_internal_1(buffer.as_mut()) };
+    buffer
+}
+
+/// Restores the buffer that was current when it was created once it
+/// goes out of scope.  The buffer is also recorded on the specpdl, so
+/// it is restored as well if a signal or throw unwinds past the guard.
+pub struct CurrentBufferGuard {
+    count: ptrdiff_t,
+}
+
+impl CurrentBufferGuard {
+    pub fn save() -> Self {
+        let count = c_specpdl_index();
+        unsafe { record_unwind_current_buffer() };
+        Self { count }
+    }
+}
+
+impl Drop for CurrentBufferGuard {
+    fn drop(&mut self) {
+        unsafe { unbind_to(self.count, Qnil) };
+    }
+}
+
+/// Binds a variable with `specbind' while alive, the native analog of
+/// `let' around a body.  The previous value is restored on drop, or by
+/// the specpdl if a signal or throw unwinds past the guard.
+pub struct SpecbindGuard {
+    count: ptrdiff_t,
+}
+
+impl SpecbindGuard {
+    pub fn bind(symbol: LispObject, value: LispObject) -> Self {
+        let count = c_specpdl_index();
+        unsafe { specbind(symbol, value) };
+        Self { count }
+    }
+
+    /// Bind `inhibit-modification-hooks' to t, so that the insertion and
+    /// deletion primitives skip `before-change-functions',
+    /// `after-change-functions' and the modification hooks of text
+    /// properties and overlays.
+    pub fn inhibit_modification_hooks() -> Self {
+        Self::bind(Qinhibit_modification_hooks, Qt)
+    }
+
+    /// Bind `inhibit-read-only' to t, so that internal edits (like
+    /// inserting process output) can modify read-only buffers and text.
+    pub fn inhibit_read_only() -> Self {
+        Self::bind(Qinhibit_read_only, Qt)
+    }
+}
+
+impl Drop for SpecbindGuard {
+    fn drop(&mut self) {
+        unsafe { unbind_to(self.count, Qnil) };
+    }
+}
+
+unsafe extern "C" fn kill_temp_buffer(buffer: LispObject) {
+    kill_buffer(buffer.into());
+}
+
+/// Creates a fresh hidden buffer and makes it current while alive, the
+/// native analog of `with-temp-buffer'.  On drop the buffer is killed
+/// and the previously current buffer made current again.  Both are also
+/// recorded on the specpdl, so they happen as well if a signal or throw
+/// unwinds past the guard.
+pub struct TempBuffer {
+    buffer: LispBufferRef,
+    count: ptrdiff_t,
+}
+
+impl TempBuffer {
+    pub fn enter() -> Self {
+        let count = c_specpdl_index();
+        unsafe { record_unwind_current_buffer() };
+
+        let name = generate_new_buffer_name(LispObject::from(" *temp*").as_string_or_error(), Qnil);
+        let mut buffer = get_buffer_create(name.into());
+        unsafe {
+            record_unwind_protect(Some(kill_temp_buffer), buffer.into());
+            set_buffer_internal_1(buffer.as_mut());
+        }
+        Self { buffer, count }
+    }
+
+    pub fn buffer(&self) -> LispBufferRef {
+        self.buffer
+    }
+}
+
+impl Drop for TempBuffer {
+    fn drop(&mut self) {
+        unsafe { unbind_to(self.count, Qnil) };
+    }
+}
+
+impl LispBufferRef {
+    /// Call F with a fresh hidden buffer current, passing it that buffer.
+    /// Afterwards the buffer is killed and the previously current buffer
+    /// restored, even if F panics or signals.
+    pub fn as_temp_for<F, R>(f: F) -> R
+    where
+        F: FnOnce(LispBufferRef) -> R,
+    {
+        let temp = TempBuffer::enter();
+        f(temp.buffer())
+    }
+
+    /// Call F with this buffer current and `inhibit-read-only' bound to
+    /// t, then restore both.
+    pub fn with_inhibit_read_only<F, R>(mut self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        let _buffer = CurrentBufferGuard::save();
+        unsafe { set_buffer_internal_1(self.as_mut()) };
+        let _inhibit = SpecbindGuard::inhibit_read_only();
+        f()
+    }
+
+    /// Return the buffer-local bindings of this buffer, as (SYMBOL, VALUE)
+    /// pairs.  Variables that are locally unbound are left out.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn capture_local_variables(self) -> Vec<(LispObject, LispObject)> {
+        let locals = unsafe { Fbuffer_local_variables(self.as_lisp_obj()) };
+        locals
+            .iter_cars_safe()
+            .filter_map(LispObject::as_cons)
+            .map(|binding| (binding.car(), binding.cdr()))
+            .collect()
+    }
+
+    /// Give this buffer the local BINDINGS, usually a subset of what
+    /// `capture_local_variables` returned before a major mode change
+    /// killed them.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn restore_local_variables(&mut self, bindings: &[(LispObject, LispObject)]) {
+        let _guard = CurrentBufferGuard::save();
+        unsafe { set_buffer_internal_1(self.as_mut()) };
+        for &(variable, value) in bindings {
+            set_buffer_local(variable.into(), value);
+        }
+    }
+}
+
+/// Run the questions and hooks `kill-buffer' runs before killing
+/// BUFFER, with BUFFER current.  First `kill-buffer-query-functions'
+/// are run; then, if INTERACTIVE, the user is asked about a modified
+/// file buffer; then `kill-buffer-hook' is run.  Return false if the
+/// buffer should not be killed.  The hooks may kill BUFFER themselves,
+/// so callers must check it is still live afterwards.
+#[no_mangle]
+pub extern "C" fn run_kill_buffer_hooks(buffer: *mut Lisp_Buffer, interactive: bool) -> bool {
+    let mut buffer = LispBufferRef::from_ptr(buffer as *mut c_void)
+        .unwrap_or_else(|| panic!("Invalid buffer reference."));
+    let _guard = CurrentBufferGuard::save();
+    unsafe { set_buffer_internal_1(buffer.as_mut()) };
+
+    let proceed = callN_raw!(
+        Frun_hook_with_args_until_failure,
+        Qkill_buffer_query_functions
+    );
+    if proceed.is_nil() {
+        return false;
+    }
+
+    if interactive
+        && buffer.filename().is_not_nil()
+        && buffer.modifications() > buffer.modifications_since_save()
+    {
+        let prompt = callN_raw!(
+            Fformat,
+            LispObject::from("Buffer %s modified; kill anyway? "),
+            buffer.name()
+        );
+        if unsafe { do_yes_or_no_p(prompt) }.is_nil() {
+            return false;
+        }
+    }
+
+    if buffer.is_live() {
+        run_hook(Qkill_buffer_hook);
+    }
+    true
+}
+
+/// Give BUFFER, which `get-buffer-create' has just made and named, the
+/// defaults that depend on its name.  A special buffer keeps no undo
+/// information and is not auto-saved; other buffers start with an empty
+/// undo list.
+#[no_mangle]
+pub extern "C" fn init_buffer_name_defaults(buffer: *mut Lisp_Buffer) {
+    let mut buffer = LispBufferRef::from_ptr(buffer as *mut c_void)
+        .unwrap_or_else(|| panic!("Invalid buffer reference."));
+    if buffer.is_special() {
+        buffer.undo_list_ = Qt;
+        buffer.auto_save_file_name_ = Qnil;
+    } else {
+        buffer.undo_list_ = Qnil;
+    }
+}
+
+/// Run `buffer-list-update-hook'.  This does nothing while Emacs is
+/// still too early in its startup to run hooks.
+#[no_mangle]
+pub extern "C" fn run_buffer_list_update_hook() {
+    run_hook(Qbuffer_list_update_hook);
+}
+
+/// Signal a `buffer-read-only' error if the current buffer is read-only.
+/// If the text under POSITION (which defaults to point) has the
+/// `inhibit-read-only' text property set, the error will not be raised.
+#[lisp_fn(min = "0")]
+pub fn barf_if_buffer_read_only(position: Option<EmacsInt>) {
+    let pos = position.unwrap_or_else(point);
+
+    let inhibit_read_only: bool = unsafe { globals.Vinhibit_read_only.into() };
+    let prop = unsafe { Fget_text_property(LispObject::from(pos), Qinhibit_read_only, Qnil) };
+
+    if ThreadState::current_buffer().is_read_only() && !inhibit_read_only && prop.is_nil() {
+        xsignal!(Qbuffer_read_only, current_buffer())
+    }
+}
+
+/// No such buffer error.
+#[no_mangle]
+pub extern "C" fn nsberror(spec: LispObject) -> ! {
+    match spec.as_string() {
+        Some(s) => error!("No buffer named {}", s),
+        None => error!("Invalid buffer argument"),
+    }
+}
+
+/// These functions are for debugging overlays.
+
+/// Return a pair of lists giving all the overlays of the current buffer.
+/// The car has all the overlays before the overlay center;
+/// the cdr has all the overlays after the overlay center.
+/// Recentering overlays moves overlays between these lists.
+/// The lists you get are copies, so that changing them has no effect.
+/// However, the overlays you get are the real objects that the buffer uses.
+#[lisp_fn]
+pub fn overlay_lists() -> LispObject {
+    let list_overlays = |ol: LispOverlayRef| -> LispObject {
+        ol.iter()
+            .fold(Qnil, |accum, n| LispObject::cons(n.as_lisp_obj(), accum))
+    };
+
+    let cur_buf = ThreadState::current_buffer();
+    let before = cur_buf.overlays_before().map_or(Qnil, &list_overlays);
+    let after = cur_buf.overlays_after().map_or(Qnil, &list_overlays);
+    unsafe { LispObject::cons(Fnreverse(before), Fnreverse(after)) }
+}
+
+/// Return a summary of the overlays of BUFFER, for debugging.
+/// The value is a list with an element (START END . NPROPS) for each
+/// overlay, sorted by START, where NPROPS is the number of properties
+/// the overlay has.  Unlike `overlay-lists', the overlays themselves and
+/// their property lists are not returned.
+/// No argument or nil as argument means use the current buffer.
+#[lisp_fn(min = "0")]
+pub fn buffer_overlay_summary(buffer: LispBufferOrCurrent) -> LispObject {
+    let entries: Vec<LispObject> = buffer
+        .unwrap()
+        .overlay_summary()
+        .into_iter()
+        .map(|(start, end, nprops)| {
+            LispObject::cons(
+                LispObject::from(start),
+                LispObject::cons(LispObject::from(end), LispObject::from(nprops as EmacsInt)),
+            )
+        })
+        .collect();
+    list(&entries)
+}
+
+/// A cache of a buffer's overlays, ordered by start position so that
+/// the overlays overlapping a region can be found without examining all
+/// of them.  The buffer's overlay lists remain authoritative: the index
+/// is dropped whenever an overlay is created, moved or deleted, and its
+/// entries are stale as soon as the buffer's text changes, since that
+/// moves the overlays' markers.
+///
+/// The garbage collector doesn't see the overlays in `entries'.  This is
+/// safe because an overlay only leaves the buffer's overlay lists, which
+/// do keep it alive, by a change that increases `overlay_modiff' or calls
+/// `invalidate_overlay_index', and an index with an outdated
+/// `overlay_modiff' is dropped without its entries being examined.
+struct OverlayIndex {
+    overlay_modiff: EmacsInt,
+    /// The number of overlays in the buffer.
+    count: usize,
+    /// The buffer's modification count when `entries' was last built, or
+    /// `None' if it never was because the buffer has too few overlays.
+    modiff: Option<EmacsInt>,
+    /// The overlays with their (START, END), sorted by START.
+    entries: Vec<(ptrdiff_t, ptrdiff_t, LispObject)>,
+    /// Treating `entries' as an implicit binary search tree, where the
+    /// root of the range LO..HI is at its midpoint, `max_end[i]' is the
+    /// greatest END in the subtree rooted at I.
+    max_end: Vec<ptrdiff_t>,
+}
+
+impl OverlayIndex {
+    /// Return an index of BUFFER's overlays that has yet to be built.
+    fn new(buffer: LispBufferRef) -> Self {
+        Self {
+            overlay_modiff: buffer.overlay_modifications(),
+            count: buffer.overlays().count(),
+            modiff: None,
+            entries: Vec::new(),
+            max_end: Vec::new(),
+        }
+    }
+
+    /// Fill the index from the current positions of BUFFER's overlays.
+    fn build(&mut self, buffer: LispBufferRef) {
+        let mut entries: Vec<(ptrdiff_t, ptrdiff_t, LispObject)> = buffer
+            .overlays()
+            .map(|ov| {
+                let start = LispMarkerRef::from(ov.start).charpos_or_error();
+                let end = LispMarkerRef::from(ov.end).charpos_or_error();
+                (start, end, ov.as_lisp_obj())
+            })
+            .collect();
+        entries.sort_by_key(|&(start, _, _)| start);
+
+        self.modiff = Some(buffer.modifications());
+        self.max_end = vec![0; entries.len()];
+        self.entries = entries;
+        self.compute_max_end(0, self.entries.len());
+    }
+
+    fn compute_max_end(&mut self, lo: usize, hi: usize) -> ptrdiff_t {
+        if lo >= hi {
+            return ptrdiff_t::min_value();
+        }
+        let mid = lo + (hi - lo) / 2;
+        let left = self.compute_max_end(lo, mid);
+        let right = self.compute_max_end(mid + 1, hi);
+        self.max_end[mid] = self.entries[mid].1.max(left).max(right);
+        self.max_end[mid]
+    }
+
+    /// Push onto RESULT the overlays that satisfy MATCHES, which must
+    /// only accept overlays with START <= END_BOUND and END >= BEG_BOUND.
+    fn search(
+        &self,
+        lo: usize,
+        hi: usize,
+        beg_bound: ptrdiff_t,
+        end_bound: ptrdiff_t,
+        matches: &dyn Fn(ptrdiff_t, ptrdiff_t) -> bool,
+        result: &mut Vec<LispObject>,
+    ) {
+        if lo >= hi {
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        if self.max_end[mid] < beg_bound {
+            return;
+        }
+        self.search(lo, mid, beg_bound, end_bound, matches, result);
+        let (start, end, overlay) = self.entries[mid];
+        if start > end_bound {
+            return;
+        }
+        if matches(start, end) {
+            result.push(overlay);
+        }
+        self.search(mid + 1, hi, beg_bound, end_bound, matches, result);
+    }
+
+    /// Return the overlays containing the character at POS, like the
+    /// C function `overlays_at'.
+    fn overlays_at(&self, pos: ptrdiff_t) -> Vec<LispObject> {
+        let mut result = Vec::new();
+        let matches = |start: ptrdiff_t, end: ptrdiff_t| start <= pos && pos < end;
+        self.search(0, self.entries.len(), pos, pos, &matches, &mut result);
+        result
+    }
+
+    /// Return the overlays overlapping BEG..END, like the C function
+    /// `overlays_in'.  Empty overlays count if they are at BEG, strictly
+    /// between BEG and END, or at END provided END is Z.
+    fn overlays_in(&self, beg: ptrdiff_t, end: ptrdiff_t, end_is_z: bool) -> Vec<LispObject> {
+        let mut result = Vec::new();
+        let matches =
+            |ostart: ptrdiff_t, oend: ptrdiff_t| overlaps_region(ostart, oend, beg, end, end_is_z);
+        self.search(0, self.entries.len(), beg, end, &matches, &mut result);
+        result
+    }
+}
+
+/// Return true if an overlay spanning OSTART..OEND overlaps BEG..END in
+/// the sense of `overlays-in'.  Empty overlays count if they are at BEG,
+/// strictly between BEG and END, or at END provided END is Z.
+fn overlaps_region(
+    ostart: ptrdiff_t,
+    oend: ptrdiff_t,
+    beg: ptrdiff_t,
+    end: ptrdiff_t,
+    end_is_z: bool,
+) -> bool {
+    (beg < oend && ostart < end) || (ostart == oend && (beg == oend || (end_is_z && oend == end)))
+}
+
+/// Drop BUFFER's overlay index, if it has one.  This must be called
+/// whenever BUFFER's overlays change in a way that doesn't increase its
+/// overlay modification count, and before BUFFER is killed.  Removing
+/// an overlay from BUFFER without doing either would let the garbage
+/// collector free it while the index still refers to it.
+#[no_mangle]
+pub extern "C" fn invalidate_overlay_index(buffer: *mut Lisp_Buffer) {
+    let mut buffer = LispBufferRef::from_ptr(buffer as *mut c_void)
+        .unwrap_or_else(|| panic!("Invalid buffer reference."));
+    if !buffer.overlay_index.is_null() {
+        unsafe { drop(Box::from_raw(buffer.overlay_index as *mut OverlayIndex)) };
+        buffer.overlay_index = ptr::null_mut();
+    }
+}
+
+/// Mark a section of BUF as needing redisplay because of overlays changes.
+#[no_mangle]
+pub extern "C" fn modify_overlay(buf: *mut Lisp_Buffer, start: ptrdiff_t, end: ptrdiff_t) {
+    LispBufferRef::from_ptr(buf as *mut c_void)
+        .unwrap_or_else(|| panic!("Invalid buffer reference."))
+        .modify_overlay(start, end);
+}
+
+/// Number of bytes in each of the overlapping windows of text hashed
+/// by `IncrementalHash`.
+const TEXT_HASH_WINDOW: ptrdiff_t = 8;
+
+/// A checksum of a buffer's text that can be brought up to date after
+/// a change at a cost proportional to the size of the change, rather
+/// than by rehashing the whole buffer.  The checksum is the sum of the
+/// hashes of the windows of `TEXT_HASH_WINDOW` bytes starting at each
+/// byte of the text (those near the end are shorter), so a change only
+/// affects the windows that overlap it.
+///
+/// The insertion and deletion primitives keep the checksum of a buffer
+/// up to date through `incremental_hash_before_change` and
+/// `incremental_hash_after_change`.  A change they don't report leaves
+/// the buffer's modification count out of step with the checksum, which
+/// is then recomputed from scratch the next time it is asked for.
+pub struct IncrementalHash {
+    value: u64,
+    /// The buffer's modification count when VALUE was last brought up
+    /// to date.
+    modiff: EmacsInt,
+    /// Between the two halves of an update, the character positions of
+    /// the text about to change, whose overlapping windows have been
+    /// subtracted from VALUE but not yet added back.
+    pending: Option<(ptrdiff_t, ptrdiff_t)>,
+}
+
+impl IncrementalHash {
+    pub fn new(buffer: LispBufferRef) -> Self {
+        Self {
+            value: buffer.text_window_hashes(buffer.beg_byte(), buffer.z_byte()),
+            modiff: buffer.modifications(),
+            pending: None,
+        }
+    }
+
+    pub fn value(&self) -> u64 {
+        self.value
+    }
+
+    fn is_current(&self, buffer: LispBufferRef) -> bool {
+        self.pending.is_none() && self.modiff == buffer.modifications()
+    }
+
+    /// Remove from the checksum the windows that overlap the text
+    /// between START and END, which is about to change.
+    /// Return false if the checksum was already stale.
+    pub fn before_change(
+        &mut self,
+        buffer: LispBufferRef,
+        start: ptrdiff_t,
+        end: ptrdiff_t,
+    ) -> bool {
+        if !self.is_current(buffer) {
+            return false;
+        }
+        let start_byte = buffer.charpos_to_bytepos(start);
+        let end_byte = buffer.charpos_to_bytepos(end);
+        let old = buffer.text_window_hashes(start_byte - (TEXT_HASH_WINDOW - 1), end_byte);
+        self.value = self.value.wrapping_sub(old);
+        self.pending = Some((start, end));
+        true
+    }
+
+    /// Add back to the checksum the windows that overlap the text passed
+    /// to `before_change', now that LENDEL characters at CHARPOS within
+    /// it have been replaced by LENINS new ones.  The change reported here
+    /// may be narrower than the one announced, so the whole announced
+    /// range, shifted by the change in length, is rehashed.  Return false
+    /// if there was no such call, or if the change isn't within it.
+    pub fn after_change(
+        &mut self,
+        buffer: LispBufferRef,
+        charpos: ptrdiff_t,
+        lendel: ptrdiff_t,
+        lenins: ptrdiff_t,
+    ) -> bool {
+        let (start, end) = match self.pending.take() {
+            Some(range) => range,
+            None => return false,
+        };
+        if charpos < start || charpos + lendel > end {
+            return false;
+        }
+        let start_byte = buffer.charpos_to_bytepos(start);
+        let new_end_byte = buffer.charpos_to_bytepos(end + lenins - lendel);
+        let new = buffer.text_window_hashes(start_byte - (TEXT_HASH_WINDOW - 1), new_end_byte);
+        self.value = self.value.wrapping_add(new);
+        self.modiff = buffer.modifications();
+        true
+    }
+}
+
+impl LispBufferRef {
+    /// Return the sum of the hashes of the text windows starting between
+    /// FROM and TO, clipped to the text of this buffer.
+    fn text_window_hashes(self, from: ptrdiff_t, to: ptrdiff_t) -> u64 {
+        let z_byte = self.z_byte();
+        (from.max(self.beg_byte())..to.min(z_byte)).fold(0, |sum, pos| {
+            // FNV-1a over the window, followed by a final mix so that
+            // the sum isn't dominated by the last bytes of each window.
+            let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+            for n in pos..(pos + TEXT_HASH_WINDOW).min(z_byte) {
+                h ^= u64::from(self.fetch_byte(n));
+                h = h.wrapping_mul(0x0000_0100_0000_01b3);
+            }
+            h ^= h >> 33;
+            h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+            h ^= h >> 33;
+            sum.wrapping_add(h)
+        })
+    }
+
+    /// The buffer that owns this buffer's text, and so its checksum.
+    fn text_owner(self) -> LispBufferRef {
+        self.base_buffer().unwrap_or(self)
+    }
+
+    /// The live buffers sharing this buffer's text: its base buffer, if
+    /// any, and all of the base's indirect buffers, including this one.
+    fn text_sharers(self) -> impl Iterator<Item = LispBufferRef> {
+        let owner = self.text_owner();
+        LiveBufferIter::new().filter(move |b| b.text_owner() == owner)
+    }
+
+    /// Return a checksum of this buffer's text.  The checksum is kept
+    /// up to date as the text changes once it has been asked for, so
+    /// calling this again after an edit only costs as much as the edit.
+    pub fn text_checksum_incremental(self) -> u64 {
+        let mut owner = self.text_owner();
+        if let Some(hash) = unsafe { (owner.text_hash as *mut IncrementalHash).as_ref() } {
+            if hash.is_current(owner) {
+                return hash.value();
+            }
+        }
+        invalidate_incremental_hash(owner.as_mut());
+        let hash = IncrementalHash::new(owner);
+        let value = hash.value();
+        owner.text_hash = Box::into_raw(Box::new(hash)) as *mut c_void;
+        value
+    }
+
+    /// Return the same checksum as `text_checksum_incremental', but
+    /// computed from the whole text.
+    pub fn text_checksum_full(self) -> u64 {
+        IncrementalHash::new(self).value()
+    }
+
+    fn incremental_hash(self) -> Option<&'static mut IncrementalHash> {
+        unsafe { (self.text_owner().text_hash as *mut IncrementalHash).as_mut() }
+    }
+}
+
+/// Drop the checksum of the text of BUFFER, if it has one.  This must
+/// be called whenever the text changes without going through the
+/// insertion and deletion primitives, and before BUFFER is killed.
+///
+/// This is the only place the checksum is freed.  `kill-buffer' calls it
+/// for the buffer being killed, and `reset_buffer' for each buffer it
+/// initializes.  The checksum lives in the buffer that owns the text, so
+/// for an indirect buffer this drops its base's checksum, to be rebuilt
+/// when it is next asked for; an indirect buffer's own `text_hash' stays
+/// null from `make-indirect-buffer' on.
+#[no_mangle]
+pub extern "C" fn invalidate_incremental_hash(buffer: *mut Lisp_Buffer) {
+    let mut buffer = LispBufferRef::from_ptr(buffer as *mut c_void)
+        .unwrap_or_else(|| panic!("Invalid buffer reference."))
+        .text_owner();
+    if !buffer.text_hash.is_null() {
+        unsafe { drop(Box::from_raw(buffer.text_hash as *mut IncrementalHash)) };
+        buffer.text_hash = ptr::null_mut();
+    }
+}
+
+/// Tell BUFFER's text checksum, if any, that the text between START
+/// and END is about to be changed.
+#[no_mangle]
+pub extern "C" fn incremental_hash_before_change(
+    buffer: *mut Lisp_Buffer,
+    start: ptrdiff_t,
+    end: ptrdiff_t,
+) {
+    let mut buffer = LispBufferRef::from_ptr(buffer as *mut c_void)
+        .unwrap_or_else(|| panic!("Invalid buffer reference."));
+    if let Some(hash) = buffer.incremental_hash() {
+        if !hash.before_change(buffer, start.min(end), start.max(end)) {
+            invalidate_incremental_hash(buffer.as_mut());
+        }
+    }
+}
+
+/// Tell BUFFER's text checksum, if any, that LENDEL characters at
+/// CHARPOS have been replaced by LENINS new ones.
+#[no_mangle]
+pub extern "C" fn incremental_hash_after_change(
+    buffer: *mut Lisp_Buffer,
+    charpos: ptrdiff_t,
+    lendel: ptrdiff_t,
+    lenins: ptrdiff_t,
+) {
+    let mut buffer = LispBufferRef::from_ptr(buffer as *mut c_void)
+        .unwrap_or_else(|| panic!("Invalid buffer reference."));
+    if let Some(hash) = buffer.incremental_hash() {
+        if !hash.after_change(buffer, charpos, lendel, lenins) {
+            invalidate_incremental_hash(buffer.as_mut());
+        }
+    }
+}
+
+/// Return a checksum of the text of BUFFER, as a fixnum.
+/// BUFFER defaults to the current buffer.  Text properties are ignored.
+/// Once it has been computed, the checksum is kept up to date as the
+/// text changes, so asking for it again after an edit is cheap.  If FULL
+/// is non-nil, compute the same checksum from scratch instead.
+#[lisp_fn(min = "0")]
+pub fn buffer_text_checksum(buffer: LispBufferOrCurrent, full: bool) -> EmacsInt {
+    let buffer: LispBufferRef = buffer.unwrap();
+    let value = if full {
+        buffer.text_checksum_full()
+    } else {
+        buffer.text_checksum_incremental()
+    };
+    (value & MOST_POSITIVE_FIXNUM as u64) as EmacsInt
+}
+
+fn get_truename_buffer_1(filename: LispObject) -> LispObject {
+    LiveBufferIter::new()
+        .find(|buf| {
+            let buf_truename = buf.truename();
+            buf_truename.is_string() && string_equal(buf_truename, filename)
+        })
+        .into()
+}
+
+#[no_mangle]
+pub extern "C" fn get_truename_buffer(filename: LispObject) -> LispObject {
+    get_truename_buffer_1(filename)
+}
+
+/// If buffer B has markers to record PT, BEGV and ZV when it is not
+/// current, update these markers.
+#[no_mangle]
+pub extern "C" fn record_buffer_markers(buffer: *mut Lisp_Buffer) {
+    let buffer_ref = LispBufferRef::from_ptr(buffer as *mut c_void)
+        .unwrap_or_else(|| panic!("Invalid buffer reference."));
+    let pt_marker = buffer_ref.pt_marker();
+
+    if pt_marker.is_not_nil() {
+        let begv_marker = buffer_ref.begv_marker();
+        let zv_marker = buffer_ref.zv_marker();
+
+        assert!(begv_marker.is_not_nil());
+        assert!(zv_marker.is_not_nil());
+
+        let buffer = buffer_ref.as_lisp_obj();
+        set_marker_both(pt_marker, buffer, buffer_ref.pt, buffer_ref.pt_byte);
+        set_marker_both(begv_marker, buffer, buffer_ref.begv, buffer_ref.begv_byte);
+        set_marker_both(zv_marker, buffer, buffer_ref.zv, buffer_ref.zv_byte);
+    }
+}
+
+/// If buffer B has markers to record PT, BEGV and ZV when it is not
+/// current, fetch these values into B->begv etc.
+#[no_mangle]
+pub extern "C" fn fetch_buffer_markers(buffer: *mut Lisp_Buffer) {
+    let mut buffer_ref = LispBufferRef::from_ptr(buffer as *mut c_void)
+        .unwrap_or_else(|| panic!("Invalid buffer reference."));
+
+    if buffer_ref.pt_marker().is_not_nil() {
+        assert!(buffer_ref.begv_marker().is_not_nil());
+        assert!(buffer_ref.zv_marker().is_not_nil());
+
+        let pt_marker = buffer_ref.pt_marker().as_marker_or_error();
+        let begv_marker = buffer_ref.begv_marker().as_marker_or_error();
+        let zv_marker = buffer_ref.zv_marker().as_marker_or_error();
+
+        buffer_ref.set_pt_both(pt_marker.charpos_or_error(), pt_marker.bytepos_or_error());
+        buffer_ref.set_begv_both(
+            begv_marker.charpos_or_error(),
+            begv_marker.bytepos_or_error(),
+        );
+        buffer_ref.set_zv_both(zv_marker.charpos_or_error(), zv_marker.bytepos_or_error());
+    }
+}
+
+/// Return the buffer visiting file FILENAME (a string).
+/// The buffer's `buffer-file-name' must match exactly the expansion of FILENAME.
+/// If there is no such live buffer, return nil.
+/// See also `find-buffer-visiting'.
+#[lisp_fn]
+pub fn get_file_buffer(filename: LispObject) -> Option<LispBufferRef> {
+    verify_lisp_type!(filename, Qstringp);
+    let filename = unsafe { Fexpand_file_name(filename, Qnil) };
+
+    // If the file name has special constructs in it,
+    // call the corresponding file handler.
+    let handler = unsafe { Ffind_file_name_handler(filename, Qget_file_buffer) };
+
+    if handler.is_not_nil() {
+        let handled_buf = call!(handler, Qget_file_buffer, filename);
+        handled_buf.as_buffer()
+    } else {
+        LiveBufferIter::new().find(|buf| {
+            let buf_filename = buf.filename();
+            buf_filename.is_string() && string_equal(buf_filename, filename)
+        })
+    }
+}
+
+/// Return the value of VARIABLE in BUFFER.
+/// If VARIABLE does not have a buffer-local binding in BUFFER, the value
+/// is the default binding of the variable.
+#[lisp_fn(name = "buffer-local-value", c_name = "buffer_local_value")]
+pub fn buffer_local_value_lisp(variable: LispObject, buffer: LispObject) -> LispObject {
+    // Each buffer's own slot may be stale for state that belongs to
+    // the text shared by indirect buffers.
+    if variable.eq(Qbuffer_undo_list) {
+        return buffer.as_buffer_or_error().text_undo_list();
+    }
+
+    let result = unsafe { buffer_local_value(variable, buffer) };
+
+    if result.eq(Qunbound) {
+        xsignal!(Qvoid_variable, variable);
+    }
+
+    result
+}
+
+/// Return a plist describing how VARIABLE behaves as a buffer-local variable.
+/// The plist has these properties:
+///
+///  `:automatically-local' -- non-nil if setting VARIABLE in any buffer
+///    makes it local there, as after `make-variable-buffer-local'.  This
+///    is also true of built-in per-buffer variables.
+///  `:permanent-local' -- the `permanent-local' property of VARIABLE;
+///    if non-nil, its local values survive a change of major mode.
+///  `:locus' -- where the current binding comes from, as returned by
+///    `variable-binding-locus'.
+#[lisp_fn]
+pub fn buffer_variable_documentation(variable: LispSymbolRef) -> LispObject {
+    let sym = variable.get_indirect_variable();
+    let automatically_local = match sym.get_redirect() {
+        symbol_redirect::SYMBOL_LOCALIZED => unsafe { sym.get_blv() }.local_if_set(),
+        symbol_redirect::SYMBOL_FORWARDED => {
+            let ty = unsafe { (*sym.get_fwd()).u_intfwd.ty };
+            ty == Lisp_Fwd_Buffer_Obj
+        }
+        _ => false,
+    };
+    let variable = LispObject::from(variable);
+    let permanent_local = unsafe { Fget(variable, Qpermanent_local) };
+    let locus = unsafe { Fvariable_binding_locus(variable) };
+
+    list!(
+        QCautomatically_local,
+        LispObject::from(automatically_local),
+        QCpermanent_local,
+        permanent_local,
+        QClocus,
+        locus
+    )
+}
+
+/// Return the base buffer of indirect buffer BUFFER.
+/// If BUFFER is not indirect, return nil.
+/// BUFFER defaults to the current buffer.
+#[lisp_fn(min = "0")]
+pub fn buffer_base_buffer(buffer: LispBufferOrCurrent) -> Option<LispBufferRef> {
+    let buf = buffer.unwrap();
+    buf.base_buffer()
+}
+
+/// Change current buffer's name to NEWNAME (a string).
+/// If second arg UNIQUE is nil or omitted, it is an error if a
+/// buffer named NEWNAME already exists.
+/// If UNIQUE is non-nil, come up with a new name using
+/// `generate-new-buffer-name'.
+/// Interactively, you can set UNIQUE with a prefix argument.
+/// We return the name we actually gave the buffer.
+/// This does not change the name of the visited file (if any).
+#[lisp_fn(
+    min = "1",
+    intspec = "(list (read-string \"Rename buffer (to new name): \" nil 'buffer-name-history (buffer-name (current-buffer))) current-prefix-arg)"
+)]
+pub fn rename_buffer(newname: LispStringRef, unique: bool) -> LispObject {
+    let mut current = ThreadState::current_buffer();
+
+    if newname.len_chars() == 0 {
+        error!("Empty string is invalid as a buffer name");
+    }
+
+    let mut name = LispObject::from(newname);
+    if let Some(existing) = get_buffer(name.into()) {
+        // Don't short-circuit if UNIQUE is t.  That is a useful way to
+        // rename the buffer automatically so you can create another
+        // with the original name.  It makes UNIQUE equivalent to
+        // (rename-buffer (generate-new-buffer-name NEWNAME)).
+        if !unique && existing == current {
+            return current.name_;
+        }
+        if unique {
+            name = generate_new_buffer_name(newname, current.name_);
+        } else {
+            error!("Buffer name `{}' is in use", newname);
+        }
+    }
+
+    current.name_ = name;
+
+    // Catch redisplay's attention.  Unless we do this, the mode lines for
+    // any windows displaying the current buffer will stay unchanged.
+    unsafe {
+        update_mode_lines = 11;
+        bset_update_mode_line(current.as_mut());
+    }
+
+    let buffer = current.as_lisp_obj();
+    setcar(
+        rassq(buffer, unsafe { Vbuffer_alist }).as_cons_or_error(),
+        name,
+    );
+    if current.filename_.is_nil() && current.auto_save_file_name_.is_not_nil() {
+        call!(LispObject::from(intern("rename-auto-save-file")));
+    }
+
+    run_buffer_list_update_hook();
+
+    // Refetch since that last call may have done GC.
+    ThreadState::current_buffer().name_
+}
+
+/// Kill the buffer specified by BUFFER-OR-NAME.
+/// The argument may be a buffer or the name of an existing buffer.
+/// Argument nil or omitted means kill the current buffer.  Return t if the
+/// buffer is actually killed, nil otherwise.
+///
+/// The functions in `kill-buffer-query-functions' are called with the
+/// buffer to be killed as the current buffer.  If any of them returns nil,
+/// the buffer is not killed.  The hook `kill-buffer-hook' is run before the
+/// buffer is actually killed.  The buffer being killed will be current
+/// while the hook is running.  Functions called by any of these hooks are
+/// supposed to not change the current buffer.
+///
+/// Any processes that have this buffer as the `process-buffer' are killed
+/// with SIGHUP.  This function calls `replace-buffer-in-windows' for
+/// cleaning up all windows currently displaying the buffer to be killed.
+#[lisp_fn(min = "0", intspec = "bKill buffer: ")]
+pub fn kill_buffer(buffer_or_name: Option<LispBufferOrName>) -> bool {
+    let mut b: LispBufferRef =
+        buffer_or_name.map_or_else(ThreadState::current_buffer, |b| b.into());
+
+    // Avoid trouble for buffer already dead.
+    if !b.is_live() {
+        return false;
+    }
+
+    if unsafe { thread_check_current_buffer(b.as_mut()) } {
+        return false;
+    }
+
+    // Run hooks with the buffer to be killed the current buffer.
+    let interactive = unsafe { globals.Vexecuting_kbd_macro.is_nil() && !noninteractive };
+    if !run_kill_buffer_hooks(b.as_mut(), interactive) {
+        return false;
+    }
+
+    // If the hooks have killed the buffer, exit now.
+    if !b.is_live() {
+        return true;
+    }
+
+    // We have no more questions to ask.  Verify that it is valid to kill
+    // the buffer.  This must be done after the questions since anything
+    // can happen within do_yes_or_no_p.
+    let buffer = b.as_lisp_obj();
+    let minibuffer_contents = || unsafe { minibuf_window }.as_window_or_error().contents;
+
+    // Don't kill the minibuffer now current.
+    if buffer == minibuffer_contents() {
+        return false;
+    }
+
+    // When we kill an ordinary buffer which shares its buffer text with
+    // indirect buffer(s), we must kill indirect buffer(s) too.  We do it
+    // at this stage so nothing terrible happens if they ask questions or
+    // their hooks get errors.
+    if b.base_buffer().is_none() && b.indirections > 0 {
+        let indirect: Vec<LispBufferRef> = LiveBufferIter::new()
+            .filter(|other| other.base_buffer() == Some(b))
+            .collect();
+        for other in indirect {
+            kill_buffer(Some(LispBufferOrName::Buffer(other.into())));
+        }
+
+        // Exit if we now have killed the base buffer (Bug#11665).
+        if !b.is_live() {
+            return true;
+        }
+    }
+
+    // Run replace_buffer_in_windows before making another buffer current
+    // since set-window-buffer-start-and-point will refuse to make another
+    // buffer current if the selected window does not show the current
+    // buffer (bug#10114).
+    unsafe { replace_buffer_in_windows(buffer) };
+
+    // Exit if replacing the buffer in windows has killed our buffer.
+    if !b.is_live() {
+        return true;
+    }
+
+    // Make this buffer not be current.  Exit if it is the sole visible
+    // buffer.
+    if b == ThreadState::current_buffer() {
+        set_buffer(unsafe { Fother_buffer(buffer, Qnil, Qnil) }.into());
+        if b == ThreadState::current_buffer() {
+            return false;
+        }
+    }
+
+    // If the buffer now current is shown in the minibuffer and our buffer
+    // is the sole other buffer give up.
+    if ThreadState::current_buffer().as_lisp_obj() == minibuffer_contents()
+        && unsafe { Fother_buffer(buffer, Qnil, Qnil) } == buffer
+    {
+        return false;
+    }
+
+    // Now there is no question: we can kill the buffer.
+    unsafe {
+        // Unlock this buffer's file, if it is locked.
+        unlock_buffer(b.as_mut());
+        kill_buffer_attachments(buffer);
+    }
+
+    // Killing buffer processes may run sentinels which may have killed
+    // our buffer.
+    if !b.is_live() {
+        return true;
+    }
+
+    unsafe {
+        // These may run Lisp code and into infinite loops (if someone
+        // insisted on circular lists) so allow quitting here.
+        frames_discard_buffer(buffer);
+        clear_charpos_cache(b.as_mut());
+
+        let inhibit_quit = globals.Vinhibit_quit;
+        globals.Vinhibit_quit = Qt;
+        // Remove the buffer from the list of all buffers.
+        Vbuffer_alist = delq(rassq(buffer, Vbuffer_alist), Vbuffer_alist);
+        // If replace_buffer_in_windows didn't do its job fix that now.
+        replace_buffer_in_windows_safely(buffer);
+        globals.Vinhibit_quit = inhibit_quit;
+    }
+
+    // Delete any auto-save file, if we saved it in this session.  But not
+    // if the buffer is modified.
+    if b.auto_save_file_name_.is_string()
+        && b.auto_save_modified != 0
+        && b.modifications_since_save() < b.auto_save_modified
+        && b.modifications_since_save() < b.modifications()
+        && symbol_value(intern("auto-save-visited-file-name")).is_nil()
+        && symbol_value(intern("delete-auto-save-files")).is_not_nil()
+    {
+        unsafe { internal_delete_file(b.auto_save_file_name_) };
+    }
+
+    // Deleting an auto-save file could have killed our buffer.
+    if !b.is_live() {
+        return true;
+    }
+
+    unsafe { delete_all_overlays(b.as_mut()) };
+    b.unchain_markers();
+    // The markers of its labeled restrictions are dead now, and the entry
+    // would keep the buffer reachable.
+    b.set_labeled_restrictions(Qnil);
+    unsafe {
+        match b.base_buffer() {
+            // Intervals should be owned by the base buffer (Bug#16502).
+            Some(base) => {
+                if let Some(i) = (*b.text).intervals.as_mut() {
+                    i.set_up_obj(true);
+                    i.up.obj = base.as_lisp_obj();
+                }
+            }
+            None => (*b.text).intervals = ptr::null_mut(),
+        }
+    }
+    invalidate_overlay_index(b.as_mut());
+    invalidate_incremental_hash(b.as_mut());
+
+    // Reset the local variables, so that this buffer's local values won't
+    // be protected from GC.  They would be protected if they happened to
+    // remain cached in their symbols.  This gets rid of them for certain.
+    unsafe {
+        swap_out_buffer_local_variables(b.as_mut());
+        reset_buffer_local_variables(b.as_mut(), true);
+    }
+
+    b.name_ = Qnil;
+    unsafe { release_buffer_text(b.as_mut()) };
+    b.undo_list_ = Qnil;
+
+    run_buffer_list_update_hook();
+    true
+}
+
+/// Swap the text between current buffer and BUFFER.
+/// Using this function from `save-excursion' might produce surprising
+/// results, see Info node `(elisp)Swapping Text'.
+#[lisp_fn]
+pub fn buffer_swap_text(mut buffer: LispBufferRef) {
+    let mut current = ThreadState::current_buffer();
+
+    if !buffer.is_live() {
+        error!("Cannot swap a dead buffer's text");
+    }
+
+    // Actually, this may be workable as well, tho probably only if
+    // they're *both* indirect.
+    if buffer.base_buffer().is_some() || current.base_buffer().is_some() {
+        error!("Cannot swap indirect buffers's text");
+    }
+
+    // This is probably harder to make work.
+    if LiveBufferIter::new().any(|other| {
+        let base = other.base_buffer();
+        base == Some(buffer) || base == Some(current)
+    }) {
+        error!("One of the buffers to swap has indirect buffers");
+    }
+
+    macro_rules! swapfield {
+        ($($field:ident),*) => {
+            $(unsafe { ptr::swap(&mut current.$field, &mut buffer.$field) };)*
+        };
+    }
+
+    // Each buffer's `text' keeps pointing to its own `own_text', which
+    // now holds the other buffer's text.
+    swapfield!(own_text);
+    swapfield!(pt, pt_byte, begv, begv_byte, zv, zv_byte, indirections);
+    swapfield!(newline_cache, width_run_cache, bidi_paragraph_cache);
+    swapfield!(overlays_before, overlays_after, overlay_center);
+    swapfield!(
+        undo_list_,
+        mark_,
+        enable_multibyte_characters_,
+        bidi_display_reordering_,
+        bidi_paragraph_direction_,
+        bidi_paragraph_separate_re_,
+        bidi_paragraph_start_re_
+    );
+    // FIXME: Not sure what we should do with these *_marker fields.
+    // Hopefully they're just nil anyway.
+    swapfield!(pt_marker_, begv_marker_, zv_marker_);
+
+    for b in &mut [current, buffer] {
+        b.set_clip_changed(true);
+        b.set_prevent_redisplay_optimizations_p(true);
+        b.point_before_scroll_ = Qnil;
+        unsafe {
+            let text = &mut *b.text;
+            text.modiff += 1;
+            text.chars_modiff += 1;
+            text.overlay_modiff += 1;
+            text.beg_unchanged = text.gpt;
+            text.end_unchanged = text.gpt;
+            // None of the text is what was last displayed in this buffer.
+            text.redisplay_start_hint = BEG;
+        }
+        invalidate_overlay_index(b.as_mut());
+        invalidate_incremental_hash(b.as_mut());
+    }
+
+    // The markers moved with the text, so make them belong to the
+    // buffer now holding it.  Since there's no indirect buffer in sight,
+    // markers on a buffer's chain are either for the other buffer or
+    // dead.
+    for &(mut owner, previous) in &[(current, buffer), (buffer, current)] {
+        let own = owner.as_mut();
+        for marker in owner.markers().into_iter().flat_map(LispMarkerRef::iter) {
+            if marker.buffer == previous.as_ptr() as *mut Lisp_Buffer {
+                marker.set_buffer(own);
+            } else {
+                debug_assert!(marker.buffer.is_null());
+            }
+        }
+    }
+
+    // Some of the C code expects that both window markers of a live
+    // window points to that window's buffer.  So since we just swapped
+    // the markers between the two buffers, we need to undo the effect of
+    // this swap for window markers.
+    let swapped = [current.as_lisp_obj(), buffer.as_lisp_obj()];
+    let mut window = selected_window();
+    let mut seen = Qnil;
+    while memq(window, seen).is_nil() {
+        seen = LispObject::cons(window, seen);
+        let w = window.as_window_or_error();
+        if swapped.contains(&w.contents) {
+            let contents = w.contents.as_buffer_or_error();
+            let positions = [
+                (w.pointm, contents.begv),
+                (w.old_pointm, contents.begv),
+                (w.start, contents.last_window_start),
+            ];
+            for &(marker, pos) in &positions {
+                if marker.is_marker() {
+                    set_marker(marker, LispObject::from(pos), w.contents);
+                }
+            }
+        }
+        window = unsafe { Fnext_window(window, Qt, Qt) };
+    }
+
+    // The interval trees belong to the buffer now holding their text.
+    for b in &[current, buffer] {
+        unsafe {
+            if let Some(intervals) = (*b.text).intervals.as_mut() {
+                intervals.up.obj = b.as_lisp_obj();
+            }
+        }
+    }
+}
+
+/// Set the multibyte flag of the current buffer to FLAG.
+/// If FLAG is t, this makes the buffer a multibyte buffer.
+/// If FLAG is nil, this makes the buffer a single-byte buffer.
+/// In these cases, the buffer contents remain unchanged as a sequence of
+/// bytes but the contents viewed as characters do change.
+/// If FLAG is `to', this makes the buffer a multibyte buffer by changing
+/// all eight-bit bytes to eight-bit characters.
+/// If the multibyte flag was really changed, undo information of the
+/// current buffer is cleared.
+#[lisp_fn]
+pub fn set_buffer_multibyte(flag: LispObject) -> LispObject {
+    let mut current = ThreadState::current_buffer();
+
+    if current.base_buffer().is_some() {
+        error!("Cannot do `set-buffer-multibyte' on an indirect buffer");
+    }
+
+    // Do nothing if nothing actually changes.
+    if flag.is_nil() == current.enable_multibyte_characters_.is_nil() {
+        return flag;
+    }
+
+    if current.begv != current.beg() || current.zv != current.z() {
+        error!("Changing multibyteness in a narrowed buffer");
+    }
+
+    let modified_p = buffer_modified_p(Qnil.into());
+    let old_undo = current.undo_list_;
+
+    // Don't record these buffer changes.  We will put a special undo
+    // entry instead.
+    current.undo_list_ = Qt;
+
+    unsafe {
+        // If the cached position is for this buffer, clear it out.
+        clear_charpos_cache(current.as_mut());
+        invalidate_buffer_caches(current.as_mut(), current.begv, current.zv);
+    }
+
+    if flag.is_nil() {
+        to_unibyte_text(current);
+    } else {
+        to_multibyte_text(current, flag.eq(Qt));
+    }
+
+    if !old_undo.eq(Qt) {
+        // Represent all the above changes by a special undo entry.
+        let entry = list(&[Qapply, intern("set-buffer-multibyte"), flag.is_nil().into()]);
+        current.undo_list_ = LispObject::cons(entry, old_undo);
+    }
+
+    current.set_prevent_redisplay_optimizations_p(true);
+
+    // If buffer is shown in a window, let redisplay consider other windows.
+    if current.is_displayed() {
+        unsafe { windows_or_buffers_changed = 10 };
+    }
+
+    // Copy this buffer's new multibyte status into all of its indirect
+    // buffers.  Their overlays moved with the text too.
+    for mut other in LiveBufferIter::new().filter(|b| b.base_buffer() == Some(current)) {
+        other.enable_multibyte_characters_ = current.enable_multibyte_characters_;
+        other.set_prevent_redisplay_optimizations_p(true);
+        invalidate_overlay_index(other.as_mut());
+    }
+
+    // The conversion rewrites the text without signaling the change.
+    unsafe { (*current.text).redisplay_start_hint = BEG };
+
+    // Converting the text moves the overlays without touching MODIFF.
+    invalidate_overlay_index(current.as_mut());
+    invalidate_incremental_hash(current.as_mut());
+
+    // Restore the modifiedness of the buffer.
+    if !modified_p && buffer_modified_p(Qnil.into()) {
+        set_buffer_modified_p(Qnil);
+    }
+
+    // Update coding systems of this buffer's process (if any).
+    let process = get_buffer_process(Some(current.as_lisp_obj().into()));
+    if process.is_process() {
+        unsafe { setup_process_coding_systems(process) };
+    }
+
+    flag
+}
+
+/// Turn the multibyte text of BUFFER, which must be current, into
+/// unibyte text made of the same bytes, except that the two-byte
+/// sequences of eight-bit characters become the raw bytes they stand for.
+fn to_unibyte_text(mut buffer: LispBufferRef) {
+    // Do this first, so it can use CHAR_TO_BYTE to calculate the old
+    // correspondences.
+    unsafe { set_intervals_multibyte(false) };
+
+    buffer.enable_multibyte_characters_ = Qnil;
+
+    unsafe {
+        let text = &mut *buffer.text;
+        text.z = text.z_byte;
+        text.gpt = text.gpt_byte;
+    }
+    let (begv_byte, zv_byte, pt_byte) = (buffer.begv_byte, buffer.zv_byte, buffer.pt_byte);
+    buffer.set_begv_both(begv_byte, begv_byte);
+    buffer.set_zv_both(zv_byte, zv_byte);
+    buffer.set_pt_both(pt_byte, pt_byte);
+
+    for mut marker in buffer.markers().into_iter().flat_map(LispMarkerRef::iter) {
+        let bytepos = marker.bytepos;
+        marker.set_charpos(bytepos);
+    }
+
+    // Convert multibyte form of 8-bit characters to unibyte, walking the
+    // text before the gap and then the text after it.
+    let mut pos = buffer.beg();
+    let mut stop = buffer.gpt();
+    let mut p = buffer.beg_addr();
+    loop {
+        if pos == stop {
+            if pos == buffer.z() {
+                break;
+            }
+            p = buffer.gap_end_addr();
+            stop = buffer.z();
+        }
+
+        let byte = unsafe { *p };
+        // Same as the `CHAR_BYTE8_HEAD_P' macro.
+        if byte == 0xC0 || byte == 0xC1 {
+            let (c, len) = multibyte_char_at(unsafe { slice::from_raw_parts(p, 2) });
+            // Delete all bytes for this 8-bit character but the last
+            // one, and change the last one to the character code.
+            let extra = len as ptrdiff_t - 1;
+            unsafe {
+                del_range_2(pos, pos, pos + extra, pos + extra, false);
+                p = buffer.gap_end_addr();
+                *p = raw_byte_from_codepoint(c);
+                p = p.offset(1);
+            }
+            pos += 1;
+            stop = buffer.z();
+        } else {
+            let len = multibyte_length_by_head(byte);
+            p = unsafe { p.add(len) };
+            pos += len as ptrdiff_t;
+        }
+    }
+}
 
-    if beg > end {
-        mem::swap(&mut beg, &mut end);
+/// Turn the unibyte text of BUFFER, which must be current, into valid
+/// multibyte text.  If DECODE is true, byte sequences that already form
+/// multibyte characters are kept as they are; every other byte above 127
+/// becomes the two-byte form of an eight-bit character.
+fn to_multibyte_text(mut buffer: LispBufferRef, decode: bool) {
+    let mut pt = buffer.pt;
+
+    // Be sure not to have a multibyte sequence striding over the GAP.
+    // Ex: We change this: "...abc\302 _GAP_ \241def..."
+    //     to: "...abc _GAP_ \302\241def..."
+    if decode
+        && buffer.gpt_byte() > 1
+        && buffer.gpt_byte() < buffer.z_byte()
+        && !char_head_p(unsafe { *buffer.gap_end_addr() })
+    {
+        unsafe {
+            let mut q = buffer.gap_start_addr().offset(-1);
+            while !char_head_p(*q) && q > buffer.beg_addr() {
+                q = q.offset(-1);
+            }
+            // Same as the `LEADING_CODE_P' macro.
+            if *q & 0xC0 == 0xC0 {
+                let new_gpt =
+                    buffer.gpt_byte() - (buffer.gap_start_addr() as ptrdiff_t - q as ptrdiff_t);
+                move_gap_both(new_gpt, new_gpt);
+            }
+        }
     }
 
-    *b = LispObject::from(beg);
-    *e = LispObject::from(end);
+    // Make the buffer contents valid as multibyte by converting 8-bit
+    // characters to multibyte form.
+    let mut pos = buffer.beg();
+    let mut stop = buffer.gpt();
+    let mut p = buffer.beg_addr();
+    let mut pend = buffer.gap_start_addr();
+    loop {
+        if pos == stop {
+            if pos == buffer.z() {
+                break;
+            }
+            p = buffer.gap_end_addr();
+            pend = buffer.z_addr();
+            stop = buffer.z();
+        }
 
-    let buf = ThreadState::current_buffer();
-    let begv = buf.begv as EmacsInt;
-    let zv = buf.zv as EmacsInt;
+        let byte = unsafe { *p };
+        let valid_len = if decode {
+            let rest = unsafe { slice::from_raw_parts(p, pend as usize - p as usize) };
+            multibyte_length(rest, false)
+        } else {
+            None
+        };
 
-    if !(begv <= beg && end <= zv) {
-        args_out_of_range!(current_buffer(), start, stop);
+        if is_ascii(Codepoint::from(byte)) {
+            p = unsafe { p.offset(1) };
+            pos += 1;
+        } else if let Some(len) = valid_len {
+            p = unsafe { p.add(len) };
+            pos += len as ptrdiff_t;
+        } else {
+            let mut tmp = [0; MAX_MULTIBYTE_LENGTH];
+            let len = write_codepoint(&mut tmp, raw_byte_codepoint(byte));
+            let extra = len as ptrdiff_t - 1;
+            unsafe {
+                *p = tmp[0];
+                buffer.set_pt_both(pos + 1, pos + 1);
+                insert_1_both(
+                    tmp[1..].as_ptr() as *const c_char,
+                    extra,
+                    extra,
+                    true,
+                    false,
+                    false,
+                );
+            }
+            // Now the gap is after the just inserted data.
+            pos = buffer.gpt();
+            p = buffer.gap_end_addr();
+            if pos <= pt {
+                pt += extra;
+            }
+            pend = buffer.z_addr();
+            stop = buffer.z();
+        }
     }
-}
 
-/// Make buffer BUFFER-OR-NAME current for editing operations.
-/// BUFFER-OR-NAME may be a buffer or the name of an existing buffer.
-/// See also `with-current-buffer' when you want to make a buffer current
-/// temporarily.  This function does not display the buffer, so its effect
-/// ends when the current command terminates.  Use `switch-to-buffer' or
-/// `pop-to-buffer' to switch buffers permanently.
-/// The return value is the buffer made current.
-#[lisp_fn]
-pub fn set_buffer(buffer_or_name: LispBufferOrName) -> LispBufferRef {
-    let mut buffer: LispBufferRef = buffer_or_name.into();
-    if !buffer.is_live() {
-        error!("Selecting deleted buffer");
-    };
-    unsafe { set_buffer_internal_1(buffer.as_mut()) };
-    buffer
-}
+    // The buffer is still unibyte here, so PT is a byte position too.
+    buffer.set_pt_both(pt, pt);
 
-/// Signal a `buffer-read-only' error if the current buffer is read-only.
-/// If the text under POSITION (which defaults to point) has the
-/// `inhibit-read-only' text property set, the error will not be raised.
-#[lisp_fn(min = "0")]
-pub fn barf_if_buffer_read_only(position: Option<EmacsInt>) {
-    let pos = position.unwrap_or_else(point);
-
-    let inhibit_read_only: bool = unsafe { globals.Vinhibit_read_only.into() };
-    let prop = unsafe { Fget_text_property(LispObject::from(pos), Qinhibit_read_only, Qnil) };
+    // Do this first, so that `advance_to_char_boundary' asks the right
+    // question.  set_intervals_multibyte needs it too.
+    buffer.enable_multibyte_characters_ = Qt;
 
-    if ThreadState::current_buffer().is_read_only() && !inhibit_read_only && prop.is_nil() {
-        xsignal!(Qbuffer_read_only, current_buffer())
+    unsafe {
+        let gpt_byte = buffer.advance_to_char_boundary(buffer.gpt_byte());
+        let text = &mut *buffer.text;
+        text.gpt_byte = gpt_byte;
+        text.gpt = multibyte_chars_in_text(text.beg, gpt_byte - BEG_BYTE) + BEG;
+        text.z = multibyte_chars_in_text(buffer.gap_end_addr(), text.z_byte - gpt_byte) + text.gpt;
     }
-}
 
-/// No such buffer error.
-#[no_mangle]
-pub extern "C" fn nsberror(spec: LispObject) -> ! {
-    match spec.as_string() {
-        Some(s) => error!("No buffer named {}", s),
-        None => error!("Invalid buffer argument"),
+    let begv_byte = buffer.advance_to_char_boundary(buffer.begv_byte);
+    buffer.set_begv_both(buffer.scan_charpos(begv_byte), begv_byte);
+    let zv_byte = buffer.advance_to_char_boundary(buffer.zv_byte);
+    buffer.set_zv_both(buffer.scan_charpos(zv_byte), zv_byte);
+    let pt_byte = buffer.advance_to_char_boundary(buffer.pt_byte);
+    buffer.set_pt_both(buffer.scan_charpos(pt_byte), pt_byte);
+
+    // Counting characters directly keeps the markers, which are not yet
+    // updated, from confusing the conversion.
+    for mut marker in buffer.markers().into_iter().flat_map(LispMarkerRef::iter) {
+        let bytepos = buffer.advance_to_char_boundary(marker.bytepos);
+        marker.set_bytepos(bytepos);
+        marker.set_charpos(buffer.scan_charpos(bytepos));
     }
+
+    // Do this last, so it can calculate the new correspondences between
+    // chars and bytes.
+    unsafe { set_intervals_multibyte(true) };
 }
 
-/// These functions are for debugging overlays.
+/// Create and return an indirect buffer for buffer BASE-BUFFER, named NAME.
+/// BASE-BUFFER should be a live buffer, or the name of an existing buffer.
+/// If BASE-BUFFER is itself indirect, the new buffer is made an indirect
+/// buffer of its base buffer instead.
+/// NAME should be a string which is not the name of an existing buffer.
+/// Optional argument CLONE non-nil means preserve BASE-BUFFER's state,
+/// such as major and minor modes, in the indirect buffer.
+/// CLONE nil means the indirect buffer's state is reset to default values.
+#[lisp_fn(
+    min = "2",
+    intspec = "bMake indirect buffer (to buffer): \nBName of indirect buffer: "
+)]
+pub fn make_indirect_buffer(
+    base_buffer: LispBufferOrName,
+    name: LispStringRef,
+    clone: bool,
+) -> LispBufferRef {
+    if get_buffer(LispObject::from(name).into()).is_some() {
+        error!("Buffer name `{}' is in use", name);
+    }
 
-/// Return a pair of lists giving all the overlays of the current buffer.
-/// The car has all the overlays before the overlay center;
-/// the cdr has all the overlays after the overlay center.
-/// Recentering overlays moves overlays between these lists.
-/// The lists you get are copies, so that changing them has no effect.
-/// However, the overlays you get are the real objects that the buffer uses.
-#[lisp_fn]
-pub fn overlay_lists() -> LispObject {
-    let list_overlays = |ol: LispOverlayRef| -> LispObject {
-        ol.iter()
-            .fold(Qnil, |accum, n| LispObject::cons(n.as_lisp_obj(), accum))
+    let base = match base_buffer.as_buffer() {
+        Some(base) => base,
+        None => error!(
+            "No such buffer: `{}'",
+            LispObject::from(base_buffer).as_string_or_error()
+        ),
     };
+    if !base.is_live() {
+        error!("Base buffer has been killed");
+    }
 
-    let cur_buf = ThreadState::current_buffer();
-    let before = cur_buf.overlays_before().map_or(Qnil, &list_overlays);
-    let after = cur_buf.overlays_after().map_or(Qnil, &list_overlays);
-    unsafe { LispObject::cons(Fnreverse(before), Fnreverse(after)) }
-}
-
-fn get_truename_buffer_1(filename: LispObject) -> LispObject {
-    LiveBufferIter::new()
-        .find(|buf| {
-            let buf_truename = buf.truename();
-            buf_truename.is_string() && string_equal(buf_truename, filename)
-        })
-        .into()
-}
-
-#[no_mangle]
-pub extern "C" fn get_truename_buffer(filename: LispObject) -> LispObject {
-    get_truename_buffer_1(filename)
-}
+    if name.len_chars() == 0 {
+        error!("Empty string for buffer name is not allowed");
+    }
 
-/// If buffer B has markers to record PT, BEGV and ZV when it is not
-/// current, update these markers.
-#[no_mangle]
-pub extern "C" fn record_buffer_markers(buffer: *mut Lisp_Buffer) {
-    let buffer_ref = LispBufferRef::from_ptr(buffer as *mut c_void)
+    let mut b = LispBufferRef::from_ptr(unsafe { allocate_buffer() } as *mut c_void)
         .unwrap_or_else(|| panic!("Invalid buffer reference."));
-    let pt_marker = buffer_ref.pt_marker();
 
-    if pt_marker.is_not_nil() {
-        let begv_marker = buffer_ref.begv_marker();
-        let zv_marker = buffer_ref.zv_marker();
+    // No double indirection - if base buffer is indirect, the new buffer
+    // becomes an indirect buffer of base's base.
+    let mut base = base.base_buffer().unwrap_or(base);
+    b.base_buffer = base.as_mut();
 
-        assert!(begv_marker.is_not_nil());
-        assert!(zv_marker.is_not_nil());
+    // Use the base buffer's text object, and tell the base buffer that
+    // we share it now.
+    b.text = base.text;
+    b.indirections = -1;
+    base.indirections += 1;
+    // Always -1 for an indirect buffer.
+    b.window_count = -1;
 
-        let buffer = buffer_ref.as_lisp_obj();
-        set_marker_both(pt_marker, buffer, buffer_ref.pt, buffer_ref.pt_byte);
-        set_marker_both(begv_marker, buffer, buffer_ref.begv, buffer_ref.begv_byte);
-        set_marker_both(zv_marker, buffer, buffer_ref.zv, buffer_ref.zv_byte);
-    }
-}
+    b.set_pt_both(base.pt, base.pt_byte);
+    b.set_begv_both(base.begv, base.begv_byte);
+    b.set_zv_both(base.zv, base.zv_byte);
 
-/// If buffer B has markers to record PT, BEGV and ZV when it is not
-/// current, fetch these values into B->begv etc.
-#[no_mangle]
-pub extern "C" fn fetch_buffer_markers(buffer: *mut Lisp_Buffer) {
-    let mut buffer_ref = LispBufferRef::from_ptr(buffer as *mut c_void)
-        .unwrap_or_else(|| panic!("Invalid buffer reference."));
+    b.newline_cache = ptr::null_mut();
+    b.width_run_cache = ptr::null_mut();
+    b.bidi_paragraph_cache = ptr::null_mut();
+    b.width_table_ = Qnil;
 
-    if buffer_ref.pt_marker().is_not_nil() {
-        assert!(buffer_ref.begv_marker().is_not_nil());
-        assert!(buffer_ref.zv_marker().is_not_nil());
+    let name = unsafe { Fsubstring_no_properties(name.into(), Qnil, Qnil) };
+    b.name_ = name;
 
-        let pt_marker = buffer_ref.pt_marker().as_marker_or_error();
-        let begv_marker = buffer_ref.begv_marker().as_marker_or_error();
-        let zv_marker = buffer_ref.zv_marker().as_marker_or_error();
+    // An indirect buffer shares the undo list of its base (Bug#18180).
+    b.undo_list_ = base.undo_list_;
 
-        buffer_ref.set_pt_both(pt_marker.charpos_or_error(), pt_marker.bytepos_or_error());
-        buffer_ref.set_begv_both(
-            begv_marker.charpos_or_error(),
-            begv_marker.bytepos_or_error(),
-        );
-        buffer_ref.set_zv_both(zv_marker.charpos_or_error(), zv_marker.bytepos_or_error());
+    b.overlay_index = ptr::null_mut();
+    b.text_hash = ptr::null_mut();
+    unsafe {
+        reset_buffer(b.as_mut());
+        reset_buffer_local_variables(b.as_mut(), true);
     }
-}
 
-/// Return the buffer visiting file FILENAME (a string).
-/// The buffer's `buffer-file-name' must match exactly the expansion of FILENAME.
-/// If there is no such live buffer, return nil.
-/// See also `find-buffer-visiting'.
-#[lisp_fn]
-pub fn get_file_buffer(filename: LispObject) -> Option<LispBufferRef> {
-    verify_lisp_type!(filename, Qstringp);
-    let filename = unsafe { Fexpand_file_name(filename, Qnil) };
+    // Put this in the alist of all live buffers.
+    let buffer = b.as_lisp_obj();
+    unsafe { Vbuffer_alist = nconc2(Vbuffer_alist, list!(LispObject::cons(name, buffer))) };
 
-    // If the file name has special constructs in it,
-    // call the corresponding file handler.
-    let handler = unsafe { Ffind_file_name_handler(filename, Qget_file_buffer) };
+    b.mark_ = unsafe { Fmake_marker() };
 
-    if handler.is_not_nil() {
-        let handled_buf = call!(handler, Qget_file_buffer, filename);
-        handled_buf.as_buffer()
-    } else {
-        LiveBufferIter::new().find(|buf| {
-            let buf_filename = buf.filename();
-            buf_filename.is_string() && string_equal(buf_filename, filename)
-        })
-    }
-}
+    // The multibyte status belongs to the base buffer.
+    b.enable_multibyte_characters_ = base.enable_multibyte_characters_;
 
-/// Return the value of VARIABLE in BUFFER.
-/// If VARIABLE does not have a buffer-local binding in BUFFER, the value
-/// is the default binding of the variable.
-#[lisp_fn(name = "buffer-local-value", c_name = "buffer_local_value")]
-pub fn buffer_local_value_lisp(variable: LispObject, buffer: LispObject) -> LispObject {
-    let result = unsafe { buffer_local_value(variable, buffer) };
+    // Make sure the base buffer has markers for its narrowing.
+    if base.pt_marker().is_nil() {
+        debug_assert!(base.begv_marker().is_nil());
+        debug_assert!(base.zv_marker().is_nil());
+        base.set_narrowing_markers();
+    }
 
-    if result.eq(Qunbound) {
-        xsignal!(Qvoid_variable, variable);
+    if clone {
+        unsafe { clone_per_buffer_values(base.as_mut(), b.as_mut()) };
+        b.filename_ = Qnil;
+        b.file_truename_ = Qnil;
+        b.display_count_ = LispObject::from(0);
+        b.backed_up_ = Qnil;
+        b.auto_save_file_name_ = Qnil;
+
+        let _guard = CurrentBufferGuard::save();
+        unsafe { set_buffer_internal_1(b.as_mut()) };
+        set(intern("buffer-save-without-query"), Qnil);
+        set(intern("buffer-file-number"), Qnil);
+        set(intern("buffer-stale-function"), Qnil);
+    } else {
+        // Give the indirect buffer markers for its narrowing.
+        b.set_narrowing_markers();
     }
 
-    result
-}
+    run_buffer_list_update_hook();
 
-/// Return the base buffer of indirect buffer BUFFER.
-/// If BUFFER is not indirect, return nil.
-/// BUFFER defaults to the current buffer.
-#[lisp_fn(min = "0")]
-pub fn buffer_base_buffer(buffer: LispBufferOrCurrent) -> Option<LispBufferRef> {
-    let buf = buffer.unwrap();
-    buf.base_buffer()
+    b
 }
 
 /// Force redisplay of the current buffer's mode line and header line.
@@ -950,7 +3793,7 @@ pub fn force_mode_line_update(all: bool) -> bool {
         }
         // FIXME: This can't be right.
         current_buffer.set_prevent_redisplay_optimizations_p(true);
-    } else if 0 < unsafe { buffer_window_count(current_buffer.as_mut()) } {
+    } else if current_buffer.is_displayed() {
         unsafe {
             bset_update_mode_line(current_buffer.as_mut());
         }
@@ -997,8 +3840,8 @@ pub fn delete_overlay(overlay: LispObject) {
         // display optimizations for the affected buffer, on the basis that
         // these strings may contain newlines.  This is easier to do than to
         // check for that situation during redisplay.
-        if windows_or_buffers_changed != 0 && Foverlay_get(overlay, Qbefore_string).is_not_nil()
-            || Foverlay_get(overlay, Qafter_string).is_not_nil()
+        if windows_or_buffers_changed != 0 && ov_ref.get(Qbefore_string).is_not_nil()
+            || ov_ref.get(Qafter_string).is_not_nil()
         {
             buf_ref.set_prevent_redisplay_optimizations_p(true);
         }
@@ -1007,6 +3850,124 @@ pub fn delete_overlay(overlay: LispObject) {
     unsafe { unbind_to(count, Qnil) };
 }
 
+/// Set the endpoints of OVERLAY to BEG and END in BUFFER.
+/// If BUFFER is omitted, leave OVERLAY in the same buffer it inhabits now.
+/// If BUFFER is omitted, and OVERLAY is in no buffer, put it in the current
+/// buffer.
+#[lisp_fn(min = "3")]
+pub fn move_overlay(
+    mut overlay: LispOverlayRef,
+    beg: LispObject,
+    end: LispObject,
+    buffer: LispObject,
+) -> LispObject {
+    let old_buffer = marker_buffer(overlay.start.as_marker_or_error());
+    let mut buffer = if buffer.is_nil() {
+        old_buffer.unwrap_or_else(ThreadState::current_buffer)
+    } else {
+        buffer.as_buffer_or_error()
+    };
+
+    if !buffer.is_live() {
+        error!("Attempt to move overlay to a dead buffer");
+    }
+
+    for &pos in &[beg, end] {
+        if let Some(marker) = pos.as_marker() {
+            if marker.buffer() != Some(buffer) {
+                xsignal!(
+                    Qerror,
+                    LispObject::from("Marker points into wrong buffer"),
+                    pos
+                );
+            }
+        }
+    }
+
+    let mut beg = beg.as_fixnum_coerce_marker_or_error();
+    let mut end = end.as_fixnum_coerce_marker_or_error();
+    if beg > end {
+        mem::swap(&mut beg, &mut end);
+    }
+
+    let count = c_specpdl_index();
+    unsafe { specbind(Qinhibit_quit, Qt) };
+
+    let old_bounds = old_buffer.map(|mut ob| {
+        let bounds = (
+            overlay.start.as_marker_or_error().charpos_or_error(),
+            overlay.end.as_marker_or_error().charpos_or_error(),
+        );
+        unsafe { unchain_both(ob.as_mut(), overlay.as_lisp_obj()) };
+        bounds
+    });
+
+    // Set the overlay boundaries, which may clip them.  The markers are
+    // reused, even when the overlay changes buffers.
+    set_marker(overlay.start, beg.into(), buffer.into());
+    set_marker(overlay.end, end.into(), buffer.into());
+
+    let new_beg = overlay.start.as_marker_or_error().charpos_or_error();
+    let new_end = overlay.end.as_marker_or_error().charpos_or_error();
+
+    match (old_buffer, old_bounds) {
+        (Some(ob), Some((o_beg, o_end))) if ob == buffer => {
+            // Redisplay the area the overlay has just left, or just
+            // enclosed.
+            if o_beg == new_beg {
+                buffer.modify_overlay(o_end, new_end);
+            } else if o_end == new_end {
+                buffer.modify_overlay(o_beg, new_beg);
+            } else {
+                buffer.modify_overlay(o_beg.min(new_beg), o_end.max(new_end));
+            }
+        }
+        _ => {
+            // The overlay has changed buffers, so do a thorough
+            // redisplay: where the overlay was, and where it is going
+            // to be.
+            if let (Some(mut ob), Some((o_beg, o_end))) = (old_buffer, old_bounds) {
+                ob.modify_overlay(o_beg, o_end);
+                if overlay.has_boundary_strings() {
+                    ob.set_prevent_redisplay_optimizations_p(true);
+                }
+            }
+            buffer.modify_overlay(new_beg, new_end);
+        }
+    }
+
+    // As in `delete-overlay', the strings of the overlay may contain
+    // newlines, which redisplay optimizations don't expect.
+    if overlay.has_boundary_strings() {
+        buffer.set_prevent_redisplay_optimizations_p(true);
+    }
+
+    // Delete the overlay if it is empty after clipping and has the
+    // evaporate property.
+    if new_beg == new_end && overlay.get(Qevaporate).is_not_nil() {
+        delete_overlay(overlay.as_lisp_obj());
+        unsafe { unbind_to(count, Qnil) };
+        return Qnil;
+    }
+
+    // Put the overlay into the new buffer's overlay lists, first on the
+    // wrong list.
+    if new_end < buffer.overlay_center {
+        overlay.next = buffer.overlays_after;
+        buffer.overlays_after = overlay.as_mut();
+    } else {
+        overlay.next = buffer.overlays_before;
+        buffer.overlays_before = overlay.as_mut();
+    }
+
+    // This puts it in the right list, and in the right order.
+    unsafe {
+        recenter_overlay_lists(buffer.as_mut(), buffer.overlay_center);
+        unbind_to(count, Qnil);
+    }
+    overlay.as_lisp_obj()
+}
+
 /// Delete all overlays of BUFFER.
 /// BUFFER omitted or nil means delete all overlays of the current buffer.
 #[lisp_fn(min = "0", name = "delete-all-overlays")]
@@ -1022,18 +3983,48 @@ pub fn erase_buffer() {
     unsafe {
         Fwiden();
 
-        let mut cur_buf = ThreadState::current_buffer();
+        let cur_buf = ThreadState::current_buffer();
         del_range(cur_buf.beg(), cur_buf.z());
+    }
 
-        cur_buf.last_window_start = 1;
+    // The text is shared with the base buffer and its other indirect
+    // buffers, if any, so all of them were erased.
+    for mut buffer in ThreadState::current_buffer().text_sharers() {
+        buffer.last_window_start = 1;
 
         // Prevent warnings, or suspension of auto saving, that would happen
         // if future size is less than past size.  Use of erase-buffer
         // implies that the future text is not really related to the past text.
-        cur_buf.save_length_ = LispObject::from(0);
+        buffer.save_length_ = LispObject::from(0);
     }
 }
 
+/// Replace the accessible portion of the current buffer with TEXT.
+/// Text outside the accessible portion is left alone, so this replaces
+/// the entire contents only if the buffer is not narrowed.
+/// This is like `delete-region' from `point-min' to `point-max' followed
+/// by `insert', but it is a single change: the modification tick advances
+/// only once, and the change hooks run only once, for the replaced region.
+#[lisp_fn]
+pub fn replace_buffer_with_string(text: LispStringRef) {
+    let mut cur_buf = ThreadState::current_buffer();
+
+    // This signals an error for a read-only buffer, runs the change
+    // hooks and bumps the modification tick once.
+    unsafe {
+        replace_range(
+            cur_buf.begv,
+            cur_buf.zv,
+            text.into(),
+            true,
+            false,
+            true,
+            false,
+        )
+    };
+    cur_buf.last_window_start = 1;
+}
+
 pub unsafe fn per_buffer_idx(offset: isize) -> isize {
     let flags = &mut buffer_local_flags as *mut Lisp_Buffer as *mut LispObject;
     let obj = flags.offset(offset);
@@ -1043,11 +4034,287 @@ pub unsafe fn per_buffer_idx(offset: isize) -> isize {
 #[no_mangle]
 pub extern "C" fn rust_syms_of_buffer() {
     def_lisp_sym!(Qget_file_buffer, "get-file-buffer");
+    def_lisp_sym!(Qbuffer_undo_list, "buffer-undo-list");
+    def_lisp_sym!(QCautomatically_local, ":automatically-local");
+    def_lisp_sym!(QCpermanent_local, ":permanent-local");
+    def_lisp_sym!(QClocus, ":locus");
 
     /// Analogous to `mode-line-format', but controls the header line.
     /// The header line appears, optionally, at the top of a window;
     /// the mode line appears at the bottom.
     defvar_per_buffer!(header_line_format_, "header-line-format", Qnil);
+
+    /// List of observers of buffers whose files change on disk.
+    /// Each element is a list (BUFFER CALLBACK NOTIFIED), where NOTIFIED is
+    /// non-nil if CALLBACK has been told that BUFFER is stale.  This is
+    /// maintained by `register-stale-observer' and `run-stale-checks'; don't
+    /// modify it directly.
+    defvar_lisp!(Vstale_observers, "internal--stale-observers", Qnil);
+
+    /// Number of overlays from which a buffer's overlays are indexed.
+    /// When a buffer has at least this many overlays, `overlays-at' and
+    /// `overlays-in' build an index of them, so that later queries need not
+    /// examine every overlay.  The index is discarded whenever the buffer's
+    /// text or overlays change, and rebuilt by the next query.
+    defvar_int!(overlay_index_threshold, "overlay-index-threshold", 1000);
+}
+
+/// Compare two modification ticks, allowing for the counter having
+/// wrapped around between them.  A tick is considered greater than
+/// another if it is less than half the counter's range ahead of it.
+pub fn tick_cmp(a: EmacsInt, b: EmacsInt) -> Ordering {
+    a.wrapping_sub(b).cmp(&0)
+}
+
+#[cfg(test)]
+use crate::remacs_sys::{Lisp_Char_Table, More_Lisp_Bits, PSEUDOVECTOR_FLAG};
+
+/// Fake a char table by putting an empty one on the heap and leaking it.
+#[cfg(test)]
+fn mock_char_table() -> LispObject {
+    let mut table: Lisp_Char_Table = unsafe { mem::zeroed() };
+    table.header.size = (PSEUDOVECTOR_FLAG
+        | ((pvec_type::PVEC_CHAR_TABLE as usize) << More_Lisp_Bits::PSEUDOVECTOR_AREA_BITS))
+        as isize;
+    LispObject::tag_ptr(
+        ExternalPtr::new(Box::into_raw(Box::new(table))),
+        Lisp_Type::Lisp_Vectorlike,
+    )
+}
+
+#[test]
+fn test_per_buffer_char_table() {
+    let slots = [
+        BufferCharTableSlot::Syntax,
+        BufferCharTableSlot::Category,
+        BufferCharTableSlot::Downcase,
+        BufferCharTableSlot::Upcase,
+        BufferCharTableSlot::CaseCanon,
+        BufferCharTableSlot::CaseEqv,
+    ];
+    let tables: Vec<LispObject> = slots.iter().map(|_| mock_char_table()).collect();
+
+    // A new buffer gets a table of each kind except a display table.
+    let mut buffer: Lisp_Buffer = unsafe { mem::zeroed() };
+    buffer.syntax_table_ = tables[0];
+    buffer.category_table_ = tables[1];
+    buffer.downcase_table_ = tables[2];
+    buffer.upcase_table_ = tables[3];
+    buffer.case_canon_table_ = tables[4];
+    buffer.case_eqv_table_ = tables[5];
+    buffer.display_table_ = Qnil;
+    let mut buffer: LispBufferRef = ExternalPtr::new(Box::into_raw(Box::new(buffer)));
+
+    for (&slot, &table) in slots.iter().zip(&tables) {
+        assert_eq!(
+            buffer
+                .per_buffer_char_table(slot)
+                .map(LispCharTableRef::as_lisp_obj),
+            Some(table)
+        );
+    }
+    assert!(buffer
+        .per_buffer_char_table(BufferCharTableSlot::Display)
+        .is_none());
+
+    let display_table = mock_char_table();
+    buffer.display_table_ = display_table;
+    assert_eq!(
+        buffer
+            .per_buffer_char_table(BufferCharTableSlot::Display)
+            .map(LispCharTableRef::as_lisp_obj),
+        Some(display_table)
+    );
+}
+
+#[test]
+fn test_tick_cmp() {
+    assert_eq!(Ordering::Equal, tick_cmp(42, 42));
+    assert_eq!(Ordering::Less, tick_cmp(1, 2));
+    assert_eq!(Ordering::Greater, tick_cmp(2, 1));
+    // Across the wraparound, the wrapped tick is the newer one.
+    assert_eq!(
+        Ordering::Greater,
+        tick_cmp(EmacsInt::min_value(), EmacsInt::max_value())
+    );
+    assert_eq!(
+        Ordering::Less,
+        tick_cmp(EmacsInt::max_value(), EmacsInt::min_value())
+    );
+    assert_eq!(
+        Ordering::Greater,
+        tick_cmp(EmacsInt::min_value() + 5, EmacsInt::max_value() - 5)
+    );
+    assert_eq!(
+        Ordering::Equal,
+        tick_cmp(EmacsInt::min_value(), EmacsInt::min_value())
+    );
+}
+
+#[test]
+fn test_mark_freshly_loaded() {
+    let mut text: buffer_text = unsafe { mem::zeroed() };
+    text.modiff = 7;
+    text.save_modiff = 3;
+    let mut buffer: Lisp_Buffer = unsafe { mem::zeroed() };
+    buffer.text = Box::into_raw(Box::new(text));
+    buffer.filename_ = Qnil;
+    // Stands in for a list of undo entries; only its identity matters.
+    buffer.undo_list_ = LispObject::from_fixnum(1);
+    let mut buffer: LispBufferRef = ExternalPtr::new(Box::into_raw(Box::new(buffer)));
+    assert!(buffer_modified_p(LispBufferOrCurrent(buffer)));
+
+    buffer.mark_freshly_loaded();
+    assert!(!buffer_modified_p(LispBufferOrCurrent(buffer)));
+    assert_eq!(buffer.undo_list_, Qnil);
+
+    // A buffer that doesn't record undo keeps it that way.
+    unsafe { (*buffer.text).modiff += 1 };
+    buffer.undo_list_ = Qt;
+    buffer.mark_freshly_loaded();
+    assert!(!buffer_modified_p(LispBufferOrCurrent(buffer)));
+    assert_eq!(buffer.undo_list_, Qt);
+}
+
+#[test]
+fn test_capture_and_restore_local_variables() {
+    use crate::{
+        functions::mock_cons,
+        remacs_sys::{Qcase_fold_search, Qleft_margin, Qoverwrite_mode},
+    };
+
+    let left_margin = (Qleft_margin, LispObject::from_fixnum(4));
+    let mut buffer: Lisp_Buffer = unsafe { mem::zeroed() };
+    // `overwrite-mode' is local but unbound.
+    buffer.local_var_alist_ = mock_cons(
+        mock_cons(Qcase_fold_search, Qnil),
+        mock_cons(
+            mock_cons(left_margin.0, left_margin.1),
+            mock_cons(Qoverwrite_mode, Qnil),
+        ),
+    );
+    let mut buffer: LispBufferRef = ExternalPtr::new(Box::into_raw(Box::new(buffer)));
+
+    let locals = buffer.capture_local_variables();
+    assert_eq!(locals, vec![(Qcase_fold_search, Qnil), left_margin]);
+
+    // As if by `kill-all-local-variables'.
+    buffer.local_var_alist_ = Qnil;
+    assert!(buffer.capture_local_variables().is_empty());
+
+    let kept: Vec<_> = locals
+        .into_iter()
+        .filter(|&(variable, _)| variable.eq(Qleft_margin))
+        .collect();
+    buffer.restore_local_variables(&kept);
+    assert_eq!(buffer.capture_local_variables(), vec![left_margin]);
+}
+
+/// Fake a buffer whose text is BEFORE and AFTER, with a gap of GAP
+/// bytes between them, by putting it on the heap and leaking it.
+#[cfg(test)]
+fn mock_buffer(before: &[u8], gap: usize, after: &[u8], multibyte: bool) -> LispBufferRef {
+    let mut bytes = before.to_vec();
+    bytes.extend(std::iter::repeat(0).take(gap));
+    bytes.extend_from_slice(after);
+    bytes.push(0);
+
+    let mut text: buffer_text = unsafe { mem::zeroed() };
+    text.beg = Box::leak(bytes.into_boxed_slice()).as_mut_ptr();
+    text.gpt_byte = BEG_BYTE + before.len() as ptrdiff_t;
+    text.z_byte = text.gpt_byte + after.len() as ptrdiff_t;
+    text.gap_size = gap as ptrdiff_t;
+
+    let mut buffer: Lisp_Buffer = unsafe { mem::zeroed() };
+    buffer.text = Box::into_raw(Box::new(text));
+    buffer.enable_multibyte_characters_ = if multibyte { Qt } else { Qnil };
+    ExternalPtr::new(Box::into_raw(Box::new(buffer)))
+}
+
+#[test]
+fn test_as_rust_string() {
+    // "héllo", with the gap in the middle of the text.
+    let buffer = mock_buffer(b"h\xC3\xA9l", 5, b"lo", true);
+    assert_eq!("h\u{e9}llo", buffer.as_rust_string());
+    // The gap at either end.
+    assert_eq!(
+        "h\u{e9}",
+        mock_buffer(b"", 3, b"h\xC3\xA9", true).as_rust_string()
+    );
+    assert_eq!(
+        "h\u{e9}",
+        mock_buffer(b"h\xC3\xA9", 3, b"", true).as_rust_string()
+    );
+    // Raw bytes have no Unicode equivalent.
+    assert_eq!(
+        "a\u{fffd}",
+        mock_buffer(b"a\xC0\x80", 2, b"", true).as_rust_string()
+    );
+    // Unibyte text is read as Latin-1.
+    let buffer = mock_buffer(b"h\xE9", 4, b"\xFFo", false);
+    assert_eq!("h\u{e9}\u{ff}o", buffer.as_rust_string());
+    assert_eq!("", mock_buffer(b"", 1, b"", false).as_rust_string());
+}
+
+#[test]
+fn test_chars_rev() {
+    // "aéb€c", with the gap after the "é".
+    let buffer = mock_buffer(b"a\xC3\xA9", 3, b"b\xE2\x82\xACc", true);
+    let chars: Vec<_> = buffer.chars_rev(buffer.z_byte()).collect();
+    assert_eq!(
+        chars,
+        vec![
+            (5, 8, 'c' as c_int),
+            (4, 5, '\u{20ac}' as c_int),
+            (3, 4, 'b' as c_int),
+            (2, 2, '\u{e9}' as c_int),
+            (1, 1, 'a' as c_int),
+        ]
+    );
+    // From the middle of the "€", which is the first character found.
+    let chars: Vec<_> = buffer.chars_rev(6).map(|(_, _, c)| c).collect();
+    assert_eq!(chars, vec![0x20ac, 'b' as c_int, 0xe9, 'a' as c_int]);
+    assert_eq!(buffer.chars_rev(BEG_BYTE).next(), None);
+
+    // In a unibyte buffer, every byte is a character.
+    let buffer = mock_buffer(b"a\xC3", 2, b"\xA9", false);
+    let chars: Vec<_> = buffer.chars_rev(buffer.z_byte()).collect();
+    assert_eq!(
+        chars,
+        vec![(3, 3, 0xa9), (2, 2, 0xc3), (1, 1, 'a' as c_int)]
+    );
+}
+
+#[test]
+fn test_specbind_guard() {
+    use crate::functions::mock_specpdl;
+
+    let outer = SpecbindGuard::inhibit_read_only();
+    assert!(mock_specpdl() == vec![(Qinhibit_read_only, Qt)]);
+    {
+        let _inner = SpecbindGuard::inhibit_modification_hooks();
+        assert!(
+            mock_specpdl() == vec![(Qinhibit_read_only, Qt), (Qinhibit_modification_hooks, Qt)]
+        );
+    }
+    assert!(mock_specpdl() == vec![(Qinhibit_read_only, Qt)]);
+    drop(outer);
+    assert!(mock_specpdl().is_empty());
+
+    let _guard = SpecbindGuard::bind(Qinhibit_quit, Qnil);
+    assert!(mock_specpdl() == vec![(Qinhibit_quit, Qnil)]);
+}
+
+#[test]
+fn test_modiff_cmp() {
+    let buffer = mock_buffer(b"", 1, b"", false);
+    unsafe { (*buffer.text).modiff = 10 };
+    assert_eq!(Ordering::Equal, buffer.modiff_cmp(10));
+    assert_eq!(Ordering::Greater, buffer.modiff_cmp(9));
+    assert_eq!(Ordering::Less, buffer.modiff_cmp(11));
+    // The buffer is newer than a tick saved just before wraparound.
+    unsafe { (*buffer.text).modiff = EmacsInt::min_value() };
+    assert_eq!(Ordering::Greater, buffer.modiff_cmp(EmacsInt::max_value()));
 }
 
 include!(concat!(env!("OUT_DIR"), "/buffers_exports.rs"));
@@ -1,19 +1,25 @@
 //! Case conversion functions.
 use std::ffi::CString;
 
+use libc::{c_int, ptrdiff_t};
+
 use remacs_macros::lisp_fn;
 
 use crate::{
+    buffers::validate_region,
+    editfns::region_bounds,
     keymap::Ctl,
     lisp::defsubr,
     lisp::LispObject,
     lists::put,
+    multibyte::Codepoint,
     obarray::intern,
     remacs_sys::EmacsInt,
-    remacs_sys::{case_action, casify_object, casify_region, casify_region_nil},
+    remacs_sys::{case_action, casify_object, casify_region, casify_region_nil, syntaxcode},
     remacs_sys::{control_x_map, initial_define_key, meta_map, scan_words, set_point},
+    remacs_sys::{downcase as downcase_char, upcase as upcase_char},
+    remacs_sys::{uniprop_table, Qtitlecase},
     remacs_sys::{Qdisabled, Qnil, Qt},
-    symbols::symbol_value,
     threads::ThreadState,
 };
 
@@ -56,12 +62,64 @@ pub fn capitalize(object: LispObject) -> LispObject {
 
 /// Convert the region to capitalized form.
 /// This means that each word's first character is converted to either
-/// title case or upper case, and the rest to lower case.  In
-/// programs, give two arguments, the starting and ending character
-/// positions to operate on.
-#[lisp_fn(intspec = "r")]
-pub fn capitalize_region(beg: LispObject, end: LispObject) -> LispObject {
-    unsafe { casify_region_nil(case_action::CASE_CAPITALIZE, beg, end) }
+/// title case or upper case, and the rest to lower case.  In programs,
+/// give two arguments, the starting and ending character positions to
+/// operate on.  Words are delimited according to the syntax table.
+#[lisp_fn(
+    min = "2",
+    intspec = "(list (region-beginning) (region-end) (region-noncontiguous-p))"
+)]
+pub fn capitalize_region(beg: LispObject, end: LispObject, region_noncontiguous_p: LispObject) {
+    if region_noncontiguous_p.is_nil() {
+        capitalize_region_1(beg, end);
+    } else {
+        for (beg, end) in region_bounds(Qt) {
+            capitalize_region_1(beg.into(), end.into());
+        }
+    }
+}
+
+fn capitalize_region_1(mut beg: LispObject, mut end: LispObject) {
+    unsafe { validate_region(&mut beg, &mut end) };
+    let beg = beg.as_fixnum_or_error() as ptrdiff_t;
+    let end = end.as_fixnum_or_error() as ptrdiff_t;
+
+    // Find the word constituents before touching the text, since casing
+    // may change the byte length of characters.
+    let buffer = ThreadState::current_buffer();
+    let mut pos_byte = buffer.charpos_to_bytepos(beg);
+    let words: Vec<bool> = (beg..end)
+        .map(|_| {
+            let is_word = buffer.char_syntax_class_at(pos_byte) == syntaxcode::Sword as u8;
+            pos_byte = buffer.inc_pos(pos_byte);
+            is_word
+        })
+        .collect();
+
+    // Initials take their title case form where there is one, as in ǅ.
+    let titlecase = unsafe { uniprop_table(Qtitlecase) }.as_char_table();
+    let to_title = |c: Codepoint| {
+        titlecase
+            .map(|table| table.get(c as isize))
+            .filter(|title| title.is_character())
+            .map_or_else(
+                || unsafe { upcase_char(c as c_int) as Codepoint },
+                |title| title.as_character_or_error(),
+            )
+    };
+
+    let mut words = words.into_iter();
+    let mut inword = false;
+    buffer.transform_region(beg, end, |c| {
+        let is_word = words.next().unwrap_or(false);
+        let cased = match (is_word, inword) {
+            (false, _) => c,
+            (true, false) => to_title(c),
+            (true, true) => unsafe { downcase_char(c as c_int) as Codepoint },
+        };
+        inword = is_word;
+        cased
+    });
 }
 
 /// Capitalize from point to the end of word, moving over.
@@ -186,15 +244,9 @@ fn casefiddle_region(
     if !region_noncontiguous_p {
         unsafe { casify_region_nil(action, beg, end) }
     } else {
-        let bounds = call!(
-            symbol_value(intern("region-extract-function")),
-            LispObject::from(intern("bounds"))
-        );
-
-        bounds.iter_cars_unchecked().for_each(|elt| {
-            let (car, cdr) = elt.as_cons_or_error().as_tuple();
-            unsafe { casify_region_nil(action, car, cdr) };
-        });
+        for (beg, end) in region_bounds(Qt) {
+            unsafe { casify_region_nil(action, beg.into(), end.into()) };
+        }
 
         Qnil
     }
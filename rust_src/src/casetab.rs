@@ -3,6 +3,8 @@
 use remacs_macros::lisp_fn;
 
 use crate::{
+    buffers::BufferCharTableSlot,
+    chartable::LispCharTableRef,
     lisp::{defsubr, LispObject},
     objects::eq,
     remacs_sys::{set_case_table, Qcase_table, Vascii_downcase_table},
@@ -36,8 +38,8 @@ pub fn case_table_p(object: LispObject) -> bool {
 
 /// Return the case table of the current buffer.
 #[lisp_fn]
-pub fn current_case_table() -> LispObject {
-    ThreadState::current_buffer().downcase_table_
+pub fn current_case_table() -> Option<LispCharTableRef> {
+    ThreadState::current_buffer().per_buffer_char_table(BufferCharTableSlot::Downcase)
 }
 
 /// Return the standard case table.
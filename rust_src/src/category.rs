@@ -1,10 +1,8 @@
 //! Routines to deal with category tables.
 
 use crate::{
-    chartable::LispCharTableRef,
-    lisp::{defsubr, LispObject},
-    remacs_sys::Qcategory_table,
-    threads::ThreadState,
+    buffers::BufferCharTableSlot, chartable::LispCharTableRef, lisp::defsubr,
+    remacs_sys::Qcategory_table, threads::ThreadState,
 };
 
 use remacs_macros::lisp_fn;
@@ -18,9 +16,8 @@ pub fn category_table_p(arg: Option<LispCharTableRef>) -> bool {
 /// Return the current category table.
 /// This is the one specified by the current buffer.
 #[lisp_fn]
-pub fn category_table() -> LispObject {
-    let buffer_ref = ThreadState::current_buffer();
-    buffer_ref.category_table_
+pub fn category_table() -> Option<LispCharTableRef> {
+    ThreadState::current_buffer().per_buffer_char_table(BufferCharTableSlot::Category)
 }
 
 include!(concat!(env!("OUT_DIR"), "/category_exports.rs"));
@@ -43,21 +43,7 @@ pub fn char_head_p(byte: c_uchar) -> bool {
 ///
 /// Can be used instead of the `DEC_POS` macro.
 pub unsafe fn dec_pos(pos_byte: ptrdiff_t) -> ptrdiff_t {
-    let buffer_ref = ThreadState::current_buffer();
-
-    let mut new_pos = pos_byte - 1;
-    let mut offset = new_pos - buffer_ref.beg_byte();
-    if new_pos >= buffer_ref.gpt_byte() {
-        offset += buffer_ref.gap_size();
-    }
-    let mut chp = buffer_ref.beg_addr().offset(offset);
-
-    while !char_head_p(*chp) {
-        chp = chp.offset(-1);
-        new_pos -= 1;
-    }
-
-    new_pos
+    ThreadState::current_buffer().dec_pos(pos_byte)
 }
 
 /// Return the character of the maximum code.
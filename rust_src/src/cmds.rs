@@ -7,9 +7,13 @@ use std::ffi::CString;
 use remacs_macros::lisp_fn;
 
 use crate::{
+    buffers::LispBufferRef,
     character::{self, characterp},
     data::set,
-    editfns::{line_beginning_position, line_end_position, preceding_char},
+    editfns::{
+        goto_char, insert_and_inherit_lisp, line_beginning_position, line_end_position,
+        preceding_char, region_to_string,
+    },
     frames::selected_frame,
     keymap::{current_global_map, Ctl},
     lisp::defsubr,
@@ -22,17 +26,18 @@ use crate::{
     obarray::intern,
     remacs_sys::EmacsInt,
     remacs_sys::{
-        bitch_at_user, concat2, current_column, del_range, frame_make_pointer_invisible, globals,
-        initial_define_key, insert_and_inherit, memory_full, replace_range, run_hook,
-        scan_newline_from_point, set_point, set_point_both, syntax_property, syntaxcode,
-        translate_char,
+        bitch_at_user, concat2, current_column, del_range, find_symbol_value,
+        frame_make_pointer_invisible, globals, initial_define_key, insert, insert_and_inherit,
+        insert_char, memory_full, replace_range, run_hook, scan_newline_from_point, set_point,
+        set_point_both, syntax_property, syntaxcode, translate_char,
     },
-    remacs_sys::{Fchar_width, Fget, Fmake_string, Fmove_to_column},
+    remacs_sys::{Fchar_width, Fget, Findent_to, Fmake_string, Fmove_to_column},
     remacs_sys::{
-        Qbeginning_of_buffer, Qend_of_buffer, Qexpand_abbrev, Qinternal_auto_fill,
+        Qbeginning_of_buffer, Qend_of_buffer, Qexpand_abbrev, Qfill_prefix, Qinternal_auto_fill,
         Qkill_forward_chars, Qnil, Qoverwrite_mode_binary, Qpost_self_insert_hook,
         Qundo_auto__this_command_amalgamating, Qundo_auto_amalgamate,
     },
+    strings::string_equal,
     threads::ThreadState,
 };
 
@@ -500,6 +505,126 @@ fn internal_self_insert(mut c: Codepoint, n: usize) -> EmacsInt {
     hairy
 }
 
+impl LispBufferRef {
+    /// Join the line at point to the previous line, or to the following
+    /// line if FOLLOWING, as `delete-indentation' does.  This buffer must
+    /// be the current buffer.
+    pub fn join_line(self, following: bool) {
+        unsafe { set_point(line_beginning_position(None) as isize) };
+        if following {
+            let (mut pos, mut pos_byte) = (0, 0);
+            unsafe {
+                scan_newline_from_point(1, &mut pos, &mut pos_byte);
+                set_point_both(pos, pos_byte);
+            }
+        }
+        if preceding_char() != EmacsInt::from(b'\n') {
+            return;
+        }
+
+        let join = self.pt - 1;
+        unsafe { del_range(join, self.pt) };
+
+        // If the second line started with the fill prefix, delete the
+        // prefix.
+        if let Some(prefix) = unsafe { find_symbol_value(Qfill_prefix) }.as_string() {
+            let end = join + prefix.len_chars();
+            if end <= self.zv && string_equal(region_to_string(join, end, false), prefix.into()) {
+                unsafe { del_range(join, end) };
+            }
+        }
+
+        self.fixup_whitespace();
+    }
+
+    /// Delete the spaces and tabs around point, then leave one space or
+    /// none, according to the context, as `fixup-whitespace' does.
+    /// Point stays before the space, if any.
+    fn fixup_whitespace(self) {
+        let char_at = |pos| self.fetch_char(self.charpos_to_bytepos(pos));
+        let syntax_at = |pos| self.char_syntax_class_at(self.charpos_to_bytepos(pos));
+        let is_blank = |c| c == libc::c_int::from(b' ') || c == libc::c_int::from(b'\t');
+
+        let mut beg = self.pt;
+        while beg > self.begv && is_blank(char_at(beg - 1)) {
+            beg -= 1;
+        }
+        let mut end = self.pt;
+        while end < self.zv && is_blank(char_at(end)) {
+            end += 1;
+        }
+        if beg < end {
+            unsafe { del_range(beg, end) };
+        }
+
+        let pos = beg;
+        let at_bol = pos == self.begv || char_at(pos - 1) == libc::c_int::from(b'\n');
+        let at_eol = pos == self.zv || char_at(pos) == libc::c_int::from(b'\n');
+        if at_bol || at_eol {
+            return;
+        }
+        let after = syntax_at(pos);
+        let before = syntax_at(pos - 1);
+        if after == syntaxcode::Sclose as u8
+            || before == syntaxcode::Sopen as u8
+            || before == syntaxcode::Squote as u8
+        {
+            return;
+        }
+        unsafe {
+            set_point(pos);
+            insert(b" ".as_ptr() as *const libc::c_char, 1);
+            set_point(pos);
+        }
+    }
+}
+
+/// Join this line to previous and fix up whitespace at join.
+/// If there is a fill prefix, delete it from the beginning of this line.
+/// With argument, join this line to following line.
+#[lisp_fn(min = "0", intspec = "*P")]
+pub fn delete_indentation(arg: LispObject) {
+    ThreadState::current_buffer().join_line(arg.is_not_nil());
+}
+
+/// Insert a newline and leave point before it.
+/// If there is a fill prefix and/or a `left-margin', insert them on
+/// the new line if the line would have been blank.
+/// With arg N, insert N newlines.  If N is negative, insert none.
+#[lisp_fn(min = "0", intspec = "*p")]
+pub fn open_line(n: Option<EmacsInt>) {
+    let n = n.unwrap_or(1).max(0);
+    let buffer = ThreadState::current_buffer();
+    let bolp = || buffer.pt == buffer.begv || preceding_char() == EmacsInt::from(b'\n');
+    let current_left_margin = || call!(LispObject::from(intern("current-left-margin")));
+
+    let do_fill_prefix = bolp() && unsafe { find_symbol_value(Qfill_prefix) }.is_string();
+    let do_left_margin = bolp() && current_left_margin().as_fixnum_or_error() > 0;
+    let loc = LispObject::from(buffer.pt);
+
+    for _ in 0..n {
+        unsafe { insert_char(libc::c_int::from(b'\n')) };
+    }
+    goto_char(loc);
+
+    if do_fill_prefix || do_left_margin {
+        for _ in 0..n {
+            if bolp() {
+                if do_left_margin {
+                    unsafe { Findent_to(current_left_margin(), Qnil) };
+                }
+                if do_fill_prefix {
+                    insert_and_inherit_lisp(&[unsafe { find_symbol_value(Qfill_prefix) }]);
+                }
+            }
+            forward_line(Some(1));
+        }
+        goto_char(loc);
+        // Necessary in case a margin or prefix was inserted.
+        end_of_line(None);
+    }
+}
+
 // module initialization
 
 #[no_mangle]
@@ -538,6 +663,7 @@ pub extern "C" fn syms_of_cmds() {
     def_lisp_sym!(Qoverwrite_mode_binary, "overwrite-mode-binary");
     def_lisp_sym!(Qexpand_abbrev, "expand-abbrev");
     def_lisp_sym!(Qpost_self_insert_hook, "post-self-insert-hook");
+    def_lisp_sym!(Qfill_prefix, "fill-prefix");
 
     /// Hook run at the end of `self-insert-command'.
     /// This is run after inserting the character.
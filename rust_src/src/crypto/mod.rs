@@ -5,18 +5,23 @@ use md5;
 use sha1;
 use sha2::{Digest, Sha224, Sha256, Sha384, Sha512};
 use std;
+use std::ffi::OsStr;
+use std::fs;
 use std::slice;
 
 use remacs_macros::lisp_fn;
 
 use crate::{
-    buffers::{buffer_file_name, LispBufferOrName, LispBufferRef},
+    buffers::{
+        buffer_file_name, CurrentBufferGuard, LispBufferOrCurrent, LispBufferOrName, LispBufferRef,
+    },
     lisp::defsubr,
     lisp::LispObject,
     multibyte::LispStringRef,
     remacs_sys::{
-        code_convert_string, extract_data_from_object, preferred_coding_system,
-        string_char_to_byte, validate_subarray, Fcoding_system_p,
+        code_convert_string, encode_file_name, extract_data_from_object, preferred_coding_system,
+        record_unwind_protect, save_restriction_restore, save_restriction_save,
+        string_char_to_byte, validate_subarray, Fcoding_system_p, Ffind_file_name_handler, Fwiden,
     },
     remacs_sys::{
         current_thread, make_buffer_string, record_unwind_current_buffer, set_buffer_internal,
@@ -24,8 +29,8 @@ use crate::{
     remacs_sys::{globals, Ffind_operation_coding_system, Flocal_variable_p},
     remacs_sys::{make_specified_string, make_uninit_string, EmacsInt},
     remacs_sys::{
-        Qbuffer_file_coding_system, Qcoding_system_error, Qmd5, Qnil, Qraw_text, Qsha1, Qsha224,
-        Qsha256, Qsha384, Qsha512, Qstringp, Qwrite_region,
+        Qbuffer_file_coding_system, Qcoding_system_error, Qinsert_file_contents, Qmd5, Qnil,
+        Qraw_text, Qsha1, Qsha224, Qsha256, Qsha384, Qsha512, Qstringp, Qt, Qwrite_region,
     },
     symbols::{fboundp, symbol_name},
     threads::ThreadState,
@@ -475,4 +480,65 @@ pub fn buffer_hash(buffer_or_name: Option<LispBufferOrName>) -> LispObject {
     digest
 }
 
+/// Return t if the text of BUFFER is identical to the file it visits.
+/// BUFFER defaults to the current buffer.  The whole text, regardless of
+/// any narrowing, is encoded with the coding system that saving the
+/// buffer would use, and compared with the file's contents on disk.  The
+/// buffer's modified flag is not consulted, so a buffer whose edits have
+/// all been undone by hand matches its file again.
+///
+/// Return nil if BUFFER is not visiting a file, the file cannot be read,
+/// or the file has a file name handler, as remote files do.
+#[lisp_fn(min = "0")]
+pub fn buffer_matches_file_p(buffer: LispBufferOrCurrent) -> bool {
+    let mut buf = buffer.unwrap();
+    let filename = match buf.filename().as_string() {
+        Some(filename) => filename,
+        None => return false,
+    };
+    if unsafe { Ffind_file_name_handler(filename.into(), Qinsert_file_contents) }.is_not_nil() {
+        return false;
+    }
+    let encoded_name = unsafe { encode_file_name(filename.into()) }.as_string_or_error();
+    let contents = match read_file_bytes(encoded_name) {
+        Some(contents) => contents,
+        None => return false,
+    };
+
+    // `extract_data_from_object' only takes text from the accessible
+    // portion, so widen BUFFER while it runs.
+    let _guard = CurrentBufferGuard::save();
+    unsafe {
+        set_buffer_internal(buf.as_mut());
+        record_unwind_protect(Some(save_restriction_restore), save_restriction_save());
+        Fwiden();
+    }
+
+    let spec = list!(LispObject::from(buf), Qnil, Qnil, Qnil, Qt);
+    let mut start_byte: ptrdiff_t = 0;
+    let mut end_byte: ptrdiff_t = 0;
+    let input = unsafe { extract_data_from_object(spec, &mut start_byte, &mut end_byte) };
+    if input.is_null() {
+        return false;
+    }
+    let text = unsafe {
+        slice::from_raw_parts(
+            input.offset(start_byte) as *const u8,
+            (end_byte - start_byte) as usize,
+        )
+    };
+    text == &contents[..]
+}
+
+#[cfg(unix)]
+fn read_file_bytes(filename: LispStringRef) -> Option<Vec<u8>> {
+    use std::os::unix::ffi::OsStrExt;
+    fs::read(OsStr::from_bytes(filename.as_slice())).ok()
+}
+
+#[cfg(not(unix))]
+fn read_file_bytes(filename: LispStringRef) -> Option<Vec<u8>> {
+    fs::read(filename.to_string()).ok()
+}
+
 include!(concat!(env!("OUT_DIR"), "/crypto_exports.rs"));
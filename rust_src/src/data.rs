@@ -23,7 +23,7 @@ use crate::{
     },
     remacs_sys::{buffer_local_flags, per_buffer_default, symbol_redirect},
     remacs_sys::{pvec_type, BoolVectorOp, EmacsInt, Lisp_Misc_Type, Lisp_Type, Set_Internal_Bind},
-    remacs_sys::{Fdelete, Ffset, Fget, Fpurecopy},
+    remacs_sys::{Fdelete, Ffset, Fget, Fmake_local_variable, Fpurecopy},
     remacs_sys::{Lisp_Buffer, Lisp_Subr_Lang},
     remacs_sys::{
         Qargs_out_of_range, Qarrayp, Qautoload, Qbool_vector, Qbuffer, Qchar_table, Qchoice,
@@ -685,6 +685,15 @@ pub fn set(symbol: LispSymbolRef, newval: LispObject) -> LispObject {
     newval
 }
 
+/// Make VARIABLE buffer-local in the current buffer and set it to VALUE.
+/// This is the same as `(set (make-local-variable VARIABLE) VALUE)'; the
+/// default value of VARIABLE is not affected.  Return VALUE.
+#[lisp_fn]
+pub fn set_buffer_local(variable: LispSymbolRef, value: LispObject) -> LispObject {
+    unsafe { Fmake_local_variable(LispObject::from(variable)) };
+    set(variable, value)
+}
+
 /// Set SYMBOL's default value to VALUE.  SYMBOL and VALUE are evaluated.
 /// The default value is seen in buffers that do not have their own
 /// values for this variable.
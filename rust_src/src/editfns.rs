@@ -4,41 +4,56 @@ use std;
 use std::ptr;
 
 use libc;
-use libc::{c_int, c_uchar, ptrdiff_t};
+use libc::{c_char, c_int, c_uchar, ptrdiff_t};
 
 use remacs_macros::lisp_fn;
 
 use crate::{
-    buffers::current_buffer,
-    buffers::{LispBufferOrCurrent, LispBufferOrName, LispBufferRef, BUF_BYTES_MAX},
+    buffers::{barf_if_buffer_read_only, current_buffer},
+    buffers::{
+        validate_region, LispBufferOrCurrent, LispBufferOrName, LispBufferRef, BUF_BYTES_MAX,
+    },
     character::{char_head_p, dec_pos},
+    data::set_buffer_local,
     eval::progn,
     lisp::{defsubr, LispObject},
+    lists::{assq, car, car_safe, cdr, delq, list},
     marker::{
-        buf_bytepos_to_charpos, buf_charpos_to_bytepos, marker_position_lisp, point_marker,
-        set_point_from_marker,
+        buf_charpos_to_bytepos, marker_position, marker_position_lisp, point_marker,
+        point_max_marker, point_min_marker, set_point_from_marker,
     },
     multibyte::{
-        is_single_byte_char, multibyte_char_at, raw_byte_codepoint, unibyte_to_char,
+        char_to_byte8, is_single_byte_char, multibyte_char_at, raw_byte_codepoint, unibyte_to_char,
         write_codepoint, MAX_MULTIBYTE_LENGTH,
     },
     multibyte::{Codepoint, LispStringRef},
     numbers::LispNumber,
-    remacs_sys::EmacsInt,
+    obarray::intern,
     remacs_sys::{
-        buffer_overflow, build_string, current_message, downcase, find_before_next_newline,
-        find_newline, get_char_property_and_overlay, globals, insert, insert_and_inherit,
-        insert_from_buffer, make_buffer_string_both, make_save_obj_obj_obj_obj,
-        make_string_from_bytes, maybe_quit, message1, record_unwind_current_buffer,
-        record_unwind_protect, save_excursion_restore, save_restriction_restore,
-        save_restriction_save, scan_newline_from_point, set_buffer_internal_1, set_point,
-        set_point_both, unbind_to, update_buffer_properties,
+        buffer_overflow, build_string, copy_intervals_to_string, current_message, del_range,
+        del_range_1, downcase, find_before_next_newline, find_newline,
+        get_char_property_and_overlay, globals, insert, insert_and_inherit, insert_before_markers,
+        insert_from_buffer, insert_from_string, insert_from_string_before_markers,
+        invalidate_current_column, make_buffer_string, make_buffer_string_both,
+        make_save_obj_obj_obj_obj, make_string_from_bytes, make_uninit_multibyte_string,
+        make_uninit_string, maybe_quit, message1, modify_text, prepare_to_modify_buffer,
+        record_delete, record_insert, record_unwind_current_buffer, record_unwind_protect,
+        replace_range_2, save_excursion_restore, save_restriction_restore, save_restriction_save,
+        scan_newline_from_point, set_buffer_internal_1, set_point, set_point_both,
+        signal_after_change, unbind_to, update_buffer_properties, update_compositions,
     },
+    remacs_sys::{EmacsInt, CHECK_ALL},
     remacs_sys::{
         Fadd_text_properties, Fcopy_sequence, Fformat_message, Fget_pos_property,
-        Fnext_single_char_property_change, Fprevious_single_char_property_change, Fx_popup_dialog,
+        Fnarrow_to_region, Fnext_property_change, Fnext_single_char_property_change,
+        Fprevious_single_char_property_change, Freplace_buffer_contents, Ftext_properties_at,
+        Fwiden, Fx_popup_dialog,
+    },
+    remacs_sys::{
+        Qboundary, Qchar_or_string_p, Qfield, Qinteger_or_marker_p, Qmark_inactive, Qnil, Qonly,
+        Qt, Quser_error,
     },
-    remacs_sys::{Qboundary, Qfield, Qinteger_or_marker_p, Qmark_inactive, Qnil, Qt},
+    symbols::symbol_value,
     textprop::get_char_property,
     threads::{c_specpdl_index, ThreadState},
     util::clip_to_bounds,
@@ -150,6 +165,36 @@ pub fn region_end() -> EmacsInt {
     region_limit(false)
 }
 
+/// Return the bounds of the region as a list of (BEG, END) pairs.
+/// When NONCONTIGUOUS is nil this is the single pair given by
+/// `region-beginning' and `region-end'.  Otherwise the pairs come from
+/// calling `region-extract-function' with `bounds', so a rectangular
+/// region yields one pair per line.
+pub fn region_bounds(noncontiguous: LispObject) -> Vec<(ptrdiff_t, ptrdiff_t)> {
+    if noncontiguous.is_nil() {
+        return vec![(
+            region_limit(true) as ptrdiff_t,
+            region_limit(false) as ptrdiff_t,
+        )];
+    }
+
+    let bounds = call!(
+        symbol_value(intern("region-extract-function")),
+        LispObject::from(intern("bounds"))
+    );
+
+    bounds
+        .iter_cars_safe()
+        .map(|elt| {
+            let (beg, end) = elt.as_cons_or_error().as_tuple();
+            (
+                beg.as_fixnum_coerce_marker_or_error() as ptrdiff_t,
+                end.as_fixnum_coerce_marker_or_error() as ptrdiff_t,
+            )
+        })
+        .collect()
+}
+
 /// Return this buffer's mark, as a marker object.
 /// Watch out!  Moving this marker changes the mark position.
 /// If you set the marker not to point anywhere, the buffer will have no mark.
@@ -158,6 +203,43 @@ pub fn mark_marker() -> LispObject {
     ThreadState::current_buffer().mark()
 }
 
+/// Put the mark where point is now, and point where the mark is now.
+/// This command works even when the mark is not active,
+/// and it reactivates the mark.
+///
+/// If Transient Mark mode is on, a prefix ARG deactivates the mark
+/// if it is active, and otherwise avoids reactivating it.  If
+/// Transient Mark mode is off, a prefix ARG enables Transient Mark
+/// mode temporarily.
+#[lisp_fn(min = "0", intspec = "P")]
+pub fn exchange_point_and_mark(arg: LispObject) {
+    let buffer = ThreadState::current_buffer();
+    let omark = marker_position_lisp(buffer.mark().into())
+        .unwrap_or_else(|| xsignal!(Quser_error, LispObject::from("No mark set in this buffer")));
+    let transient_mark_mode = unsafe { globals.Vtransient_mark_mode };
+    let temp_highlight = car_safe(transient_mark_mode).eq(Qonly);
+
+    call!(
+        LispObject::from(intern("set-mark")),
+        LispObject::from(point())
+    );
+    goto_char(LispObject::from(omark));
+
+    if temp_highlight {
+        set_buffer_local(
+            intern("transient-mark-mode"),
+            LispObject::cons(Qonly, transient_mark_mode),
+        );
+    } else {
+        let active = call!(LispObject::from(intern("region-active-p"))).is_not_nil();
+        if arg.is_not_nil() == active {
+            call!(LispObject::from(intern("deactivate-mark")));
+        } else {
+            call!(LispObject::from(intern("activate-mark")));
+        }
+    }
+}
+
 /// Return the minimum permissible value of point in the current
 /// buffer.  This is 1, unless narrowing (a buffer restriction) is in
 /// effect.
@@ -174,6 +256,111 @@ pub fn point_max() -> EmacsInt {
     ThreadState::current_buffer().zv as EmacsInt
 }
 
+impl LispBufferRef {
+    /// Return the labeled restrictions of this buffer, innermost first,
+    /// as a list of (LABEL BEG-MARKER END-MARKER) entries.
+    pub fn labeled_restrictions(self) -> LispObject {
+        cdr(assq(self.as_lisp_obj(), unsafe {
+            globals.Vlabeled_restrictions
+        }))
+    }
+
+    /// Replace the labeled restrictions of this buffer with RESTRICTIONS.
+    pub fn set_labeled_restrictions(self, restrictions: LispObject) {
+        let buffer = self.as_lisp_obj();
+        let mut alist = unsafe { globals.Vlabeled_restrictions };
+        alist = delq(assq(buffer, alist), alist);
+        if restrictions.is_not_nil() {
+            alist = LispObject::cons(LispObject::cons(buffer, restrictions), alist);
+        }
+        unsafe { globals.Vlabeled_restrictions = alist };
+    }
+
+    /// Return the innermost labeled restriction of this buffer as
+    /// (LABEL, BEG-MARKER, END-MARKER), if there is one.  `widen' never
+    /// expands the buffer past it.
+    pub fn narrowing_lock(self) -> Option<(LispObject, LispObject, LispObject)> {
+        let innermost = car(self.labeled_restrictions());
+        if innermost.is_nil() {
+            None
+        } else {
+            let (label, bounds) = innermost.as_cons_or_error().as_tuple();
+            Some((label, car(bounds), car(cdr(bounds))))
+        }
+    }
+}
+
+/// Return the bounds of the current buffer's innermost labeled
+/// restriction as a cons (BEG-MARKER . END-MARKER), or nil if there is
+/// none.
+#[no_mangle]
+pub extern "C" fn labeled_restriction_bounds() -> LispObject {
+    match ThreadState::current_buffer().narrowing_lock() {
+        Some((_, beg, end)) => LispObject::cons(beg, end),
+        None => Qnil,
+    }
+}
+
+/// Restrict this buffer to START..END and lock the restriction with LABEL.
+/// Until the restriction is lifted with `internal-labeled-widen' and the
+/// same LABEL, `widen' only widens the buffer back to START..END.
+/// Labeled restrictions nest; the innermost one is the one in effect.
+#[lisp_fn]
+pub fn internal_labeled_narrow_to_region(start: LispObject, end: LispObject, label: LispObject) {
+    unsafe { Fnarrow_to_region(start, end) };
+
+    let buffer = ThreadState::current_buffer();
+    let restriction = list(&[label, point_min_marker(), point_max_marker()]);
+    buffer.set_labeled_restrictions(LispObject::cons(restriction, buffer.labeled_restrictions()));
+}
+
+/// Remove the restriction labeled LABEL from the current buffer and widen it.
+/// If LABEL doesn't match the innermost labeled restriction, this is
+/// just `widen', which does not expand the buffer past that restriction.
+#[lisp_fn]
+pub fn internal_labeled_widen(label: LispObject) {
+    let buffer = ThreadState::current_buffer();
+    if let Some((innermost, _, _)) = buffer.narrowing_lock() {
+        if innermost.eq(label) {
+            buffer.set_labeled_restrictions(cdr(buffer.labeled_restrictions()));
+        }
+    }
+
+    unsafe { Fwiden() };
+}
+
+/// Expand the accessible portion of the current buffer to include START..END.
+/// Unlike `narrow-to-region', this never shrinks the accessible portion:
+/// a bound that already lies outside START..END is kept.  A restriction
+/// made with `internal-labeled-narrow-to-region' still limits the result,
+/// as it does for `widen'.
+#[lisp_fn]
+pub fn widen_to_region(start: EmacsInt, end: EmacsInt) {
+    let mut buffer = ThreadState::current_buffer();
+    let (start, end) = (start.min(end) as ptrdiff_t, start.max(end) as ptrdiff_t);
+    if start < buffer.beg() || end > buffer.z() {
+        args_out_of_range!(LispObject::from(start), LispObject::from(end));
+    }
+
+    let (lower, upper) = match buffer.narrowing_lock() {
+        Some((_, beg, end)) => (marker_position(beg), marker_position(end)),
+        None => (buffer.beg(), buffer.z()),
+    };
+    let begv = start.max(lower).min(buffer.begv);
+    let zv = end.min(upper).max(buffer.zv);
+    if begv == buffer.begv && zv == buffer.zv {
+        return;
+    }
+
+    let begv_byte = buffer.charpos_to_bytepos(begv);
+    let zv_byte = buffer.charpos_to_bytepos(zv);
+    buffer.set_begv_both(begv, begv_byte);
+    buffer.set_zv_both(zv, zv_byte);
+    buffer.set_clip_changed(true);
+    // Changing the buffer bounds invalidates any recorded current column.
+    unsafe { invalidate_current_column() };
+}
+
 /// Set point to POSITION, a number or marker.
 /// Beginning of buffer is position (point-min), end is (point-max).
 ///
@@ -193,19 +380,32 @@ pub fn goto_char(position: LispObject) -> LispObject {
     position
 }
 
+/// Set point to POSITION, first pushing the old point onto the mark ring.
+/// This is `goto-char' for long jumps: the previous position is recorded
+/// with `push-mark' (without a message or activating the mark), so that
+/// \\[universal-argument] \\[set-mark-command] returns to it.
+///
+/// The return value is POSITION.
+#[lisp_fn(intspec = "NGoto char: ")]
+pub fn goto_char_recording(position: LispObject) -> LispObject {
+    if !(position.is_marker() || position.is_fixnum()) {
+        wrong_type!(Qinteger_or_marker_p, position);
+    }
+    call!(
+        LispObject::from(intern("push-mark")),
+        LispObject::from(point()),
+        Qt
+    );
+    goto_char(position)
+}
+
 /// Return the byte position for character position POSITION.
 /// If POSITION is out of range, the value is nil.
 #[lisp_fn]
 pub fn position_bytes(position: LispNumber) -> Option<EmacsInt> {
-    let pos = position.to_fixnum() as ptrdiff_t;
-    let mut cur_buf = ThreadState::current_buffer();
-
-    if pos >= cur_buf.begv && pos <= cur_buf.zv {
-        let bytepos = unsafe { buf_charpos_to_bytepos(cur_buf.as_mut(), pos) };
-        Some(bytepos as EmacsInt)
-    } else {
-        None
-    }
+    ThreadState::current_buffer()
+        .position_bytes(position.to_fixnum() as ptrdiff_t)
+        .map(|bytepos| bytepos as EmacsInt)
 }
 
 /// TODO: Write better docstring
@@ -306,6 +506,157 @@ pub fn insert_char(character: Codepoint, count: Option<EmacsInt>, inherit: bool)
     }
 }
 
+/// Insert the Lisp objects in ARGS by calling INSERT_FUNC (if an object
+/// is a character) or INSERT_FROM_STRING_FUNC (if an object is a
+/// string).  INHERIT is passed to INSERT_FROM_STRING_FUNC as the last
+/// argument.
+fn general_insert_function(
+    insert_func: unsafe extern "C" fn(*const c_char, ptrdiff_t),
+    insert_from_string_func: unsafe extern "C" fn(
+        LispObject,
+        ptrdiff_t,
+        ptrdiff_t,
+        ptrdiff_t,
+        ptrdiff_t,
+        bool,
+    ),
+    inherit: bool,
+    args: &[LispObject],
+) {
+    for &val in args {
+        if val.is_character() {
+            let c = val.as_character_or_error();
+            let mut str = [0_u8; MAX_MULTIBYTE_LENGTH];
+            let len = if ThreadState::current_buffer().multibyte_characters_enabled() {
+                write_codepoint(&mut str[..], c)
+            } else {
+                str[0] = char_to_byte8(c);
+                1
+            };
+            unsafe { insert_func(str.as_ptr() as *const c_char, len as ptrdiff_t) };
+        } else if let Some(string) = val.as_string() {
+            unsafe {
+                insert_from_string_func(val, 0, 0, string.len_chars(), string.len_bytes(), inherit)
+            };
+        } else {
+            wrong_type!(Qchar_or_string_p, val);
+        }
+    }
+}
+
+/// Insert the arguments, either strings or characters, at point.
+/// Point and after-insertion markers move forward to end up
+///  after the inserted text.
+/// Any other markers at the point of insertion remain before the text.
+///
+/// If the current buffer is multibyte, unibyte strings are converted
+/// to multibyte for insertion (see `string-make-multibyte').
+/// If the current buffer is unibyte, multibyte strings are converted
+/// to unibyte for insertion (see `string-make-unibyte').
+///
+/// When operating on binary data, it may be necessary to preserve the
+/// original bytes of a unibyte string when inserting it into a multibyte
+/// buffer; to accomplish this, apply `string-as-multibyte' to the string
+/// and insert the result.
+///
+/// usage: (insert &rest ARGS)
+#[lisp_fn(name = "insert", c_name = "insert")]
+pub fn insert_lisp(args: &[LispObject]) {
+    general_insert_function(insert, insert_from_string, false, args);
+}
+
+/// Insert a newline at the end of the accessible portion of the buffer,
+/// unless that portion is empty or already ends in a newline.
+/// Point is left where it was, before the new newline if it was at
+/// `point-max'.  Signal an error if the buffer is read-only.
+/// Return t if a newline was inserted, nil otherwise.
+#[lisp_fn(min = "0")]
+pub fn ensure_final_newline() -> bool {
+    let buffer = ThreadState::current_buffer();
+    let zv = buffer.zv;
+
+    if buffer.begv == zv || char_before(LispObject::from(zv as EmacsInt)) == Some('\n' as EmacsInt)
+    {
+        return false;
+    }
+
+    barf_if_buffer_read_only(Some(zv as EmacsInt));
+    // Restoring the excursion puts point back at OPOINT, whether or not
+    // the insertion signals.
+    let count = c_specpdl_index();
+    unsafe {
+        record_unwind_protect(Some(save_excursion_restore), save_excursion_save());
+        set_point(zv);
+    }
+    insert_lisp(&[LispObject::from("\n")]);
+    unsafe { unbind_to(count, Qnil) };
+    true
+}
+
+/// Insert the arguments at point, inheriting properties from adjoining text.
+/// Point and after-insertion markers move forward to end up
+///  after the inserted text.
+/// Any other markers at the point of insertion remain before the text.
+///
+/// If the current buffer is multibyte, unibyte strings are converted
+/// to multibyte for insertion (see `unibyte-char-to-multibyte').
+/// If the current buffer is unibyte, multibyte strings are converted
+/// to unibyte for insertion.
+///
+/// usage: (insert-and-inherit &rest ARGS)
+#[lisp_fn(name = "insert-and-inherit", c_name = "insert_and_inherit")]
+pub fn insert_and_inherit_lisp(args: &[LispObject]) {
+    general_insert_function(insert_and_inherit, insert_from_string, true, args);
+}
+
+/// Insert strings or characters at point, relocating markers after the text.
+/// Point and markers move forward to end up after the inserted text.
+///
+/// If the current buffer is multibyte, unibyte strings are converted
+/// to multibyte for insertion (see `unibyte-char-to-multibyte').
+/// If the current buffer is unibyte, multibyte strings are converted
+/// to unibyte for insertion.
+///
+/// If an overlay begins at the insertion point, the inserted text falls
+/// outside the overlay; if a nonempty overlay ends at the insertion
+/// point, the inserted text falls inside that overlay.
+///
+/// usage: (insert-before-markers &rest ARGS)
+#[lisp_fn(name = "insert-before-markers", c_name = "insert_before_markers")]
+pub fn insert_before_markers_lisp(args: &[LispObject]) {
+    general_insert_function(
+        insert_before_markers,
+        insert_from_string_before_markers,
+        false,
+        args,
+    );
+}
+
+/// Insert TEXT, a string or a character, at the beginning of the
+/// accessible portion of the current buffer.  Unlike `insert' at
+/// `point-min', this leaves point on the character it was on, so it
+/// moves forward by the length of TEXT.  Markers at the insertion
+/// position advance over TEXT only if their insertion type is non-nil.
+#[lisp_fn]
+pub fn prepend_to_current_buffer(text: LispObject) {
+    let buf = ThreadState::current_buffer();
+    let (opoint, opoint_byte) = (buf.pt, buf.pt_byte);
+    let (oz, oz_byte) = (buf.z(), buf.z_byte());
+
+    // Put point back where it was if the insertion signals.
+    let count = c_specpdl_index();
+    unsafe {
+        record_unwind_protect(Some(save_excursion_restore), save_excursion_save());
+        set_point_both(buf.begv, buf.begv_byte);
+    }
+    general_insert_function(insert, insert_from_string, false, &[text]);
+    unsafe { unbind_to(count, Qnil) };
+
+    let nchars = buf.z() - oz;
+    let nbytes = buf.z_byte() - oz_byte;
+    unsafe { set_point_both(opoint + nchars, opoint_byte + nbytes) };
+}
+
 /// Return the character following point, as a number. At the end of
 /// the buffer or accessible region, return 0.
 #[lisp_fn]
@@ -374,6 +725,14 @@ pub fn char_before(pos: LispObject) -> Option<EmacsInt> {
     Some(pos_before)
 }
 
+/// Return t if the character before point is CHARACTER.
+/// The value is nil at the beginning of the accessible portion of the
+/// buffer.  This is equivalent to (eq (char-before) CHARACTER).
+#[lisp_fn]
+pub fn char_before_point_eq(character: EmacsInt) -> bool {
+    character >= 0 && ThreadState::current_buffer().char_before_point_is(character as Codepoint)
+}
+
 /// Return character in current buffer at position POS.
 /// POS is an integer or a marker and defaults to point.
 /// If POS is out of range, the value is nil.
@@ -758,22 +1117,22 @@ pub fn constrain_to_field(
 /// If BYTEPOS is out of range, the value is nil.
 #[lisp_fn]
 pub fn byte_to_position(bytepos: EmacsInt) -> Option<EmacsInt> {
-    let mut cur_buf = ThreadState::current_buffer();
+    let cur_buf = ThreadState::current_buffer();
     let mut pos_byte = bytepos as isize;
     if pos_byte < cur_buf.beg_byte() || pos_byte > cur_buf.z_byte() {
         return None;
     }
-    if cur_buf.z() != cur_buf.z_byte() {
+    if cur_buf.z() != cur_buf.z_byte()
+        && pos_byte < cur_buf.z_byte()
+        && !char_head_p(cur_buf.fetch_byte(pos_byte))
+    {
         // There are multibyte characters in the buffer.
-        // The argument of BYTE_TO_CHAR must be a byte position at
-        // a character boundary, so search for the start of the current
-        // character.
-        while !char_head_p(cur_buf.fetch_byte(pos_byte)) {
-            pos_byte -= 1;
-        }
+        // `bytepos_to_charpos' needs a byte position at a character
+        // boundary, so snap back to the start of the current character.
+        pos_byte = unsafe { dec_pos(pos_byte + 1) };
     }
 
-    Some(unsafe { buf_bytepos_to_charpos(cur_buf.as_mut(), pos_byte) } as EmacsInt)
+    Some(cur_buf.bytepos_to_charpos(pos_byte) as EmacsInt)
 }
 
 /// Return t if two characters match, optionally ignoring case.
@@ -934,15 +1293,471 @@ pub fn lisp_current_message() -> LispObject {
 /// of the buffer.
 #[lisp_fn]
 pub fn buffer_string() -> LispObject {
+    buffer_string_opt(false)
+}
+
+/// Return the contents of the current buffer as a string.
+/// If NO-PROPERTIES is non-nil, the string has no text properties, which
+/// is cheaper than copying them and then discarding them.
+/// If narrowing is in effect, this function returns only the visible part
+/// of the buffer.
+#[lisp_fn(min = "0")]
+pub fn buffer_string_opt(no_properties: bool) -> LispObject {
+    let cur_buf = ThreadState::current_buffer();
+    region_to_string(cur_buf.begv, cur_buf.zv, !no_properties)
+}
+
+/// Return the accessible portion of the current buffer as a string.
+/// This is the text between `point-min' and `point-max', with its text
+/// properties.  Unlike `buffer-string', it is guaranteed to respect any
+/// narrowing in effect, for callers that depend on that.
+#[lisp_fn]
+pub fn accessible_buffer_string() -> LispObject {
     let cur_buf = ThreadState::current_buffer();
+    region_to_string(cur_buf.begv, cur_buf.zv, true)
+}
+
+/// Return the text of the current buffer between START and END as a
+/// string, with its text properties if PROPS is true.
+pub fn region_to_string(start: ptrdiff_t, end: ptrdiff_t, props: bool) -> LispObject {
+    let mut cur_buf = ThreadState::current_buffer();
+    let multibyte = cur_buf.multibyte_characters_enabled();
 
-    let begv = cur_buf.begv;
-    let begv_byte = cur_buf.begv_byte;
+    if start == end {
+        let empty = if multibyte {
+            LispObject::empty_multibyte_string()
+        } else {
+            LispObject::empty_unibyte_string()
+        };
+        return empty.into();
+    }
 
-    let zv = cur_buf.zv;
-    let zv_byte = cur_buf.zv_byte;
+    let start_byte = cur_buf.charpos_to_bytepos(start);
+    let end_byte = cur_buf.charpos_to_bytepos(end);
+    let nbytes = (end_byte - start_byte) as EmacsInt;
+    let result = unsafe {
+        if multibyte {
+            make_uninit_multibyte_string((end - start) as EmacsInt, nbytes)
+        } else {
+            make_uninit_string(nbytes)
+        }
+    };
 
-    unsafe { make_buffer_string_both(begv, begv_byte, zv, zv_byte, true) }
+    // Copy the text on either side of the gap, without moving it.
+    let gpt_byte = cur_buf.gpt_byte();
+    let mut dest = result.as_string_or_error().data_ptr();
+    for &(from, to) in &[
+        (start_byte, end_byte.min(gpt_byte)),
+        (start_byte.max(gpt_byte), end_byte),
+    ] {
+        if from < to {
+            unsafe {
+                let src = cur_buf
+                    .byte_pos_addr(from)
+                    .offset(cur_buf.pos_within_range(from));
+                ptr::copy_nonoverlapping(src, dest, (to - from) as usize);
+                dest = dest.offset(to - from);
+            }
+        }
+    }
+
+    if props {
+        unsafe { update_buffer_properties(start, end) };
+        let (start_obj, end_obj) = (LispObject::from(start), LispObject::from(end));
+        let next_change = unsafe { Fnext_property_change(start_obj, Qnil, end_obj) };
+        let props_at_start = unsafe { Ftext_properties_at(start_obj, Qnil) };
+        if !next_change.eq(end_obj) || props_at_start.is_not_nil() {
+            unsafe { copy_intervals_to_string(result, cur_buf.as_mut(), start, end - start) };
+        }
+    }
+    result
+}
+
+/// Return the contents of part of the current buffer as a string.
+/// The two arguments START and END are character positions;
+/// they can be in either order.
+/// The string returned is multibyte if the buffer is multibyte.
+///
+/// This function copies the text properties of that part of the buffer
+/// into the result string; if you don't want the text properties,
+/// use `buffer-substring-no-properties' instead.
+#[lisp_fn]
+pub fn buffer_substring(start: LispObject, end: LispObject) -> LispObject {
+    let (mut start, mut end) = (start, end);
+    unsafe { validate_region(&mut start, &mut end) };
+    region_to_string(
+        start.as_fixnum_or_error() as ptrdiff_t,
+        end.as_fixnum_or_error() as ptrdiff_t,
+        true,
+    )
+}
+
+/// Return the characters of part of the buffer, without the text properties.
+/// The two arguments START and END are character positions;
+/// they can be in either order.
+#[lisp_fn]
+pub fn buffer_substring_no_properties(start: LispObject, end: LispObject) -> LispObject {
+    let (mut start, mut end) = (start, end);
+    unsafe { validate_region(&mut start, &mut end) };
+    region_to_string(
+        start.as_fixnum_or_error() as ptrdiff_t,
+        end.as_fixnum_or_error() as ptrdiff_t,
+        false,
+    )
+}
+
+impl LispBufferRef {
+    /// Return the text between START and END as one string per line,
+    /// with text properties but without the terminating newlines.  A
+    /// final line that doesn't end in a newline is included as well.
+    /// This buffer must be the current buffer.
+    pub fn buffer_substring_lines(self, start: ptrdiff_t, end: ptrdiff_t) -> Vec<LispObject> {
+        let mut lines = Vec::new();
+        let mut pos = start;
+        let mut pos_byte = self.charpos_to_bytepos(start);
+        let end_byte = self.charpos_to_bytepos(end);
+
+        while pos < end {
+            let mut shortage = 0;
+            let mut next_byte = 0;
+            let next = unsafe {
+                find_newline(
+                    pos,
+                    pos_byte,
+                    end,
+                    end_byte,
+                    1,
+                    &mut shortage,
+                    &mut next_byte,
+                    true,
+                )
+            };
+            // On success, NEXT is just after the newline.
+            let (line_end, line_end_byte) = if shortage == 0 {
+                (next - 1, next_byte - 1)
+            } else {
+                (end, end_byte)
+            };
+            lines.push(unsafe {
+                make_buffer_string_both(pos, pos_byte, line_end, line_end_byte, true)
+            });
+            pos = next;
+            pos_byte = next_byte;
+        }
+
+        lines
+    }
+}
+
+impl LispBufferRef {
+    /// Delete the spaces and tabs at the end of each line between START
+    /// and END, and at the end of the accessible portion if END is
+    /// there.  This buffer must be the current buffer.
+    pub fn delete_trailing_whitespace(self, start: ptrdiff_t, mut end: ptrdiff_t) {
+        let is_blank = |pos| {
+            let c = self.fetch_char(self.charpos_to_bytepos(pos));
+            c == c_int::from(b' ') || c == c_int::from(b'\t')
+        };
+        let mut pos = start;
+
+        loop {
+            let mut shortage = 0;
+            let next =
+                unsafe { find_newline(pos, -1, end, -1, 1, &mut shortage, ptr::null_mut(), true) };
+            let eol = if shortage == 0 {
+                next - 1
+            } else if end == self.zv
+                || self.fetch_char(self.charpos_to_bytepos(end)) == c_int::from(b'\n')
+            {
+                end
+            } else {
+                return;
+            };
+
+            let mut blank = eol;
+            while blank > pos && is_blank(blank - 1) {
+                blank -= 1;
+            }
+            if blank < eol {
+                unsafe { del_range(blank, eol) };
+                // Everything after the deleted text has moved back.
+                end -= eol - blank;
+            }
+
+            pos = blank + 1;
+            if shortage != 0 || pos > end {
+                return;
+            }
+        }
+    }
+
+    /// Replace each character C between BEG and END with F(C), in order.
+    /// Characters whose replacement has the same byte length are
+    /// overwritten in place; the others are replaced one at a time, which
+    /// keeps their text properties and moves markers as little as
+    /// possible.  The region's length in characters never changes.  In a
+    /// unibyte buffer, replacements that are not single bytes are ignored.
+    /// F must not modify the buffer.  This buffer must be the current
+    /// buffer.
+    pub(crate) fn transform_region(
+        self,
+        beg: ptrdiff_t,
+        end: ptrdiff_t,
+        mut f: impl FnMut(Codepoint) -> Codepoint,
+    ) {
+        if beg == end {
+            return;
+        }
+        unsafe {
+            modify_text(beg, end);
+            record_delete(beg, make_buffer_string(beg, end, true), false);
+        }
+
+        let multibyte = self.multibyte_characters_enabled();
+        let mut changed: Option<(ptrdiff_t, ptrdiff_t)> = None;
+        let mut pos = beg;
+        let mut pos_byte = self.charpos_to_bytepos(beg);
+
+        while pos < end {
+            let mut next_byte = self.inc_pos(pos_byte);
+            let c = self.fetch_char(pos_byte) as Codepoint;
+            let new = f(c);
+
+            if new != c && (multibyte || new < 0x100) {
+                let mut buf = [0; MAX_MULTIBYTE_LENGTH];
+                let len = if multibyte {
+                    write_codepoint(&mut buf, new)
+                } else {
+                    buf[0] = new as c_uchar;
+                    1
+                };
+
+                if len as ptrdiff_t == next_byte - pos_byte {
+                    unsafe {
+                        let addr = self
+                            .beg_addr()
+                            .offset(pos_byte - self.beg_byte() + self.pos_within_range(pos_byte));
+                        ptr::copy_nonoverlapping(buf.as_ptr(), addr, len);
+                    }
+                } else {
+                    unsafe {
+                        replace_range_2(
+                            pos,
+                            pos_byte,
+                            pos + 1,
+                            next_byte,
+                            buf.as_ptr() as *const c_char,
+                            1,
+                            len as ptrdiff_t,
+                            false,
+                        )
+                    };
+                    next_byte = pos_byte + len as ptrdiff_t;
+                }
+
+                let first = changed.map_or(pos, |(first, _)| first);
+                changed = Some((first, pos + 1));
+            }
+
+            pos += 1;
+            pos_byte = next_byte;
+        }
+
+        unsafe { record_insert(beg, end - beg) };
+        if let Some((first, last)) = changed {
+            unsafe {
+                signal_after_change(first, last - first, last - first);
+                update_compositions(first, last, CHECK_ALL as c_int);
+            }
+        }
+    }
+}
+
+/// Replace each character in the region with the result of FUNCTION.
+/// FUNCTION is called with each character between BEG and END, in order,
+/// and must return a character.  All the calls are made before the
+/// buffer is changed, and characters that FUNCTION maps to themselves
+/// are left untouched, as are their text properties.
+#[lisp_fn(name = "internal--transform-region", c_name = "transform_region")]
+pub fn transform_region_lisp(beg: LispObject, end: LispObject, function: LispObject) {
+    let (mut beg, mut end) = (beg, end);
+    unsafe { validate_region(&mut beg, &mut end) };
+    let (beg, end) = (beg.as_fixnum_or_error(), end.as_fixnum_or_error());
+
+    let buffer = ThreadState::current_buffer();
+    let replacements: Vec<Codepoint> = buffer
+        .chars(buffer.charpos_to_bytepos(beg as ptrdiff_t))
+        .take_while(|&(pos, _, _)| pos < end as ptrdiff_t)
+        .map(|(_, _, c)| {
+            call!(function, LispObject::from(EmacsInt::from(c))).as_character_or_error()
+        })
+        .collect();
+
+    let mut replacements = replacements.into_iter();
+    buffer.transform_region(beg as ptrdiff_t, end as ptrdiff_t, |c| {
+        replacements.next().unwrap_or(c)
+    });
+}
+
+/// Delete the text between START and END, recording it for undo.
+/// This is like `delete-region', except that the deleted text is
+/// recorded before anything is removed, rather than by the deletion
+/// itself, so the undo entry is there even where the deletion would
+/// skip it.  Nothing is recorded if the buffer refuses the change, for
+/// instance because it is read-only, and a buffer whose undo is
+/// disabled keeps it disabled.
+#[lisp_fn(intspec = "r")]
+pub fn delete_region_undoable(start: LispObject, end: LispObject) {
+    let (mut start, mut end) = (start, end);
+    unsafe { validate_region(&mut start, &mut end) };
+    let mut start = start.as_fixnum_or_error() as ptrdiff_t;
+    let end = end.as_fixnum_or_error() as ptrdiff_t;
+    if start == end {
+        return;
+    }
+
+    // The modification hooks may move the text around, so find it again
+    // afterwards the way `del_range_1' does.
+    let length = end - start;
+    unsafe { prepare_to_modify_buffer(start, end, &mut start) };
+    let mut buffer = ThreadState::current_buffer();
+    let end = (start + length).min(buffer.zv);
+
+    // Record the deletion ourselves, and keep `del_range_1' from
+    // recording it a second time.
+    unsafe {
+        record_delete(start, make_buffer_string(start, end, true), true);
+        let count = c_specpdl_index();
+        record_unwind_protect(Some(delete_region_undoable_unwind), buffer.undo_list_);
+        buffer.undo_list_ = Qt;
+        del_range_1(start, end, false, false);
+        unbind_to(count, Qnil);
+    }
+}
+
+unsafe extern "C" fn delete_region_undoable_unwind(undo_list: LispObject) {
+    ThreadState::current_buffer().undo_list_ = undo_list;
+}
+
+unsafe extern "C" fn subst_char_in_region_unwind(arg: LispObject) {
+    let mut buffer = ThreadState::current_buffer();
+    buffer.undo_list_ = arg;
+}
+
+unsafe extern "C" fn subst_char_in_region_unwind_1(arg: LispObject) {
+    let mut buffer = ThreadState::current_buffer();
+    buffer.filename_ = arg;
+}
+
+/// From START to END, replace FROMCHAR with TOCHAR each time it occurs.
+/// If optional arg NOUNDO is non-nil, don't record this change for undo
+/// and don't mark the buffer as really changed.
+/// The characters need not have multi-byte forms of the same length.
+#[lisp_fn(min = "4")]
+pub fn subst_char_in_region(
+    start: LispObject,
+    end: LispObject,
+    fromchar: Codepoint,
+    tochar: Codepoint,
+    noundo: bool,
+) {
+    let (mut start, mut end) = (start, end);
+    unsafe { validate_region(&mut start, &mut end) };
+    let start = start.as_fixnum_or_error() as ptrdiff_t;
+    let end = end.as_fixnum_or_error() as ptrdiff_t;
+
+    let mut buffer = ThreadState::current_buffer();
+    // A unibyte buffer holds only the low byte of each character.
+    let (fromc, toc) = if buffer.multibyte_characters_enabled() {
+        (fromchar, tochar)
+    } else {
+        (fromchar & 0xff, tochar & 0xff)
+    };
+
+    // Leave the buffer alone, and run no change hooks, if there is
+    // nothing to replace.
+    let mut pos_byte = buffer.charpos_to_bytepos(start);
+    let first = (start..end).find(|_| {
+        let c = buffer.fetch_char(pos_byte) as Codepoint;
+        pos_byte = buffer.inc_pos(pos_byte);
+        c == fromc
+    });
+    let first = match first {
+        Some(first) => first,
+        None => return,
+    };
+
+    let count = c_specpdl_index();
+    // If we don't want undo, turn off putting stuff on the list.
+    // That's faster than getting rid of things,
+    // and it prevents even the entry for a first change.
+    // Also inhibit locking the file.
+    if noundo {
+        unsafe {
+            record_unwind_protect(Some(subst_char_in_region_unwind), buffer.undo_list_);
+            buffer.undo_list_ = Qt;
+            record_unwind_protect(Some(subst_char_in_region_unwind_1), buffer.filename_);
+            buffer.filename_ = Qnil;
+        }
+    }
+    let was_saved = buffer.modifications() == buffer.modifications_since_save();
+    let was_auto_saved = !buffer.is_autosave_stale();
+
+    // The substitution is recorded for undo as the deletion and
+    // reinsertion of the text from FIRST to END, which stays correct
+    // when TOCHAR's multi-byte form is longer or shorter than
+    // FROMCHAR's.
+    buffer.transform_region(first, end, |c| if c == fromc { toc } else { c });
+
+    if noundo {
+        if was_saved {
+            buffer.record_save();
+        }
+        if was_auto_saved {
+            buffer.record_auto_save();
+        }
+    }
+
+    unsafe { unbind_to(count, Qnil) };
+}
+
+/// Delete trailing spaces and tabs between START and END.
+/// START and END default to the start and end of the accessible portion
+/// of the buffer.  Whitespace is deleted before each newline in the
+/// region, and before the end of the accessible portion if END is there.
+///
+/// This is the primitive underlying cleanups like the
+/// `delete-trailing-whitespace' command, which also knows about
+/// syntax-table whitespace and `delete-trailing-lines'.
+#[lisp_fn(
+    min = "0",
+    name = "internal-delete-trailing-whitespace",
+    c_name = "delete_trailing_whitespace"
+)]
+pub fn delete_trailing_whitespace(start: Option<EmacsInt>, end: Option<EmacsInt>) {
+    let buffer = ThreadState::current_buffer();
+    let start = start.map_or(buffer.begv, |p| p as ptrdiff_t);
+    let end = end.map_or(buffer.zv, |p| p as ptrdiff_t);
+    let (start, end) = (start.min(end), start.max(end));
+    if start < buffer.begv || end > buffer.zv {
+        args_out_of_range!(LispObject::from(start), LispObject::from(end));
+    }
+    buffer.delete_trailing_whitespace(start, end);
+}
+
+/// Return the lines of the region between START and END as a list of strings.
+/// The strings keep their text properties but not their terminating
+/// newlines.  If the region ends in the middle of a line, that partial
+/// line is the last element.  This is cheaper than extracting each line
+/// with `buffer-substring'.
+#[lisp_fn]
+pub fn buffer_lines(start: EmacsInt, end: EmacsInt) -> LispObject {
+    let buffer = ThreadState::current_buffer();
+    let (start, end) = (start.min(end) as ptrdiff_t, start.max(end) as ptrdiff_t);
+    if start < buffer.begv || end > buffer.zv {
+        args_out_of_range!(LispObject::from(start), LispObject::from(end));
+    }
+    list(&buffer.buffer_substring_lines(start, end))
 }
 
 // Save current buffer state for `save-excursion' special form.
@@ -1177,4 +1992,61 @@ pub fn rust_find_field(
     }
 }
 
+/// Replace the region between BEG and END using REPLACE-FN.
+/// REPLACE-FN runs on the current buffer narrowed to the region.  It
+/// should return either a string or a buffer replacing the region.
+///
+/// The replacement is performed using `replace-buffer-contents' which
+/// also describes the MAX-SECS and MAX-COSTS arguments and the return
+/// value.  This implementation of `replace-buffer-contents' always
+/// computes the complete diff, so both are currently ignored.
+///
+/// Note: If the replacement is a string, it'll be placed in a temporary
+/// buffer so that `replace-buffer-contents' can operate on it.
+/// Therefore, if you already have the replacement in a buffer, it makes
+/// no sense to convert it to a string using `buffer-substring' or
+/// similar.
+#[lisp_fn(min = "3")]
+pub fn replace_region_contents(
+    beg: EmacsInt,
+    end: EmacsInt,
+    replace_fn: LispObject,
+    _max_secs: Option<EmacsInt>,
+    _max_costs: Option<EmacsInt>,
+) -> LispObject {
+    let count = c_specpdl_index();
+    unsafe {
+        record_unwind_protect(Some(save_excursion_restore), save_excursion_save());
+        record_unwind_protect(Some(save_restriction_restore), save_restriction_save());
+        Fnarrow_to_region(beg.into(), end.into());
+    }
+    goto_char(point_min().into());
+
+    let replacement = call!(replace_fn);
+    let result = if replacement.is_buffer() {
+        unsafe { Freplace_buffer_contents(replacement) }
+    } else {
+        let mut source = ThreadState::current_buffer();
+        LispBufferRef::as_temp_for(|temp| {
+            insert_lisp(&[replacement]);
+            unsafe {
+                set_buffer_internal_1(source.as_mut());
+                Freplace_buffer_contents(temp.into())
+            }
+        })
+    };
+
+    unsafe { unbind_to(count, result) }
+}
+
+#[no_mangle]
+pub extern "C" fn rust_syms_of_editfns() {
+    /// Alist of labeled restrictions, keyed by buffer.
+    /// Each value is a list of (LABEL BEG-MARKER END-MARKER) entries, innermost
+    /// first.  This is maintained by `internal-labeled-narrow-to-region' and
+    /// `internal-labeled-widen'; don't modify it directly.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    defvar_lisp!(Vlabeled_restrictions, "internal--labeled-restrictions", Qnil);
+}
+
 include!(concat!(env!("OUT_DIR"), "/editfns_exports.rs"));
@@ -1,13 +1,30 @@
 //! Functions to deal with files
 use std::path;
 
+use libc::{self, c_char, ptrdiff_t};
+
 use remacs_macros::lisp_fn;
 
 use crate::{
-    lisp::defsubr,
+    buffers::LispBufferOrCurrent,
+    editfns::{goto_char, insert_lisp},
+    lisp::{defsubr, LispObject},
     lists::LispCons,
     math::{arithcompare, ArithComparison},
     multibyte::LispStringRef,
+    obarray::intern,
+    remacs_sys::EmacsInt,
+    remacs_sys::{
+        emacs_close, emacs_open, emacs_read, encode_file_name, make_unibyte_string,
+        report_file_error,
+    },
+    remacs_sys::{Fcoding_system_base, Fcoding_system_eol_type, Fencode_coding_region},
+    remacs_sys::{Fexpand_file_name, Ffind_file_name_handler},
+    remacs_sys::{QCbom, QCpost_read_conversion, Qapproximate, Qexact, Qrounded},
+    remacs_sys::{Qcharset, Qraw_text, Qt, Qundecided, Qutf_16, Qutf_8},
+    remacs_sys::{Qcoding_system_get, Qcoding_system_type, Qline_number_at_pos},
+    remacs_sys::{Qinsert_file_contents_lite, Qnil},
+    strings::string_as_multibyte,
     threads::ThreadState,
 };
 
@@ -49,4 +66,304 @@ pub fn recent_auto_save_p() -> bool {
     cur_buf.modifications_since_save() < cur_buf.auto_save_modified
 }
 
+/// Return t if BUFFER has changes that the next auto-save should write.
+/// That is the case when auto-saving is enabled for BUFFER and it was
+/// modified both since it was last auto-saved and since it was last
+/// saved.  Indirect buffers are never auto-saved themselves; their base
+/// buffer takes care of it.
+/// No argument or nil as argument means use current buffer as BUFFER.
+#[lisp_fn(min = "0")]
+pub fn buffer_needs_auto_save_p(buffer: LispBufferOrCurrent) -> bool {
+    let buf = buffer.unwrap();
+
+    buf.base_buffer().is_none()
+        && buf.auto_save_file_name_.is_string()
+        && buf.save_length_.as_fixnum().map_or(false, |len| len >= 0)
+        && buf.modifications_since_save() < buf.modifications()
+        && buf.is_autosave_stale()
+}
+
+/// Mark BUFFER as auto-saved with its current text, so that
+/// `buffer-needs-auto-save-p' returns nil until BUFFER changes again.
+/// Unlike `set-buffer-auto-saved', this leaves the recorded buffer size
+/// and any auto-save failure alone.
+/// No argument or nil as argument means use current buffer as BUFFER.
+#[lisp_fn(min = "0")]
+pub fn mark_buffer_auto_saved(buffer: LispBufferOrCurrent) {
+    buffer.unwrap().record_auto_save();
+}
+
+/// How `bufferpos-to-filepos' can map buffer positions to file positions
+/// for a given coding system.
+#[derive(Clone, Copy)]
+enum FileEncoding {
+    /// Plain UTF-8: file bytes match the buffer's internal bytes.
+    Utf8,
+    /// One byte per character.
+    SingleByte,
+    /// Two bytes per character within the BMP, four outside it.
+    Utf16,
+    /// Anything else, which can only be measured by encoding.
+    Other,
+}
+
+/// Coding systems of type `charset' that are nevertheless variable-width.
+const VARIABLE_WIDTH_CHARSET_CODINGS: [&str; 10] = [
+    "chinese-gbk",
+    "chinese-gb18030",
+    "euc-tw",
+    "euc-jis-2004",
+    "korean-iso-8bit",
+    "chinese-iso-8bit",
+    "japanese-iso-8bit",
+    "chinese-big5-hkscs",
+    "japanese-cp932",
+    "korean-cp949",
+];
+
+fn file_encoding(coding_system: LispObject) -> FileEncoding {
+    let coding_type = call!(Qcoding_system_type, coding_system);
+
+    if coding_type == Qutf_8 {
+        // Any post-read/pre-write conversions mean it's not really UTF-8.
+        if call!(Qcoding_system_get, coding_system, QCpost_read_conversion).is_nil() {
+            FileEncoding::Utf8
+        } else {
+            FileEncoding::Other
+        }
+    } else if coding_type == Qutf_16 {
+        FileEncoding::Utf16
+    } else if coding_type == Qcharset || coding_type == Qraw_text || coding_type == Qundecided {
+        let base = unsafe { Fcoding_system_base(coding_system) };
+        if VARIABLE_WIDTH_CHARSET_CODINGS
+            .iter()
+            .any(|&name| LispObject::from(intern(name)) == base)
+        {
+            FileEncoding::Other
+        } else {
+            FileEncoding::SingleByte
+        }
+    } else {
+        FileEncoding::Other
+    }
+}
+
+/// Return the number of bytes the text of the current buffer from its
+/// beginning up to POSITION takes once encoded with CODING-SYSTEM,
+/// including any BOM the coding system writes.
+fn encoded_length_up_to(position: EmacsInt, coding_system: LispObject) -> EmacsInt {
+    let buffer = ThreadState::current_buffer();
+    let end = position
+        .max(buffer.beg() as EmacsInt)
+        .min(buffer.z() as EmacsInt);
+    let encoded = unsafe {
+        Fencode_coding_region(
+            LispObject::from(buffer.beg()),
+            LispObject::from(end),
+            coding_system,
+            Qt,
+        )
+    };
+
+    encoded.as_string_or_error().len_bytes() as EmacsInt
+}
+
+/// Try to return the file byte corresponding to a particular buffer POSITION.
+/// Value is the file position given as a (0-based) byte count.
+/// The function presumes the file is encoded with CODING-SYSTEM, which defaults
+/// to `buffer-file-coding-system'.
+/// QUALITY can be:
+///   `approximate', in which case we may cut some corners to avoid
+///     excessive work.
+///   `rounded', like `approximate', but for UTF-16 the value is rounded
+///     down to a whole code unit, so that it never points into the
+///     middle of one.
+///   `exact', in which case we may end up re-encoding a large part of
+///     the buffer, this can be expensive and slow.
+///   nil, in which case we may return nil rather than an approximation.
+#[lisp_fn(min = "1")]
+pub fn bufferpos_to_filepos(
+    position: EmacsInt,
+    quality: LispObject,
+    coding_system: LispObject,
+) -> Option<EmacsInt> {
+    let buffer = ThreadState::current_buffer();
+    let coding_system = if coding_system.is_nil() {
+        buffer.buffer_file_coding_system()
+    } else {
+        coding_system
+    };
+
+    // Account for CR in CRLF pairs.
+    let eol = unsafe { Fcoding_system_eol_type(coding_system) };
+    let lineno = if eol == LispObject::from(1) {
+        call!(Qline_number_at_pos, LispObject::from(position)).as_fixnum_or_error() - 1
+    } else {
+        0
+    };
+    let bom = |len: EmacsInt| {
+        if call!(Qcoding_system_get, coding_system, QCbom).is_nil() {
+            0
+        } else {
+            len
+        }
+    };
+    // The buffer's internal byte offset of POSITION, clipped to the
+    // accessible portion.
+    let internal_byte = || {
+        let bytepos = buffer
+            .position_bytes(position as ptrdiff_t)
+            .unwrap_or_else(|| if position <= 0 { 1 } else { buffer.zv_byte });
+        bytepos as EmacsInt - 1
+    };
+
+    match file_encoding(coding_system) {
+        FileEncoding::Utf8 => Some(internal_byte() + bom(3) + lineno),
+        FileEncoding::SingleByte => Some(position - 1 + lineno),
+        FileEncoding::Utf16 if quality != Qexact => {
+            // Assume all characters are within the BMP, i.e. each one
+            // takes up 2 bytes, and so does each CR.
+            let byte = (position - 1) * 2 + bom(2) + lineno * 2;
+            Some(if quality == Qrounded {
+                byte - byte % 2
+            } else {
+                byte
+            })
+        }
+        _ => {
+            if quality == Qexact {
+                Some(encoded_length_up_to(position, coding_system))
+            } else if quality == Qapproximate || quality == Qrounded {
+                Some(internal_byte() + lineno)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// Read the bytes of the file FILENAME from offset BEG up to END, or up
+/// to the end of the file if END is `None`.
+fn read_file_bytes(filename: LispObject, beg: EmacsInt, end: Option<EmacsInt>) -> Vec<u8> {
+    if beg < 0 || end.map_or(false, |end| end < beg) {
+        args_out_of_range!(LispObject::from(beg), LispObject::from(end));
+    }
+
+    let encoded = unsafe { encode_file_name(filename) }.as_string_or_error();
+    let fd = unsafe { emacs_open(encoded.const_data_ptr() as *const c_char, libc::O_RDONLY, 0) };
+    if fd < 0 {
+        unsafe { report_file_error(b"Opening input file\0".as_ptr() as *const c_char, filename) };
+    }
+
+    let mut contents = Vec::new();
+    let mut chunk = [0_u8; 16 * 1024];
+    let mut failed = unsafe { libc::lseek(fd, beg as libc::off_t, libc::SEEK_SET) } < 0;
+    while !failed {
+        let wanted = end.map_or(chunk.len(), |end| {
+            (end - beg - contents.len() as EmacsInt).min(chunk.len() as EmacsInt) as usize
+        });
+        if wanted == 0 {
+            break;
+        }
+        let nread = unsafe {
+            emacs_read(
+                fd,
+                chunk.as_mut_ptr() as *mut libc::c_void,
+                wanted as ptrdiff_t,
+            )
+        };
+        if nread <= 0 {
+            failed = nread < 0;
+            break;
+        }
+        contents.extend_from_slice(&chunk[..nread as usize]);
+    }
+
+    unsafe { emacs_close(fd) };
+    if failed {
+        unsafe { report_file_error(b"Read error\0".as_ptr() as *const c_char, filename) };
+    }
+    contents
+}
+
+/// Insert contents of file FILENAME after point, without decoding them.
+/// Returns list of absolute file name and number of characters inserted.
+/// This is a lightweight `insert-file-contents': no coding system is
+/// involved.  In a multibyte buffer, the contents are read as UTF-8, a
+/// byte that isn't part of a valid sequence becoming a raw byte; a
+/// unibyte buffer gets the bytes as they are.
+///
+/// BEG and END specify the part of the file to insert, as byte offsets
+/// into the file.  They default to its beginning and end.
+///
+/// If second argument VISIT is non-nil, the buffer's visited filename and
+/// last save file modtime are set, and it is marked unmodified.  The whole
+/// file must then be inserted into an empty buffer.
+#[lisp_fn(min = "1")]
+pub fn insert_file_contents_lite(
+    filename: LispObject,
+    visit: bool,
+    beg: Option<EmacsInt>,
+    end: Option<EmacsInt>,
+) -> LispObject {
+    let filename = unsafe { Fexpand_file_name(filename, Qnil) };
+
+    // If the file name has special constructs in it, call the
+    // corresponding file handler.
+    let handler = unsafe { Ffind_file_name_handler(filename, Qinsert_file_contents_lite) };
+    if handler.is_not_nil() {
+        return call!(
+            handler,
+            Qinsert_file_contents_lite,
+            filename,
+            visit.into(),
+            beg.into(),
+            end.into()
+        );
+    }
+
+    let mut buffer = ThreadState::current_buffer();
+    if visit {
+        if beg.is_some() || end.is_some() {
+            error!("Attempt to visit less than an entire file");
+        }
+        if buffer.beg() < buffer.z() {
+            error!("Cannot do file visiting in a non-empty buffer");
+        }
+    }
+
+    let contents = read_file_bytes(filename, beg.unwrap_or(0), end);
+    let mut text = unsafe {
+        make_unibyte_string(
+            contents.as_ptr() as *const c_char,
+            contents.len() as ptrdiff_t,
+        )
+    };
+    if buffer.multibyte_characters_enabled() {
+        text = string_as_multibyte(text.into());
+    }
+    let inserted = text.as_string_or_error().len_chars();
+
+    // Like `insert-file-contents', leave point before the inserted text.
+    let pt = buffer.pt;
+    insert_lisp(&[text]);
+    goto_char(pt.into());
+
+    if visit {
+        buffer.filename_ = filename;
+        buffer.mark_freshly_loaded();
+    }
+
+    list!(filename, LispObject::from(inserted))
+}
+
+def_lisp_sym!(Qapproximate, "approximate");
+def_lisp_sym!(Qexact, "exact");
+def_lisp_sym!(Qrounded, "rounded");
+def_lisp_sym!(Qinsert_file_contents_lite, "insert-file-contents-lite");
+def_lisp_sym!(QCbom, ":bom");
+def_lisp_sym!(Qcoding_system_get, "coding-system-get");
+def_lisp_sym!(Qcoding_system_type, "coding-system-type");
+def_lisp_sym!(Qline_number_at_pos, "line-number-at-pos");
+
 include!(concat!(env!("OUT_DIR"), "/fileio_exports.rs"));
@@ -130,3 +130,122 @@ pub extern "C" fn make_string(s: *const c_char, length: isize) -> LispObject {
 pub extern "C" fn make_unibyte_string(s: *const c_char, length: isize) -> LispObject {
     mock_unibyte_string!()
 }
+
+/// Fake a cons cell by putting it on the heap and leaking it.
+#[cfg(test)]
+pub fn mock_cons(car: LispObject, cdr: LispObject) -> LispObject {
+    let cell: Lisp_Cons = unsafe { ::std::mem::zeroed() };
+    let ptr = crate::lisp::ExternalPtr::new(Box::into_raw(Box::new(cell)));
+    let object = LispObject::tag_ptr(ptr, Lisp_Type::Lisp_Cons);
+    let cons = object.as_cons().unwrap();
+    cons.set_car(car);
+    cons.set_cdr(cdr);
+    object
+}
+
+thread_local! {
+    /// The buffer last made current with `set_buffer_internal_1`.
+    static MOCK_CURRENT_BUFFER: ::std::cell::Cell<*mut Lisp_Buffer> =
+        ::std::cell::Cell::new(::std::ptr::null_mut());
+}
+
+#[cfg(test)]
+#[allow(dead_code)]
+#[no_mangle]
+pub extern "C" fn set_buffer_internal_1(b: *mut Lisp_Buffer) {
+    MOCK_CURRENT_BUFFER.with(|current| current.set(b));
+}
+
+thread_local! {
+    /// The bindings made with `specbind` and not yet unbound, as
+    /// (SYMBOL, VALUE) pairs, innermost last.
+    static MOCK_SPECPDL: ::std::cell::RefCell<Vec<(LispObject, LispObject)>> =
+        ::std::cell::RefCell::new(Vec::new());
+}
+
+/// Return the bindings currently on the mocked specpdl, innermost last.
+#[cfg(test)]
+pub fn mock_specpdl() -> Vec<(LispObject, LispObject)> {
+    MOCK_SPECPDL.with(|specpdl| specpdl.borrow().clone())
+}
+
+#[cfg(test)]
+#[allow(dead_code)]
+#[no_mangle]
+pub extern "C" fn SPECPDL_INDEX() -> ptrdiff_t {
+    MOCK_SPECPDL.with(|specpdl| specpdl.borrow().len() as ptrdiff_t)
+}
+
+#[cfg(test)]
+#[allow(dead_code)]
+#[no_mangle]
+pub extern "C" fn specbind(symbol: LispObject, value: LispObject) {
+    MOCK_SPECPDL.with(|specpdl| specpdl.borrow_mut().push((symbol, value)));
+}
+
+#[cfg(test)]
+#[allow(dead_code)]
+#[no_mangle]
+pub extern "C" fn record_unwind_current_buffer() {}
+
+#[cfg(test)]
+#[allow(dead_code)]
+#[no_mangle]
+pub extern "C" fn unbind_to(count: ptrdiff_t, value: LispObject) -> LispObject {
+    MOCK_SPECPDL.with(|specpdl| specpdl.borrow_mut().truncate(count as usize));
+    value
+}
+
+/// Only the buffer's `local_var_alist' is reported, which is where the
+/// mocked `set_internal` puts the local bindings it makes.
+#[cfg(test)]
+#[allow(dead_code)]
+#[no_mangle]
+pub extern "C" fn Fbuffer_local_variables(buffer: LispObject) -> LispObject {
+    let buffer = buffer.get_untaggedptr() as *mut Lisp_Buffer;
+    unsafe { (*buffer).local_var_alist_ }
+}
+
+#[cfg(test)]
+#[allow(dead_code)]
+#[no_mangle]
+pub extern "C" fn Fmake_local_variable(variable: LispObject) -> LispObject {
+    variable
+}
+
+/// Every binding made is taken to be local to the current buffer, and
+/// is pushed onto its `local_var_alist'.
+#[cfg(test)]
+#[allow(unused_variables)]
+#[allow(dead_code)]
+#[no_mangle]
+pub extern "C" fn set_internal(
+    symbol: LispObject,
+    newval: LispObject,
+    where_: LispObject,
+    bindflag: Set_Internal_Bind,
+) {
+    MOCK_CURRENT_BUFFER.with(|current| unsafe {
+        let buffer = current.get();
+        (*buffer).local_var_alist_ =
+            mock_cons(mock_cons(symbol, newval), (*buffer).local_var_alist_);
+    });
+}
+
+/// Count the characters before BYTEPOS one byte at a time, which is all
+/// the small buffers of the tests need.
+#[cfg(test)]
+#[allow(dead_code)]
+#[no_mangle]
+pub extern "C" fn buf_bytepos_to_charpos(b: *mut Lisp_Buffer, bytepos: ptrdiff_t) -> ptrdiff_t {
+    use crate::buffers::{LispBufferRef, BEG, BEG_BYTE};
+
+    let buffer = LispBufferRef::new(b);
+    if !buffer.multibyte_characters_enabled() {
+        return bytepos;
+    }
+    let chars = (BEG_BYTE..bytepos)
+        .filter(|&pos| crate::character::char_head_p(buffer.fetch_byte(pos)))
+        .count();
+    BEG + chars as ptrdiff_t
+}
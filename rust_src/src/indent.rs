@@ -1,14 +1,17 @@
 //! Indentation functions
 
+use libc::{c_int, ptrdiff_t};
 use std::ptr;
 
 use remacs_macros::lisp_fn;
 
 use crate::{
-    buffers::{point_byte, point_min_byte},
+    buffers::{point_byte, point_min_byte, LispBufferRef},
     editfns::{point, point_min},
     lisp::defsubr,
-    remacs_sys::{self, find_newline, position_indentation, EmacsInt},
+    lisp::LispObject,
+    remacs_sys::{self, find_newline, position_indentation, EmacsInt, Fchar_width},
+    threads::ThreadState,
 };
 
 /// Return the indentation of the current line.  This is the
@@ -52,4 +55,79 @@ pub fn current_column() -> EmacsInt {
     column as EmacsInt
 }
 
+impl LispBufferRef {
+    /// Return the line number of POS, counting from 1 at the start of
+    /// the accessible portion, and its column.  Columns are counted as by
+    /// `current-column', except that invisible text and display properties
+    /// are not taken into account.  This buffer must be the current buffer.
+    pub fn position_line_and_column(self, pos: ptrdiff_t) -> (EmacsInt, EmacsInt) {
+        let pos_byte = self.charpos_to_bytepos(pos);
+
+        let mut line_start_byte = 0;
+        let line_start = unsafe {
+            find_newline(
+                pos,
+                pos_byte,
+                self.begv,
+                self.begv_byte,
+                -1,
+                ptr::null_mut(),
+                &mut line_start_byte,
+                true,
+            )
+        };
+
+        // Only the newlines before the line of POS need to be counted, and
+        // they can be found without decoding the text.
+        let mut shortage = 0;
+        unsafe {
+            find_newline(
+                self.begv,
+                self.begv_byte,
+                line_start,
+                line_start_byte,
+                ptrdiff_t::max_value(),
+                &mut shortage,
+                ptr::null_mut(),
+                true,
+            )
+        };
+        let line = (ptrdiff_t::max_value() - shortage) as EmacsInt + 1;
+
+        let tab_width = self
+            .tab_width_
+            .as_fixnum()
+            .filter(|&width| 0 < width && width <= 1000)
+            .unwrap_or(8);
+        let mut column = 0;
+        let mut bytepos = line_start_byte;
+        while bytepos < pos_byte {
+            let c = self.fetch_char(bytepos);
+            if c == c_int::from(b'\t') {
+                column += tab_width - column % tab_width;
+            } else if 0x20 <= c && c < 0x7f {
+                column += 1;
+            } else {
+                let width = unsafe { Fchar_width(EmacsInt::from(c).into()) };
+                column += width.as_fixnum_or_error();
+            }
+            bytepos = self.inc_pos(bytepos);
+        }
+
+        (line, column)
+    }
+}
+
+/// Return the line number and column of point as a cons (LINE . COLUMN).
+/// LINE counts from 1 at `point-min', as with `line-number-at-pos', and
+/// COLUMN is as `current-column' would return, except that invisible text
+/// and display properties are not taken into account.  This is cheaper
+/// than calling those functions in turn.
+#[lisp_fn]
+pub fn point_line_and_column() -> LispObject {
+    let buffer = ThreadState::current_buffer();
+    let (line, column) = buffer.position_line_and_column(buffer.pt);
+    LispObject::cons(LispObject::from(line), LispObject::from(column))
+}
+
 include!(concat!(env!("OUT_DIR"), "/indent_exports.rs"));
@@ -581,6 +581,84 @@ impl LispBufferRef {
             self.z_byte()
         }
     }
+
+    /// Return the byte position corresponding to CHARPOS.
+    pub fn charpos_to_bytepos(mut self, charpos: ptrdiff_t) -> ptrdiff_t {
+        unsafe { buf_charpos_to_bytepos(self.as_mut(), charpos) }
+    }
+
+    /// Return the character position corresponding to BYTEPOS.
+    pub fn bytepos_to_charpos(mut self, bytepos: ptrdiff_t) -> ptrdiff_t {
+        unsafe { buf_bytepos_to_charpos(self.as_mut(), bytepos) }
+    }
+
+    /// Return the byte position of CHARPOS, or `None` if CHARPOS lies
+    /// outside the accessible portion of the buffer.
+    pub fn position_bytes(self, charpos: ptrdiff_t) -> Option<ptrdiff_t> {
+        if charpos >= self.begv && charpos <= self.zv {
+            Some(self.charpos_to_bytepos(charpos))
+        } else {
+            None
+        }
+    }
+
+    /// Return the (charpos, bytepos) pair recorded by the last
+    /// conversion that had to scan the text, as long as the buffer has
+    /// not been modified since.  Conversions near that pair can start
+    /// scanning from it instead of from a more distant known position.
+    pub fn charpos_cache(self) -> Option<(ptrdiff_t, ptrdiff_t)> {
+        if self.is_cached && self.modifications() == self.cached_modiff {
+            Some((self.cached_charpos, self.cached_bytepos))
+        } else {
+            None
+        }
+    }
+
+    /// In debug builds, check that every marker on this buffer's chain
+    /// points into its text.  Code that moves text between buffers, such
+    /// as `buffer-swap-text', calls this to catch markers left pointing
+    /// at the positions of the text they used to belong to.
+    pub fn check_marker_positions(self) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+        for marker in self.markers().into_iter().flat_map(LispMarkerRef::iter) {
+            if marker.buffer().is_none() {
+                continue;
+            }
+            debug_assert!(
+                self.beg_byte() <= marker.bytepos && marker.bytepos <= self.z_byte(),
+                "marker byte position {} outside {}..{}",
+                marker.bytepos,
+                self.beg_byte(),
+                self.z_byte()
+            );
+            debug_assert!(
+                self.beg() <= marker.charpos && marker.charpos <= self.z(),
+                "marker position {} outside {}..{}",
+                marker.charpos,
+                self.beg(),
+                self.z()
+            );
+        }
+    }
+
+    /// Record CHARPOS and BYTEPOS as the anchor for the next conversion.
+    pub fn set_charpos_cache(&mut self, charpos: ptrdiff_t, bytepos: ptrdiff_t) {
+        self.is_cached = true;
+        self.cached_modiff = self.modifications();
+        self.cached_charpos = charpos;
+        self.cached_bytepos = bytepos;
+    }
+}
+
+/// Check the marker positions of buffer B; see
+/// `LispBufferRef::check_marker_positions`.
+#[no_mangle]
+pub extern "C" fn check_marker_positions(b: *mut Lisp_Buffer) {
+    LispBufferRef::from_ptr(b as *mut c_void)
+        .unwrap_or_else(|| panic!("Invalid buffer reference."))
+        .check_marker_positions();
 }
 
 // Converting between character positions and byte positions.
@@ -651,8 +729,8 @@ pub unsafe extern "C" fn buf_charpos_to_bytepos(b: *mut Lisp_Buffer, charpos: is
     consider_known!(buffer_ref.begv, buffer_ref.begv_byte);
     consider_known!(buffer_ref.zv, buffer_ref.zv_byte);
 
-    if buffer_ref.is_cached && buffer_ref.modifications() == buffer_ref.cached_modiff {
-        consider_known!(buffer_ref.cached_charpos, buffer_ref.cached_bytepos);
+    if let Some((cached_charpos, cached_bytepos)) = buffer_ref.charpos_cache() {
+        consider_known!(cached_charpos, cached_bytepos);
     }
 
     for m in buffer_ref.markers().iter() {
@@ -679,11 +757,7 @@ pub unsafe extern "C" fn buf_charpos_to_bytepos(b: *mut Lisp_Buffer, charpos: is
             byte_char_debug_check(buffer_ref, best_below, best_below_byte);
         }
 
-        buffer_ref.is_cached = true;
-        buffer_ref.cached_modiff = buffer_ref.modifications();
-
-        buffer_ref.cached_charpos = best_below;
-        buffer_ref.cached_bytepos = best_below_byte;
+        buffer_ref.set_charpos_cache(best_below, best_below_byte);
 
         best_below_byte
     } else {
@@ -698,14 +772,10 @@ pub unsafe extern "C" fn buf_charpos_to_bytepos(b: *mut Lisp_Buffer, charpos: is
             build_marker(b, best_above, best_above_byte);
         }
         if MARKER_DEBUG {
-            byte_char_debug_check(buffer_ref, best_below, best_below_byte);
+            byte_char_debug_check(buffer_ref, best_above, best_above_byte);
         }
 
-        buffer_ref.is_cached = true;
-        buffer_ref.cached_modiff = buffer_ref.modifications();
-
-        buffer_ref.cached_charpos = best_above;
-        buffer_ref.cached_bytepos = best_above_byte;
+        buffer_ref.set_charpos_cache(best_above, best_above_byte);
 
         best_above_byte
     }
@@ -762,8 +832,8 @@ pub unsafe extern "C" fn buf_bytepos_to_charpos(b: *mut Lisp_Buffer, bytepos: is
     consider_known!(buffer_ref.begv_byte, buffer_ref.begv);
     consider_known!(buffer_ref.zv_byte, buffer_ref.zv);
 
-    if buffer_ref.is_cached && buffer_ref.modifications() == buffer_ref.cached_modiff {
-        consider_known!(buffer_ref.cached_bytepos, buffer_ref.cached_charpos);
+    if let Some((cached_charpos, cached_bytepos)) = buffer_ref.charpos_cache() {
+        consider_known!(cached_bytepos, cached_charpos);
     }
 
     for m in buffer_ref.markers().iter() {
@@ -800,11 +870,7 @@ pub unsafe extern "C" fn buf_bytepos_to_charpos(b: *mut Lisp_Buffer, bytepos: is
             byte_char_debug_check(buffer_ref, best_below, best_below_byte);
         }
 
-        buffer_ref.is_cached = true;
-        buffer_ref.cached_modiff = buffer_ref.modifications();
-
-        buffer_ref.cached_charpos = best_below;
-        buffer_ref.cached_bytepos = best_below_byte;
+        buffer_ref.set_charpos_cache(best_below, best_below_byte);
 
         best_below
     } else {
@@ -821,17 +887,13 @@ pub unsafe extern "C" fn buf_bytepos_to_charpos(b: *mut Lisp_Buffer, bytepos: is
         // But don't do it if BUF_MARKERS is nil;
         // that is a signal from Fset_buffer_multibyte.
         if record && buffer_ref.markers().is_some() {
-            build_marker(b, best_below, best_below_byte);
+            build_marker(b, best_above, best_above_byte);
         }
         if MARKER_DEBUG {
-            byte_char_debug_check(buffer_ref, best_below, best_below_byte);
+            byte_char_debug_check(buffer_ref, best_above, best_above_byte);
         }
 
-        buffer_ref.is_cached = true;
-        buffer_ref.cached_modiff = buffer_ref.modifications();
-
-        buffer_ref.cached_charpos = best_above;
-        buffer_ref.cached_bytepos = best_above_byte;
+        buffer_ref.set_charpos_cache(best_above, best_above_byte);
 
         best_above
     }
@@ -42,7 +42,7 @@ use crate::{
     lisp::{ExternalPtr, LispObject},
     remacs_sys::Qstringp,
     remacs_sys::{char_bits, EmacsDouble, EmacsInt, Lisp_String, Lisp_Type},
-    remacs_sys::{emacs_abort, empty_unibyte_string},
+    remacs_sys::{emacs_abort, empty_multibyte_string, empty_unibyte_string},
 };
 
 pub type LispStringRef = ExternalPtr<Lisp_String>;
@@ -274,6 +274,10 @@ impl LispObject {
     pub fn empty_unibyte_string() -> LispStringRef {
         LispStringRef::from(unsafe { empty_unibyte_string })
     }
+
+    pub fn empty_multibyte_string() -> LispStringRef {
+        LispStringRef::from(unsafe { empty_multibyte_string })
+    }
 }
 
 pub fn is_ascii(c: Codepoint) -> bool {
@@ -479,7 +483,7 @@ pub unsafe extern "C" fn str_to_multibyte(
 
 /// Same as `MULTIBYTE_LENGTH` macro in C.
 #[allow(clippy::if_same_then_else)]
-fn multibyte_length(slice: &[c_uchar], allow_encoded_raw: bool) -> Option<usize> {
+pub fn multibyte_length(slice: &[c_uchar], allow_encoded_raw: bool) -> Option<usize> {
     let len = slice.len();
     if len < 1 {
         None
@@ -562,6 +566,29 @@ pub fn multibyte_length_by_head(byte: c_uchar) -> usize {
     }
 }
 
+/// Return the number of complete characters at the start of BYTES and
+/// the number of bytes they occupy.  A character cut short at the end of
+/// BYTES is left out, so the prefix can be inserted without splitting
+/// it.  If MULTIBYTE is false, every byte is a character.
+#[cfg_attr(not(test), allow(dead_code))]
+pub fn chars_in_byte_prefix(bytes: &[u8], multibyte: bool) -> (usize, usize) {
+    if !multibyte {
+        return (bytes.len(), bytes.len());
+    }
+
+    let mut chars = 0;
+    let mut consumed = 0;
+    while consumed < bytes.len() {
+        let len = multibyte_length_by_head(bytes[consumed]);
+        if consumed + len > bytes.len() {
+            break;
+        }
+        consumed += len;
+        chars += 1;
+    }
+    (chars, consumed)
+}
+
 /// Return the number of characters in the NBYTES bytes at PTR.
 /// This works by looking at the contents and checking for multibyte
 /// sequences while assuming that there's no invalid sequence.  It
@@ -792,3 +819,19 @@ pub fn char_to_byte8(c: Codepoint) -> u8 {
 pub fn single_byte_charp(c: Codepoint) -> bool {
     c < 0x100
 }
+
+#[test]
+fn test_chars_in_byte_prefix() {
+    assert_eq!((0, 0), chars_in_byte_prefix(b"", true));
+    assert_eq!((3, 3), chars_in_byte_prefix(b"abc", true));
+    // "aé€" is 1 + 2 + 3 bytes.
+    let text = "a\u{e9}\u{20ac}".as_bytes();
+    assert_eq!((3, 6), chars_in_byte_prefix(text, true));
+    assert_eq!((2, 3), chars_in_byte_prefix(&text[..5], true));
+    assert_eq!((2, 3), chars_in_byte_prefix(&text[..4], true));
+    assert_eq!((1, 1), chars_in_byte_prefix(&text[..2], true));
+    // A raw byte in a multibyte buffer takes two bytes.
+    assert_eq!((1, 2), chars_in_byte_prefix(&[0xC1, 0xBF, 0xC0], true));
+    // In a unibyte buffer every byte is a character of its own.
+    assert_eq!((5, 5), chars_in_byte_prefix(&text[..5], false));
+}
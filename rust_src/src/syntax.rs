@@ -3,20 +3,216 @@
 use remacs_macros::lisp_fn;
 
 use crate::{
+    buffers::{BufferCharTableSlot, LispBufferRef},
     chartable::LispCharTableRef,
+    editfns::{constrain_to_field, save_excursion_save},
     lisp::defsubr,
     lisp::LispObject,
-    remacs_sys::{buffer_defaults, scan_lists, set_char_table_defalt},
+    numbers::LispNumber,
+    obarray::intern,
+    remacs_sys::{
+        buffer_defaults, record_unwind_protect, save_excursion_restore, save_restriction_restore,
+        save_restriction_save, scan_lists, scan_words, set_char_table_defalt, set_point, specbind,
+        syntaxcode, unbind_to,
+    },
     remacs_sys::{EmacsInt, Qnil, Qsyntax_table, Qsyntax_table_p},
-    remacs_sys::{Fcopy_sequence, Fset_char_table_parent},
-    threads::ThreadState,
+    remacs_sys::{Fcopy_sequence, Fmake_char_table, Fnarrow_to_region, Fset_char_table_parent},
+    threads::{c_specpdl_index, ThreadState},
 };
 
 /// Return the current syntax table. This is the one specified by the
 /// current buffer.
 #[lisp_fn]
-pub fn syntax_table() -> LispObject {
-    ThreadState::current_buffer().syntax_table_
+pub fn syntax_table() -> Option<LispCharTableRef> {
+    ThreadState::current_buffer().per_buffer_char_table(BufferCharTableSlot::Syntax)
+}
+
+impl LispBufferRef {
+    /// Return the syntax class of the character at byte position
+    /// BYTEPOS, as looked up in this buffer's syntax table.  Syntax
+    /// flags are masked off, and characters without an entry count as
+    /// whitespace.  Text properties are not consulted.
+    pub fn char_syntax_class_at(self, bytepos: isize) -> u8 {
+        let c = self.fetch_char(bytepos);
+        let entry = self
+            .per_buffer_char_table(BufferCharTableSlot::Syntax)
+            .map_or(Qnil, |table| table.get(c as isize));
+        let code = entry
+            .as_cons()
+            .and_then(|cons| cons.car().as_fixnum())
+            .unwrap_or(syntaxcode::Swhitespace as EmacsInt);
+        (code & 0xff) as u8
+    }
+
+    /// Scan from character position FROM, which is DEPTH parentheses
+    /// deep, for the position where the depth returns to zero: just
+    /// after the parenthesis that balances when scanning FORWARD, or at
+    /// it when scanning backward.  A positive DEPTH therefore moves out
+    /// of that many levels, and a negative one moves in.  Return None if
+    /// the accessible portion of the buffer ends first, or if a closing
+    /// parenthesis takes the depth below where it started.
+    ///
+    /// Parentheses are recognized by their open and close syntax in this
+    /// buffer's syntax table.  Strings and comments are not treated
+    /// specially.
+    pub fn scan_balanced(self, from: isize, mut depth: i32, forward: bool) -> Option<isize> {
+        let min_depth = depth.min(0);
+        let open = syntaxcode::Sopen as u8;
+        let close = syntaxcode::Sclose as u8;
+        let mut pos = from;
+        let mut pos_byte = self.charpos_to_bytepos(from);
+
+        loop {
+            let class = if forward {
+                if pos == self.zv {
+                    return None;
+                }
+                let class = self.char_syntax_class_at(pos_byte);
+                pos += 1;
+                pos_byte = self.inc_pos(pos_byte);
+                class
+            } else {
+                if pos == self.begv {
+                    return None;
+                }
+                pos -= 1;
+                pos_byte = self.dec_pos(pos_byte);
+                self.char_syntax_class_at(pos_byte)
+            };
+
+            let step = match class {
+                c if c == open => 1,
+                c if c == close => -1,
+                _ => continue,
+            };
+            depth += if forward { step } else { -step };
+            if depth == 0 {
+                return Some(pos);
+            }
+            if depth < min_depth {
+                return None;
+            }
+        }
+    }
+}
+
+/// Move point forward N words (backward if N is negative).
+/// If N is omitted or nil, move point forward one word.
+/// Normally returns t.
+/// If an edge of the buffer or a field boundary is reached, point is
+/// left there and the function returns nil.  Field boundaries are not
+/// noticed if `inhibit-field-text-motion' is non-nil.
+///
+/// The word boundaries are normally determined by the buffer's syntax
+/// table, but `find-word-boundary-function-table', such as set up
+/// by `subword-mode', can change that.  If a Lisp program needs to
+/// move by words determined strictly by the syntax table, it should
+/// use `forward-word-strictly' instead.
+#[lisp_fn(min = "0", intspec = "^p")]
+pub fn forward_word(n: Option<EmacsInt>) -> bool {
+    let n = n.unwrap_or(1);
+    let buffer = ThreadState::current_buffer();
+
+    let target = unsafe { scan_words(buffer.pt, n) };
+    let val = match target {
+        0 => {
+            if n > 0 {
+                buffer.zv
+            } else {
+                buffer.begv
+            }
+        }
+        pos => pos,
+    };
+
+    // Avoid jumping out of an input field.
+    let val = constrain_to_field(
+        Some(LispNumber::Fixnum(val as EmacsInt)),
+        LispNumber::Fixnum(buffer.pt as EmacsInt),
+        false,
+        false,
+        Qnil,
+    ) as isize;
+
+    unsafe { set_point(val) };
+    target == val
+}
+
+/// Return the syntax class code of the character at POSITION.
+/// POSITION defaults to point.  The code is an integer as used in raw
+/// syntax descriptors, e.g. 0 for whitespace, 1 for punctuation and 2
+/// for word constituents.  Unlike `syntax-after', this ignores any
+/// `syntax-table' text properties.
+#[lisp_fn(min = "0")]
+pub fn char_syntax_class_at(position: LispObject) -> EmacsInt {
+    let buffer = ThreadState::current_buffer();
+    let pos = if position.is_nil() {
+        buffer.pt
+    } else {
+        position.as_fixnum_coerce_marker_or_error() as isize
+    };
+    if pos < buffer.begv || pos >= buffer.zv {
+        args_out_of_range!(LispObject::from(pos), buffer.begv, buffer.zv);
+    }
+    EmacsInt::from(buffer.char_syntax_class_at(buffer.charpos_to_bytepos(pos)))
+}
+
+/// Return the number of words between START and END.
+/// START and END may be integers or markers, in either order.  Words
+/// are counted the way `forward-word-strictly' moves over them, so the
+/// syntax table, `syntax-table' text properties and script boundaries
+/// are honored, but `find-word-boundary-function-table' is not.  A word
+/// cut by START or END is counted too.  This is the counting used by
+/// `count-words' and `count-words-region'.
+#[lisp_fn(name = "internal-count-words", c_name = "count_words_region")]
+pub fn count_words_region(start: LispObject, end: LispObject) -> EmacsInt {
+    let count = c_specpdl_index();
+    unsafe {
+        record_unwind_protect(Some(save_excursion_restore), save_excursion_save());
+        record_unwind_protect(Some(save_restriction_restore), save_restriction_save());
+        Fnarrow_to_region(
+            start.as_fixnum_coerce_marker_or_error().into(),
+            end.as_fixnum_coerce_marker_or_error().into(),
+        );
+        specbind(
+            intern("find-word-boundary-function-table").into(),
+            Fmake_char_table(Qnil, Qnil),
+        );
+    }
+
+    let mut pos = ThreadState::current_buffer().begv;
+    let mut words = 0;
+    loop {
+        pos = unsafe { scan_words(pos, 1) };
+        if pos == 0 {
+            break;
+        }
+        words += 1;
+    }
+
+    unsafe { unbind_to(count, Qnil) };
+    words
+}
+
+/// Scan from FROM for the position where the parenthesis depth is zero.
+/// DEPTH is the nesting depth of FROM, so a positive DEPTH moves out of
+/// that many levels and a negative one moves into that many; with DEPTH
+/// zero, this moves over one balanced group.  Scan backward if BACKWARD
+/// is non-nil.  Return the position found, or nil if the accessible
+/// portion of the buffer ends first or the parentheses are unbalanced.
+///
+/// Only the open and close classes of the syntax table are used; unlike
+/// `scan-lists', strings and comments are not skipped.
+#[lisp_fn(min = "2")]
+pub fn scan_balanced(from: EmacsInt, depth: EmacsInt, backward: bool) -> Option<EmacsInt> {
+    let buffer = ThreadState::current_buffer();
+    let from = from as isize;
+    if from < buffer.begv || from > buffer.zv {
+        args_out_of_range!(LispObject::from(from), buffer.begv, buffer.zv);
+    }
+    buffer
+        .scan_balanced(from, depth as i32, !backward)
+        .map(|pos| pos as EmacsInt)
 }
 
 /// Scan from character number FROM by COUNT lists.
@@ -1,13 +1,21 @@
 //! Text property support
 
+use libc::ptrdiff_t;
 use std::ptr;
 
 use remacs_macros::lisp_fn;
 
 use crate::{
+    buffers::LispBufferRef,
     lisp::{defsubr, LispObject},
+    lists::{list, plist_put},
     remacs_sys::get_char_property_and_overlay,
     remacs_sys::EmacsInt,
+    remacs_sys::{
+        Fcopy_sequence, Fnext_single_char_property_change, Foverlays_at, Ftext_properties_at,
+    },
+    remacs_sys::{Qnil, Qt},
+    threads::ThreadState,
 };
 
 /// Return the value of POSITION's property PROP, in OBJECT.
@@ -25,4 +33,120 @@ pub fn get_char_property(position: EmacsInt, prop: LispObject, object: LispObjec
     }
 }
 
+/// Return the properties of the character at POSITION in the current buffer.
+/// The result merges the text properties at POSITION with the properties
+/// of the overlays covering it.  When several of them set the same
+/// property, an overlay takes precedence over the text, and among
+/// overlays the one with the highest priority wins.
+#[lisp_fn]
+pub fn char_properties_at(position: EmacsInt) -> LispObject {
+    let pos = LispObject::from(position);
+    let mut plist = unsafe { Fcopy_sequence(Ftext_properties_at(pos, Qnil)) };
+
+    // `overlays-at' sorts by decreasing priority, so walk the list
+    // backwards and let each overlay overwrite the ones below it.
+    let overlays: Vec<LispObject> = unsafe { Foverlays_at(pos, Qt) }.iter_cars_safe().collect();
+    for overlay in overlays
+        .into_iter()
+        .rev()
+        .filter_map(LispObject::as_overlay)
+    {
+        let props: Vec<LispObject> = overlay.plist.iter_cars_safe().collect();
+        for pair in props.chunks(2) {
+            if let [prop, val] = *pair {
+                plist = plist_put(plist, prop, val);
+            }
+        }
+    }
+    plist
+}
+
+/// Iterator over the runs of a property in a buffer; see
+/// `LispBufferRef::property_runs`.
+pub struct PropertyRuns {
+    buffer: LispObject,
+    prop: LispObject,
+    pos: ptrdiff_t,
+    end: ptrdiff_t,
+}
+
+impl Iterator for PropertyRuns {
+    type Item = (ptrdiff_t, ptrdiff_t, LispObject);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.end {
+            return None;
+        }
+        let start = self.pos;
+        let value = get_char_property(start as EmacsInt, self.prop, self.buffer);
+        let next = unsafe {
+            Fnext_single_char_property_change(
+                LispObject::from(start),
+                self.prop,
+                self.buffer,
+                LispObject::from(self.end),
+            )
+        };
+        self.pos = next.as_fixnum_or_error() as ptrdiff_t;
+        Some((start, self.pos, value))
+    }
+}
+
+impl LispBufferRef {
+    /// Iterate over the maximal runs of text between BEG and END over
+    /// which the value of PROP, as seen by `get-char-property', stays
+    /// the same.  Each item is (START, END, VALUE); both text properties
+    /// and overlays contribute to VALUE.
+    pub fn property_runs(self, prop: LispObject, beg: ptrdiff_t, end: ptrdiff_t) -> PropertyRuns {
+        PropertyRuns {
+            buffer: self.as_lisp_obj(),
+            prop,
+            pos: beg,
+            end,
+        }
+    }
+
+    /// Whether some character between BEG and END has a non-nil PROP,
+    /// either as a text property or through an overlay.
+    pub fn region_has_property(self, beg: ptrdiff_t, end: ptrdiff_t, prop: LispObject) -> bool {
+        self.property_runs(prop, beg, end)
+            .any(|(_, _, value)| value.is_not_nil())
+    }
+}
+
+/// Return the runs of property PROP in the current buffer.
+/// The value is a list of elements (START END VALUE), one for each
+/// maximal stretch of text from BEG to END over which PROP's value is
+/// VALUE, taking overlays as well as text properties into account.
+/// BEG and END default to the limits of the accessible portion.
+#[lisp_fn(min = "1")]
+pub fn property_runs(prop: LispObject, beg: Option<EmacsInt>, end: Option<EmacsInt>) -> LispObject {
+    let buffer = ThreadState::current_buffer();
+    let beg = beg.map_or(buffer.begv, |b| b as ptrdiff_t);
+    let end = end.map_or(buffer.zv, |e| e as ptrdiff_t);
+    if beg < buffer.begv || end > buffer.zv || beg > end {
+        args_out_of_range!(LispObject::from(beg), LispObject::from(end));
+    }
+
+    let runs: Vec<LispObject> = buffer
+        .property_runs(prop, beg, end)
+        .map(|(start, end, value)| list!(LispObject::from(start), LispObject::from(end), value))
+        .collect();
+    list(&runs)
+}
+
+/// Return t if any character between BEG and END has a non-nil PROP.
+/// Both text properties and overlays in the current buffer are checked,
+/// so this answers questions such as whether any part of a region is
+/// read-only or invisible.  BEG and END may be given in either order.
+#[lisp_fn(min = "3")]
+pub fn region_has_property(beg: EmacsInt, end: EmacsInt, prop: LispObject) -> bool {
+    let buffer = ThreadState::current_buffer();
+    let (beg, end) = (beg.min(end) as ptrdiff_t, beg.max(end) as ptrdiff_t);
+    if beg < buffer.begv || end > buffer.zv {
+        args_out_of_range!(LispObject::from(beg), LispObject::from(end));
+    }
+    buffer.region_has_property(beg, end, prop)
+}
+
 include!(concat!(env!("OUT_DIR"), "/textprop_exports.rs"));
@@ -2,18 +2,19 @@
 
 use std::ptr;
 
-use libc::c_int;
+use libc::{c_int, c_void, ptrdiff_t};
 
 use remacs_macros::lisp_fn;
 
 use crate::{
+    buffers::{LispBufferOrCurrent, LispBufferRef},
     editfns::{goto_char, point},
     frames::{frame_live_or_selected, selected_frame, LispFrameRef},
     interactive::prefix_numeric_value,
     lisp::defsubr,
     lisp::{ExternalPtr, LispObject},
-    lists::{assq, setcdr},
-    marker::{marker_position_lisp, set_marker_restricted},
+    lists::{assq, list, setcdr},
+    marker::{marker_position_lisp, set_marker_both, set_marker_restricted},
     remacs_sys::globals,
     remacs_sys::{
         estimate_mode_line_height, minibuf_level,
@@ -22,10 +23,10 @@ use crate::{
         update_mode_lines, window_body_width, window_list_1, window_menu_bar_p, window_tool_bar_p,
         wset_redisplay,
     },
-    remacs_sys::{face_id, glyph_matrix, pvec_type, EmacsInt, Lisp_Type, Lisp_Window},
+    remacs_sys::{face_id, glyph_matrix, pvec_type, EmacsInt, Lisp_Buffer, Lisp_Type, Lisp_Window},
     remacs_sys::{
-        Qceiling, Qfloor, Qheader_line_format, Qmode_line_format, Qnil, Qnone, Qwindow_live_p,
-        Qwindow_valid_p, Qwindowp,
+        Qceiling, Qfloor, Qheader_line_format, Qmode_line_format, Qnil, Qnone, Qt, Qwindow_live_p,
+        Qwindow_valid_p, Qwindowp, Vframe_list,
     },
     threads::ThreadState,
 };
@@ -299,6 +300,130 @@ impl LispObject {
      */
 }
 
+impl LispBufferRef {
+    /// Return an iterator over the windows, on all frames, that are
+    /// currently displaying this buffer.
+    pub fn windows_showing(self) -> impl Iterator<Item = LispWindowRef> {
+        let buffer = self.as_lisp_obj();
+        unsafe { window_list_1(Qnil, Qt, Qt) }
+            .iter_cars_safe()
+            .filter_map(LispObject::as_window)
+            .filter(move |w| w.contents.eq(buffer))
+    }
+
+    /// Keep windows that start at the beginning of the accessible
+    /// portion from scrolling text prepended there into view, so that
+    /// the text they show stays put.  AT_BYTE is where BYTES_ADDED bytes
+    /// were just inserted; insertions anywhere else are ignored.
+    /// Windows whose point is at the insertion, such as the one being
+    /// typed in, are left alone so the new text remains visible there.
+    /// This must be called on the current buffer after its markers were
+    /// adjusted for the insertion.
+    pub fn adjust_window_starts_after_insert(
+        &mut self,
+        at_byte: ptrdiff_t,
+        bytes_added: ptrdiff_t,
+    ) {
+        if self.window_count == 0 || bytes_added == 0 || at_byte != self.begv_byte {
+            return;
+        }
+
+        let buffer = self.as_lisp_obj();
+        let selected = selected_window();
+        each_window(|w| {
+            if !w.contents.eq(buffer) {
+                return;
+            }
+            let point_byte = if w.as_lisp_obj().eq(selected) {
+                self.pt_byte
+            } else {
+                w.pointm.as_marker_or_error().bytepos_or_error()
+            };
+            if point_byte == at_byte {
+                return;
+            }
+
+            let start_byte = w.start.as_marker_or_error().bytepos_or_error();
+            let new_start = window_start_after_insert(start_byte, at_byte, bytes_added);
+            if new_start != start_byte {
+                let charpos = self.bytepos_to_charpos(new_start);
+                set_marker_both(w.start, buffer, charpos, new_start);
+            }
+        });
+    }
+}
+
+/// Call F for every leaf window on every frame, minibuffer windows
+/// included.  Unlike `window_list_1', this conses nothing, so it is
+/// cheap enough to use from the insertion primitives.
+pub fn each_window(mut f: impl FnMut(LispWindowRef)) {
+    for frame in unsafe { Vframe_list }
+        .iter_cars_safe()
+        .filter_map(LispObject::as_frame)
+    {
+        // delete_frame may set the root window of a frame to nil.
+        if let Some(root) = frame.root_window.as_window() {
+            each_window_1(root, &mut f);
+        }
+    }
+}
+
+/// Helper for `each_window'.  Call F for all leaf windows reachable
+/// from W and its next siblings.
+fn each_window_1(mut w: LispWindowRef, f: &mut impl FnMut(LispWindowRef)) {
+    loop {
+        match w.contents.as_window() {
+            Some(child) => each_window_1(child, f),
+            None => f(w),
+        }
+        match w.next.as_window() {
+            Some(next) => w = next,
+            None => break,
+        }
+    }
+}
+
+/// Return the byte position a window start at START_BYTE should have
+/// once BYTES_ADDED bytes were inserted at AT_BYTE, given that markers
+/// were already relocated.  A start after the insertion has been moved
+/// along with its text; only a start at the insertion itself stays in
+/// front of the new text and has to skip over it.
+fn window_start_after_insert(
+    start_byte: ptrdiff_t,
+    at_byte: ptrdiff_t,
+    bytes_added: ptrdiff_t,
+) -> ptrdiff_t {
+    if start_byte == at_byte {
+        start_byte + bytes_added
+    } else {
+        start_byte
+    }
+}
+
+#[test]
+fn test_window_start_after_insert() {
+    // Insertion at the window start: skip the new text.
+    assert_eq!(15, window_start_after_insert(10, 10, 5));
+    // Insertion before the start: the marker already moved past it.
+    assert_eq!(15, window_start_after_insert(15, 3, 5));
+    // Insertion after the start doesn't affect it.
+    assert_eq!(10, window_start_after_insert(10, 12, 5));
+    assert_eq!(1, window_start_after_insert(1, 1, 0));
+}
+
+/// Adjust the starts of the windows showing BUFFER after an insertion.
+/// See `LispBufferRef::adjust_window_starts_after_insert`.
+#[no_mangle]
+pub extern "C" fn adjust_window_starts_after_insert(
+    buffer: *mut Lisp_Buffer,
+    at_byte: ptrdiff_t,
+    bytes_added: ptrdiff_t,
+) {
+    let mut buf = LispBufferRef::from_ptr(buffer as *mut c_void)
+        .unwrap_or_else(|| panic!("Invalid buffer reference."));
+    buf.adjust_window_starts_after_insert(at_byte, bytes_added);
+}
+
 pub type LispGlyphMatrixRef = ExternalPtr<glyph_matrix>;
 
 impl LispGlyphMatrixRef {
@@ -812,6 +937,19 @@ pub fn window_list_one(
     unsafe { (window_list_1(window, minibuf, all_frames)) }
 }
 
+/// Return a list of all windows, on all frames, that display BUFFER.
+/// BUFFER defaults to the current buffer.  Minibuffer windows and
+/// windows on invisible frames are included.
+#[lisp_fn(min = "0")]
+pub fn buffer_window_list(buffer: LispBufferOrCurrent) -> LispObject {
+    let windows: Vec<LispObject> = buffer
+        .unwrap()
+        .windows_showing()
+        .map(LispWindowRef::as_lisp_obj)
+        .collect();
+    list(&windows)
+}
+
 /// Return non-nil when WINDOW is dedicated to its buffer.
 /// More precisely, return the value assigned by the last call of
 /// `set-window-dedicated-p' for WINDOW.  Return nil if that function was
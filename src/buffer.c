@@ -112,8 +112,6 @@ int last_per_buffer_idx;
 static void call_overlay_mod_hooks (Lisp_Object list, Lisp_Object overlay,
                                     bool after, Lisp_Object arg1,
                                     Lisp_Object arg2, Lisp_Object arg3);
-static void swap_out_buffer_local_variables (struct buffer *b);
-static void reset_buffer_local_variables (struct buffer *, bool);
 
 void drop_overlay (struct buffer *, struct Lisp_Overlay *);
 void unchain_both (struct buffer *, Lisp_Object);
@@ -125,10 +123,8 @@ Lisp_Object Vbuffer_alist;
 
 static Lisp_Object QSFundamental;	/* A string "Fundamental".  */
 
-static void alloc_buffer_text (struct buffer *, ptrdiff_t);
 static void free_buffer_text (struct buffer *b);
 static struct Lisp_Overlay * copy_overlays (struct buffer *, struct Lisp_Overlay *);
-static void modify_overlay (struct buffer *, ptrdiff_t, ptrdiff_t);
 static Lisp_Object buffer_lisp_local_variables (struct buffer *, bool);
 
 static void
@@ -376,107 +372,6 @@ bset_zv_marker (struct buffer *b, Lisp_Object val)
 }
 
 
-DEFUN ("get-buffer-create", Fget_buffer_create, Sget_buffer_create, 1, 1, 0,
-       doc: /* Return the buffer specified by BUFFER-OR-NAME, creating a new one if needed.
-If BUFFER-OR-NAME is a string and a live buffer with that name exists,
-return that buffer.  If no such buffer exists, create a new buffer with
-that name and return it.  If BUFFER-OR-NAME starts with a space, the new
-buffer does not keep undo information.
-
-If BUFFER-OR-NAME is a buffer instead of a string, return it as given,
-even if it is dead.  The return value is never nil.  */)
-  (register Lisp_Object buffer_or_name)
-{
-  register Lisp_Object buffer, name;
-  register struct buffer *b;
-
-  buffer = Fget_buffer (buffer_or_name);
-  if (!NILP (buffer))
-    return buffer;
-
-  if (SCHARS (buffer_or_name) == 0)
-    error ("Empty string for buffer name is not allowed");
-
-  b = allocate_buffer ();
-
-  /* An ordinary buffer uses its own struct buffer_text.  */
-  b->text = &b->own_text;
-  b->base_buffer = NULL;
-  /* No one shares the text with us now.  */
-  b->indirections = 0;
-  /* No one shows us now.  */
-  b->window_count = 0;
-
-  BUF_GAP_SIZE (b) = 20;
-  block_input ();
-  /* We allocate extra 1-byte at the tail and keep it always '\0' for
-     anchoring a search.  */
-  alloc_buffer_text (b, BUF_GAP_SIZE (b) + 1);
-  unblock_input ();
-  if (! BUF_BEG_ADDR (b))
-    buffer_memory_full (BUF_GAP_SIZE (b) + 1);
-
-  b->pt = BEG;
-  b->begv = BEG;
-  b->zv = BEG;
-  b->pt_byte = BEG_BYTE;
-  b->begv_byte = BEG_BYTE;
-  b->zv_byte = BEG_BYTE;
-
-  BUF_GPT (b) = BEG;
-  BUF_GPT_BYTE (b) = BEG_BYTE;
-
-  BUF_Z (b) = BEG;
-  BUF_Z_BYTE (b) = BEG_BYTE;
-  BUF_MODIFF (b) = 1;
-  BUF_CHARS_MODIFF (b) = 1;
-  BUF_OVERLAY_MODIFF (b) = 1;
-  BUF_SAVE_MODIFF (b) = 1;
-  BUF_COMPACT (b) = 1;
-  set_buffer_intervals (b, NULL);
-  BUF_UNCHANGED_MODIFIED (b) = 1;
-  BUF_OVERLAY_UNCHANGED_MODIFIED (b) = 1;
-  BUF_END_UNCHANGED (b) = 0;
-  BUF_BEG_UNCHANGED (b) = 0;
-  *(BUF_GPT_ADDR (b)) = *(BUF_Z_ADDR (b)) = 0; /* Put an anchor '\0'.  */
-  b->text->inhibit_shrinking = false;
-  b->text->redisplay = false;
-
-  b->newline_cache = 0;
-  b->width_run_cache = 0;
-  b->bidi_paragraph_cache = 0;
-  bset_width_table (b, Qnil);
-  b->prevent_redisplay_optimizations_p = 1;
-
-  /* An ordinary buffer normally doesn't need markers
-     to handle BEGV and ZV.  */
-  bset_pt_marker (b, Qnil);
-  bset_begv_marker (b, Qnil);
-  bset_zv_marker (b, Qnil);
-
-  name = Fcopy_sequence (buffer_or_name);
-  set_string_intervals (name, NULL);
-  bset_name (b, name);
-
-  bset_undo_list (b, SREF (name, 0) != ' ' ? Qnil : Qt);
-
-  reset_buffer (b);
-  reset_buffer_local_variables (b, 1);
-
-  bset_mark (b, Fmake_marker ());
-  BUF_MARKERS (b) = NULL;
-
-  /* Put this in the alist of all live buffers.  */
-  XSETBUFFER (buffer, b);
-  Vbuffer_alist = nconc2 (Vbuffer_alist, list1 (Fcons (name, buffer)));
-  /* And run buffer-list-update-hook.  */
-  if (!NILP (Vrun_hooks))
-    call1 (Vrun_hooks, Qbuffer_list_update_hook);
-
-  return buffer;
-}
-
-
 /* Return a list of overlays which is a copy of the overlay list
    LIST, but for buffer B.  */
 
@@ -531,7 +426,7 @@ set_buffer_overlays_after (struct buffer *b, struct Lisp_Overlay *o)
    are copied and made to refer to TO, and (3) overlay lists are
    copied.  */
 
-static void
+void
 clone_per_buffer_values (struct buffer *from, struct buffer *to)
 {
   int offset;
@@ -572,137 +467,6 @@ void record_buffer_markers (struct buffer *b);
 void fetch_buffer_markers (struct buffer *b);
 
 
-DEFUN ("make-indirect-buffer", Fmake_indirect_buffer, Smake_indirect_buffer,
-       2, 3,
-       "bMake indirect buffer (to buffer): \nBName of indirect buffer: ",
-       doc: /* Create and return an indirect buffer for buffer BASE-BUFFER, named NAME.
-BASE-BUFFER should be a live buffer, or the name of an existing buffer.
-NAME should be a string which is not the name of an existing buffer.
-Optional argument CLONE non-nil means preserve BASE-BUFFER's state,
-such as major and minor modes, in the indirect buffer.
-CLONE nil means the indirect buffer's state is reset to default values.  */)
-  (Lisp_Object base_buffer, Lisp_Object name, Lisp_Object clone)
-{
-  Lisp_Object buf, tem;
-  struct buffer *b;
-
-  CHECK_STRING (name);
-  buf = Fget_buffer (name);
-  if (!NILP (buf))
-    error ("Buffer name `%s' is in use", SDATA (name));
-
-  tem = base_buffer;
-  base_buffer = Fget_buffer (base_buffer);
-  if (NILP (base_buffer))
-    error ("No such buffer: `%s'", SDATA (tem));
-  if (!BUFFER_LIVE_P (XBUFFER (base_buffer)))
-    error ("Base buffer has been killed");
-
-  if (SCHARS (name) == 0)
-    error ("Empty string for buffer name is not allowed");
-
-  b = allocate_buffer ();
-
-  /* No double indirection - if base buffer is indirect,
-     new buffer becomes an indirect to base's base.  */
-  b->base_buffer = (XBUFFER (base_buffer)->base_buffer
-		    ? XBUFFER (base_buffer)->base_buffer
-		    : XBUFFER (base_buffer));
-
-  /* Use the base buffer's text object.  */
-  b->text = b->base_buffer->text;
-  /* We have no own text.  */
-  b->indirections = -1;
-  /* Notify base buffer that we share the text now.  */
-  b->base_buffer->indirections++;
-  /* Always -1 for an indirect buffer.  */
-  b->window_count = -1;
-
-  b->pt = b->base_buffer->pt;
-  b->begv = b->base_buffer->begv;
-  b->zv = b->base_buffer->zv;
-  b->pt_byte = b->base_buffer->pt_byte;
-  b->begv_byte = b->base_buffer->begv_byte;
-  b->zv_byte = b->base_buffer->zv_byte;
-
-  b->newline_cache = 0;
-  b->width_run_cache = 0;
-  b->bidi_paragraph_cache = 0;
-  bset_width_table (b, Qnil);
-
-  name = Fcopy_sequence (name);
-  set_string_intervals (name, NULL);
-  bset_name (b, name);
-
-  /* An indirect buffer shares undo list of its base (Bug#18180).  */
-  bset_undo_list (b, BVAR (b->base_buffer, undo_list));
-
-  reset_buffer (b);
-  reset_buffer_local_variables (b, 1);
-
-  /* Put this in the alist of all live buffers.  */
-  XSETBUFFER (buf, b);
-  Vbuffer_alist = nconc2 (Vbuffer_alist, list1 (Fcons (name, buf)));
-
-  bset_mark (b, Fmake_marker ());
-
-  /* The multibyte status belongs to the base buffer.  */
-  bset_enable_multibyte_characters
-    (b, BVAR (b->base_buffer, enable_multibyte_characters));
-
-  /* Make sure the base buffer has markers for its narrowing.  */
-  if (NILP (BVAR (b->base_buffer, pt_marker)))
-    {
-      eassert (NILP (BVAR (b->base_buffer, begv_marker)));
-      eassert (NILP (BVAR (b->base_buffer, zv_marker)));
-
-      bset_pt_marker (b->base_buffer,
-		      build_marker (b->base_buffer, b->base_buffer->pt,
-				    b->base_buffer->pt_byte));
-
-      bset_begv_marker (b->base_buffer,
-			build_marker (b->base_buffer, b->base_buffer->begv,
-				      b->base_buffer->begv_byte));
-
-      bset_zv_marker (b->base_buffer,
-		      build_marker (b->base_buffer, b->base_buffer->zv,
-				    b->base_buffer->zv_byte));
-
-      XMARKER (BVAR (b->base_buffer, zv_marker))->insertion_type = 1;
-    }
-
-  if (NILP (clone))
-    {
-      /* Give the indirect buffer markers for its narrowing.  */
-      bset_pt_marker (b, build_marker (b, b->pt, b->pt_byte));
-      bset_begv_marker (b, build_marker (b, b->begv, b->begv_byte));
-      bset_zv_marker (b, build_marker (b, b->zv, b->zv_byte));
-      XMARKER (BVAR (b, zv_marker))->insertion_type = 1;
-    }
-  else
-    {
-      struct buffer *old_b = current_buffer;
-
-      clone_per_buffer_values (b->base_buffer, b);
-      bset_filename (b, Qnil);
-      bset_file_truename (b, Qnil);
-      bset_display_count (b, make_number (0));
-      bset_backed_up (b, Qnil);
-      bset_auto_save_file_name (b, Qnil);
-      set_buffer_internal_1 (b);
-      Fset (intern ("buffer-save-without-query"), Qnil);
-      Fset (intern ("buffer-file-number"), Qnil);
-      Fset (intern ("buffer-stale-function"), Qnil);
-      set_buffer_internal_1 (old_b);
-    }
-
-  /* Run buffer-list-update-hook.  */
-  if (!NILP (Vrun_hooks))
-    call1 (Vrun_hooks, Qbuffer_list_update_hook);
-
-  return buf;
-}
-
 /* Mark OV as no longer associated with B.  */
 
 void
@@ -766,6 +530,10 @@ reset_buffer (register struct buffer *b)
   bset_backed_up (b, Qnil);
   BUF_AUTOSAVE_MODIFF (b) = 0;
   b->auto_save_failure_time = 0;
+  b->last_change_beg = 0;
+  b->last_change_end = 0;
+  invalidate_overlay_index (b);
+  invalidate_incremental_hash (b);
   bset_auto_save_file_name (b, Qnil);
   bset_read_only (b, Qnil);
   set_buffer_overlays_before (b, NULL);
@@ -794,7 +562,7 @@ reset_buffer (register struct buffer *b)
    If PERMANENT_TOO, reset permanent buffer-local variables.
    If not, preserve those.  */
 
-static void
+void
 reset_buffer_local_variables (struct buffer *b, bool permanent_too)
 {
   int offset, i;
@@ -896,56 +664,6 @@ reset_buffer_local_variables (struct buffer *b, bool permanent_too)
    and set-visited-file-name ought to be able to use this to really
    rename the buffer properly.  */
 
-DEFUN ("generate-new-buffer-name", Fgenerate_new_buffer_name,
-       Sgenerate_new_buffer_name, 1, 2, 0,
-       doc: /* Return a string that is the name of no existing buffer based on NAME.
-If there is no live buffer named NAME, then return NAME.
-Otherwise modify name by appending `<NUMBER>', incrementing NUMBER
-\(starting at 2) until an unused name is found, and then return that name.
-Optional second argument IGNORE specifies a name that is okay to use (if
-it is in the sequence to be tried) even if a buffer with that name exists.
-
-If NAME begins with a space (i.e., a buffer that is not normally
-visible to users), then if buffer NAME already exists a random number
-is first appended to NAME, to speed up finding a non-existent buffer.  */)
-  (Lisp_Object name, Lisp_Object ignore)
-{
-  Lisp_Object genbase;
-
-  CHECK_STRING (name);
-
-  if ((!NILP (ignore) && !NILP (Fstring_equal (name, ignore)))
-      || NILP (Fget_buffer (name)))
-    return name;
-
-  if (SREF (name, 0) != ' ') /* See bug#1229.  */
-    genbase = name;
-  else
-    {
-      char number[sizeof "-999999"];
-
-      /* Use XINT instead of XFASTINT to work around GCC bug 80776.  */
-      int i = XINT (Frandom (make_number (1000000)));
-      eassume (0 <= i && i < 1000000);
-
-      AUTO_STRING_WITH_LEN (lnumber, number, sprintf (number, "-%d", i));
-      genbase = concat2 (name, lnumber);
-      if (NILP (Fget_buffer (genbase)))
-	return genbase;
-    }
-
-  for (ptrdiff_t count = 2; ; count++)
-    {
-      char number[INT_BUFSIZE_BOUND (ptrdiff_t) + sizeof "<>"];
-      AUTO_STRING_WITH_LEN (lnumber, number,
-			    sprintf (number, "<%"pD"d>", count));
-      Lisp_Object gentemp = concat2 (genbase, lnumber);
-      if (!NILP (Fstring_equal (gentemp, ignore))
-	  || NILP (Fget_buffer (gentemp)))
-	return gentemp;
-    }
-}
-
 
 
 /* Like Fbuffer_local_value, but return Qunbound if the variable is
@@ -1078,135 +796,7 @@ No argument or nil as argument means use current buffer as BUFFER.  */)
   return result;
 }
 
-DEFUN ("set-buffer-modified-p", Fset_buffer_modified_p, Sset_buffer_modified_p,
-       1, 1, 0,
-       doc: /* Mark current buffer as modified or unmodified according to FLAG.
-A non-nil FLAG means mark the buffer modified.  */)
-  (Lisp_Object flag)
-{
-  Frestore_buffer_modified_p (flag);
-
-  /* Set update_mode_lines only if buffer is displayed in some window.
-     Packages like jit-lock or lazy-lock preserve a buffer's modified
-     state by recording/restoring the state around blocks of code.
-     Setting update_mode_lines makes redisplay consider all windows
-     (on all frames).  Stealth fontification of buffers not displayed
-     would incur additional redisplay costs if we'd set
-     update_modes_lines unconditionally.
-
-     Ideally, I think there should be another mechanism for fontifying
-     buffers without "modifying" buffers, or redisplay should be
-     smarter about updating the `*' in mode lines.  --gerd  */
-  return Fforce_mode_line_update (Qnil);
-}
-
-DEFUN ("restore-buffer-modified-p", Frestore_buffer_modified_p,
-       Srestore_buffer_modified_p, 1, 1, 0,
-       doc: /* Like `set-buffer-modified-p', with a difference concerning redisplay.
-It is not ensured that mode lines will be updated to show the modified
-state of the current buffer.  Use with care.  */)
-  (Lisp_Object flag)
-{
-  Lisp_Object fn;
-
-  /* If buffer becoming modified, lock the file.
-     If buffer becoming unmodified, unlock the file.  */
-
-  struct buffer *b = current_buffer->base_buffer
-    ? current_buffer->base_buffer
-    : current_buffer;
-
-  fn = BVAR (b, file_truename);
-  /* Test buffer-file-name so that binding it to nil is effective.  */
-  if (!NILP (fn) && ! NILP (BVAR (b, filename)))
-    {
-      bool already = SAVE_MODIFF < MODIFF;
-      if (!already && !NILP (flag))
-	lock_file (fn);
-      else if (already && NILP (flag))
-	unlock_file (fn);
-    }
-
-  /* Here we have a problem.  SAVE_MODIFF is used here to encode
-     buffer-modified-p (as SAVE_MODIFF<MODIFF) as well as
-     recent-auto-save-p (as SAVE_MODIFF<auto_save_modified).  So if we
-     modify SAVE_MODIFF to affect one, we may affect the other
-     as well.
-     E.g. if FLAG is nil we need to set SAVE_MODIFF to MODIFF, but
-     if SAVE_MODIFF<auto_save_modified that means we risk changing
-     recent-auto-save-p from t to nil.
-     Vice versa, if FLAG is non-nil and SAVE_MODIFF>=auto_save_modified
-     we risk changing recent-auto-save-p from nil to t.  */
-  SAVE_MODIFF = (NILP (flag)
-		 /* FIXME: This unavoidably sets recent-auto-save-p to nil.  */
-		 ? MODIFF
-		 /* Let's try to preserve recent-auto-save-p.  */
-		 : SAVE_MODIFF < MODIFF ? SAVE_MODIFF
-		 /* If SAVE_MODIFF == auto_save_modified == MODIFF,
-		    we can either decrease SAVE_MODIFF and auto_save_modified
-		    or increase MODIFF.  */
-		 : MODIFF++);
-
-  return flag;
-}
-
 
-DEFUN ("rename-buffer", Frename_buffer, Srename_buffer, 1, 2,
-       "(list (read-string \"Rename buffer (to new name): \" \
-	      nil 'buffer-name-history (buffer-name (current-buffer))) \
-	      current-prefix-arg)",
-       doc: /* Change current buffer's name to NEWNAME (a string).
-If second arg UNIQUE is nil or omitted, it is an error if a
-buffer named NEWNAME already exists.
-If UNIQUE is non-nil, come up with a new name using
-`generate-new-buffer-name'.
-Interactively, you can set UNIQUE with a prefix argument.
-We return the name we actually gave the buffer.
-This does not change the name of the visited file (if any).  */)
-  (register Lisp_Object newname, Lisp_Object unique)
-{
-  register Lisp_Object tem, buf;
-
-  CHECK_STRING (newname);
-
-  if (SCHARS (newname) == 0)
-    error ("Empty string is invalid as a buffer name");
-
-  tem = Fget_buffer (newname);
-  if (!NILP (tem))
-    {
-      /* Don't short-circuit if UNIQUE is t.  That is a useful way to
-	 rename the buffer automatically so you can create another
-	 with the original name.  It makes UNIQUE equivalent to
-	 (rename-buffer (generate-new-buffer-name NEWNAME)).  */
-      if (NILP (unique) && XBUFFER (tem) == current_buffer)
-	return BVAR (current_buffer, name);
-      if (!NILP (unique))
-	newname = Fgenerate_new_buffer_name (newname, BVAR (current_buffer, name));
-      else
-	error ("Buffer name `%s' is in use", SDATA (newname));
-    }
-
-  bset_name (current_buffer, newname);
-
-  /* Catch redisplay's attention.  Unless we do this, the mode lines for
-     any windows displaying current_buffer will stay unchanged.  */
-  update_mode_lines = 11;
-
-  XSETBUFFER (buf, current_buffer);
-  Fsetcar (Frassq (buf, Vbuffer_alist), newname);
-  if (NILP (BVAR (current_buffer, filename))
-      && !NILP (BVAR (current_buffer, auto_save_file_name)))
-    call0 (intern ("rename-auto-save-file"));
-
-  /* Run buffer-list-update-hook.  */
-  if (!NILP (Vrun_hooks))
-    call1 (Vrun_hooks, Qbuffer_list_update_hook);
-
-  /* Refetch since that last call may have done GC.  */
-  return BVAR (current_buffer, name);
-}
-
 /* True if B can be used as 'other-than-BUFFER' buffer.  */
 
 static bool
@@ -1365,239 +955,22 @@ compact_buffer (struct buffer *buffer)
     }
 }
 
-DEFUN ("kill-buffer", Fkill_buffer, Skill_buffer, 0, 1, "bKill buffer: ",
-       doc: /* Kill the buffer specified by BUFFER-OR-NAME.
-The argument may be a buffer or the name of an existing buffer.
-Argument nil or omitted means kill the current buffer.  Return t if the
-buffer is actually killed, nil otherwise.
-
-The functions in `kill-buffer-query-functions' are called with the
-buffer to be killed as the current buffer.  If any of them returns nil,
-the buffer is not killed.  The hook `kill-buffer-hook' is run before the
-buffer is actually killed.  The buffer being killed will be current
-while the hook is running.  Functions called by any of these hooks are
-supposed to not change the current buffer.
-
-Any processes that have this buffer as the `process-buffer' are killed
-with SIGHUP.  This function calls `replace-buffer-in-windows' for
-cleaning up all windows currently displaying the buffer to be killed. */)
-  (Lisp_Object buffer_or_name)
-{
-  Lisp_Object buffer;
-  struct buffer *b;
-  Lisp_Object tem;
-  struct Lisp_Marker *m;
-
-  if (NILP (buffer_or_name))
-    buffer = Fcurrent_buffer ();
-  else
-    buffer = Fget_buffer (buffer_or_name);
-  if (NILP (buffer))
-    nsberror (buffer_or_name);
-
-  b = XBUFFER (buffer);
-
-  /* Avoid trouble for buffer already dead.  */
-  if (!BUFFER_LIVE_P (b))
-    return Qnil;
-
-  if (thread_check_current_buffer (b))
-    return Qnil;
-
-  /* Run hooks with the buffer to be killed the current buffer.  */
-  {
-    ptrdiff_t count = SPECPDL_INDEX ();
-
-    record_unwind_protect (save_excursion_restore, save_excursion_save ());
-    set_buffer_internal (b);
-
-    /* First run the query functions; if any query is answered no,
-       don't kill the buffer.  */
-    tem = CALLN (Frun_hook_with_args_until_failure,
-		 Qkill_buffer_query_functions);
-    if (NILP (tem))
-      return unbind_to (count, Qnil);
-
-    /* Query if the buffer is still modified.  */
-    if (INTERACTIVE && !NILP (BVAR (b, filename))
-	&& BUF_MODIFF (b) > BUF_SAVE_MODIFF (b))
-      {
-	AUTO_STRING (format, "Buffer %s modified; kill anyway? ");
-	tem = do_yes_or_no_p (CALLN (Fformat, format, BVAR (b, name)));
-	if (NILP (tem))
-	  return unbind_to (count, Qnil);
-      }
-
-    /* If the hooks have killed the buffer, exit now.  */
-    if (!BUFFER_LIVE_P (b))
-      return unbind_to (count, Qt);
-
-    /* Then run the hooks.  */
-    run_hook (Qkill_buffer_hook);
-    unbind_to (count, Qnil);
-  }
-
-  /* If the hooks have killed the buffer, exit now.  */
-  if (!BUFFER_LIVE_P (b))
-    return Qt;
-
-  /* We have no more questions to ask.  Verify that it is valid
-     to kill the buffer.  This must be done after the questions
-     since anything can happen within do_yes_or_no_p.  */
-
-  /* Don't kill the minibuffer now current.  */
-  if (EQ (buffer, XWINDOW (minibuf_window)->contents))
-    return Qnil;
-
-  /* When we kill an ordinary buffer which shares its buffer text
-     with indirect buffer(s), we must kill indirect buffer(s) too.
-     We do it at this stage so nothing terrible happens if they
-     ask questions or their hooks get errors.  */
-  if (!b->base_buffer && b->indirections > 0)
-    {
-      struct buffer *other;
-
-      FOR_EACH_BUFFER (other)
-	if (other->base_buffer == b)
-	  {
-	    Lisp_Object buf;
-	    XSETBUFFER (buf, other);
-	    Fkill_buffer (buf);
-	  }
-
-      /* Exit if we now have killed the base buffer (Bug#11665).  */
-      if (!BUFFER_LIVE_P (b))
-	return Qt;
-    }
-
-  /* Run replace_buffer_in_windows before making another buffer current
-     since set-window-buffer-start-and-point will refuse to make another
-     buffer current if the selected window does not show the current
-     buffer (bug#10114).  */
-  replace_buffer_in_windows (buffer);
-
-  /* Exit if replacing the buffer in windows has killed our buffer.  */
-  if (!BUFFER_LIVE_P (b))
-    return Qt;
-
-  /* Make this buffer not be current.  Exit if it is the sole visible
-     buffer.  */
-  if (b == current_buffer)
-    {
-      tem = Fother_buffer (buffer, Qnil, Qnil);
-      Fset_buffer (tem);
-      if (b == current_buffer)
-	return Qnil;
-    }
-
-  /* If the buffer now current is shown in the minibuffer and our buffer
-     is the sole other buffer give up.  */
-  XSETBUFFER (tem, current_buffer);
-  if (EQ (tem, XWINDOW (minibuf_window)->contents)
-      && EQ (buffer, Fother_buffer (buffer, Qnil, Qnil)))
-    return Qnil;
-
-  /* Now there is no question: we can kill the buffer.  */
-
-  /* Unlock this buffer's file, if it is locked.  */
-  unlock_buffer (b);
+/* Kill the processes and xwidgets that belong to BUFFER, which
+   `kill-buffer' is about to kill.  */
 
+void
+kill_buffer_attachments (Lisp_Object buffer)
+{
   kill_buffer_processes (buffer);
   kill_buffer_xwidgets (buffer);
+}
 
-  /* Killing buffer processes may run sentinels which may have killed
-     our buffer.  */
-  if (!BUFFER_LIVE_P (b))
-    return Qt;
-
-  /* These may run Lisp code and into infinite loops (if someone
-     insisted on circular lists) so allow quitting here.  */
-  frames_discard_buffer (buffer);
-
-  clear_charpos_cache (b);
-
-  tem = Vinhibit_quit;
-  Vinhibit_quit = Qt;
-  /* Remove the buffer from the list of all buffers.  */
-  Vbuffer_alist = Fdelq (Frassq (buffer, Vbuffer_alist), Vbuffer_alist);
-  /* If replace_buffer_in_windows didn't do its job fix that now.  */
-  replace_buffer_in_windows_safely (buffer);
-  Vinhibit_quit = tem;
-
-  /* Delete any auto-save file, if we saved it in this session.
-     But not if the buffer is modified.  */
-  if (STRINGP (BVAR (b, auto_save_file_name))
-      && BUF_AUTOSAVE_MODIFF (b) != 0
-      && BUF_SAVE_MODIFF (b) < BUF_AUTOSAVE_MODIFF (b)
-      && BUF_SAVE_MODIFF (b) < BUF_MODIFF (b)
-      && NILP (Fsymbol_value (intern ("auto-save-visited-file-name"))))
-    {
-      Lisp_Object delete;
-      delete = Fsymbol_value (intern ("delete-auto-save-files"));
-      if (! NILP (delete))
-	internal_delete_file (BVAR (b, auto_save_file_name));
-    }
-
-  /* Deleting an auto-save file could have killed our buffer.  */
-  if (!BUFFER_LIVE_P (b))
-    return Qt;
-
-  if (b->base_buffer)
-    {
-      INTERVAL i;
-      /* Unchain all markers that belong to this indirect buffer.
-	 Don't unchain the markers that belong to the base buffer
-	 or its other indirect buffers.  */
-      struct Lisp_Marker **mp = &BUF_MARKERS (b);
-      while ((m = *mp))
-	{
-	  if (m->buffer == b)
-	    {
-	      m->buffer = NULL;
-	      *mp = m->next;
-	    }
-	  else
-	    mp = &m->next;
-	}
-      /* Intervals should be owned by the base buffer (Bug#16502).  */
-      i = buffer_intervals (b);
-      if (i)
-	{
-	  Lisp_Object owner;
-	  XSETBUFFER (owner, b->base_buffer);
-	  set_interval_object (i, owner);
-	}
-    }
-  else
-    {
-      /* Unchain all markers of this buffer and its indirect buffers.
-	 and leave them pointing nowhere.  */
-      for (m = BUF_MARKERS (b); m; )
-	{
-	  struct Lisp_Marker *next = m->next;
-	  m->buffer = 0;
-	  m->next = NULL;
-	  m = next;
-	}
-      BUF_MARKERS (b) = NULL;
-      set_buffer_intervals (b, NULL);
-
-      /* Perhaps we should explicitly free the interval tree here...  */
-    }
-  /* Since we've unlinked the markers, the overlays can't be here any more
-     either.  */
-  b->overlays_before = NULL;
-  b->overlays_after = NULL;
-
-  /* Reset the local variables, so that this buffer's local values
-     won't be protected from GC.  They would be protected
-     if they happened to remain cached in their symbols.
-     This gets rid of them for certain.  */
-  swap_out_buffer_local_variables (b);
-  reset_buffer_local_variables (b, 1);
-
-  bset_name (b, Qnil);
+/* Free what B, which `kill-buffer' has just killed, no longer needs:
+   its text, unless it is an indirect buffer, and its caches.  */
 
+void
+release_buffer_text (struct buffer *b)
+{
   block_input ();
   if (b->base_buffer)
     {
@@ -1633,13 +1006,6 @@ cleaning up all windows currently displaying the buffer to be killed. */)
     }
   bset_width_table (b, Qnil);
   unblock_input ();
-  bset_undo_list (b, Qnil);
-
-  /* Run buffer-list-update-hook.  */
-  if (!NILP (Vrun_hooks))
-    call1 (Vrun_hooks, Qbuffer_list_update_hook);
-
-  return Qt;
 }
 
 /* Move association for BUFFER to the front of buffer (a)lists.  Since
@@ -1878,489 +1244,6 @@ set_buffer_if_live (Lisp_Object buffer)
 }
 
 
-/* Advance BYTE_POS up to a character boundary
-   and return the adjusted position.  */
-
-static ptrdiff_t
-advance_to_char_boundary (ptrdiff_t byte_pos)
-{
-  int c;
-
-  if (byte_pos == BEG)
-    /* Beginning of buffer is always a character boundary.  */
-    return BEG;
-
-  c = FETCH_BYTE (byte_pos);
-  if (! CHAR_HEAD_P (c))
-    {
-      /* We should advance BYTE_POS only when C is a constituent of a
-         multibyte sequence.  */
-      ptrdiff_t orig_byte_pos = byte_pos;
-
-      do
-	{
-	  byte_pos--;
-	  c = FETCH_BYTE (byte_pos);
-	}
-      while (! CHAR_HEAD_P (c) && byte_pos > BEG);
-      INC_POS (byte_pos);
-      if (byte_pos < orig_byte_pos)
-	byte_pos = orig_byte_pos;
-      /* If C is a constituent of a multibyte sequence, BYTE_POS was
-         surely advance to the correct character boundary.  If C is
-         not, BYTE_POS was unchanged.  */
-    }
-
-  return byte_pos;
-}
-
-DEFUN ("buffer-swap-text", Fbuffer_swap_text, Sbuffer_swap_text,
-       1, 1, 0,
-       doc: /* Swap the text between current buffer and BUFFER.
-Using this function from `save-excursion' might produce surprising
-results, see Info node `(elisp)Swapping Text'.  */)
-  (Lisp_Object buffer)
-{
-  struct buffer *other_buffer;
-  CHECK_BUFFER (buffer);
-  other_buffer = XBUFFER (buffer);
-
-  if (!BUFFER_LIVE_P (other_buffer))
-    error ("Cannot swap a dead buffer's text");
-
-  /* Actually, it probably works just fine.
-   * if (other_buffer == current_buffer)
-   *   error ("Cannot swap a buffer's text with itself"); */
-
-  /* Actually, this may be workable as well, tho probably only if they're
-     *both* indirect.  */
-  if (other_buffer->base_buffer
-      || current_buffer->base_buffer)
-    error ("Cannot swap indirect buffers's text");
-
-  { /* This is probably harder to make work.  */
-    struct buffer *other;
-    FOR_EACH_BUFFER (other)
-      if (other->base_buffer == other_buffer
-	  || other->base_buffer == current_buffer)
-	error ("One of the buffers to swap has indirect buffers");
-  }
-
-#define swapfield(field, type) \
-  do {							\
-    type tmp##field = other_buffer->field;		\
-    other_buffer->field = current_buffer->field;	\
-    current_buffer->field = tmp##field;			\
-  } while (0)
-#define swapfield_(field, type) \
-  do {							\
-    type tmp##field = BVAR (other_buffer, field);		\
-    bset_##field (other_buffer, BVAR (current_buffer, field));	\
-    bset_##field (current_buffer, tmp##field);			\
-  } while (0)
-
-  swapfield (own_text, struct buffer_text);
-  eassert (current_buffer->text == &current_buffer->own_text);
-  eassert (other_buffer->text == &other_buffer->own_text);
-#ifdef REL_ALLOC
-  r_alloc_reset_variable ((void **) &current_buffer->own_text.beg,
-			  (void **) &other_buffer->own_text.beg);
-  r_alloc_reset_variable ((void **) &other_buffer->own_text.beg,
-			  (void **) &current_buffer->own_text.beg);
-#endif /* REL_ALLOC */
-
-  swapfield (pt, ptrdiff_t);
-  swapfield (pt_byte, ptrdiff_t);
-  swapfield (begv, ptrdiff_t);
-  swapfield (begv_byte, ptrdiff_t);
-  swapfield (zv, ptrdiff_t);
-  swapfield (zv_byte, ptrdiff_t);
-  eassert (!current_buffer->base_buffer);
-  eassert (!other_buffer->base_buffer);
-  swapfield (indirections, ptrdiff_t);
-  current_buffer->clip_changed = 1;	other_buffer->clip_changed = 1;
-  swapfield (newline_cache, struct region_cache *);
-  swapfield (width_run_cache, struct region_cache *);
-  swapfield (bidi_paragraph_cache, struct region_cache *);
-  current_buffer->prevent_redisplay_optimizations_p = 1;
-  other_buffer->prevent_redisplay_optimizations_p = 1;
-  swapfield (overlays_before, struct Lisp_Overlay *);
-  swapfield (overlays_after, struct Lisp_Overlay *);
-  swapfield (overlay_center, ptrdiff_t);
-  swapfield_ (undo_list, Lisp_Object);
-  swapfield_ (mark, Lisp_Object);
-  swapfield_ (enable_multibyte_characters, Lisp_Object);
-  swapfield_ (bidi_display_reordering, Lisp_Object);
-  swapfield_ (bidi_paragraph_direction, Lisp_Object);
-  swapfield_ (bidi_paragraph_separate_re, Lisp_Object);
-  swapfield_ (bidi_paragraph_start_re, Lisp_Object);
-  /* FIXME: Not sure what we should do with these *_marker fields.
-     Hopefully they're just nil anyway.  */
-  swapfield_ (pt_marker, Lisp_Object);
-  swapfield_ (begv_marker, Lisp_Object);
-  swapfield_ (zv_marker, Lisp_Object);
-  bset_point_before_scroll (current_buffer, Qnil);
-  bset_point_before_scroll (other_buffer, Qnil);
-
-  current_buffer->text->modiff++;	  other_buffer->text->modiff++;
-  current_buffer->text->chars_modiff++;	  other_buffer->text->chars_modiff++;
-  current_buffer->text->overlay_modiff++; other_buffer->text->overlay_modiff++;
-  current_buffer->text->beg_unchanged = current_buffer->text->gpt;
-  current_buffer->text->end_unchanged = current_buffer->text->gpt;
-  other_buffer->text->beg_unchanged = other_buffer->text->gpt;
-  other_buffer->text->end_unchanged = other_buffer->text->gpt;
-  {
-    struct Lisp_Marker *m;
-    for (m = BUF_MARKERS (current_buffer); m; m = m->next)
-      if (m->buffer == other_buffer)
-	m->buffer = current_buffer;
-      else
-	/* Since there's no indirect buffer in sight, markers on
-	   BUF_MARKERS(buf) should either be for `buf' or dead.  */
-	eassert (!m->buffer);
-    for (m = BUF_MARKERS (other_buffer); m; m = m->next)
-      if (m->buffer == current_buffer)
-	m->buffer = other_buffer;
-      else
-	/* Since there's no indirect buffer in sight, markers on
-	   BUF_MARKERS(buf) should either be for `buf' or dead.  */
-	eassert (!m->buffer);
-  }
-  { /* Some of the C code expects that both window markers of a
-       live window points to that window's buffer.  So since we
-       just swapped the markers between the two buffers, we need
-       to undo the effect of this swap for window markers.  */
-    Lisp_Object w = selected_window, ws = Qnil;
-    Lisp_Object buf1, buf2;
-    XSETBUFFER (buf1, current_buffer); XSETBUFFER (buf2, other_buffer);
-
-    while (NILP (Fmemq (w, ws)))
-      {
-	ws = Fcons (w, ws);
-	if (MARKERP (XWINDOW (w)->pointm)
-	    && (EQ (XWINDOW (w)->contents, buf1)
-		|| EQ (XWINDOW (w)->contents, buf2)))
-	  Fset_marker (XWINDOW (w)->pointm,
-		       make_number
-		       (BUF_BEGV (XBUFFER (XWINDOW (w)->contents))),
-		       XWINDOW (w)->contents);
-	/* Blindly copied from pointm part.  */
-	if (MARKERP (XWINDOW (w)->old_pointm)
-	    && (EQ (XWINDOW (w)->contents, buf1)
-		|| EQ (XWINDOW (w)->contents, buf2)))
-	  Fset_marker (XWINDOW (w)->old_pointm,
-		       make_number
-		       (BUF_BEGV (XBUFFER (XWINDOW (w)->contents))),
-		       XWINDOW (w)->contents);
-	if (MARKERP (XWINDOW (w)->start)
-	    && (EQ (XWINDOW (w)->contents, buf1)
-		|| EQ (XWINDOW (w)->contents, buf2)))
-	  Fset_marker (XWINDOW (w)->start,
-		       make_number
-		       (XBUFFER (XWINDOW (w)->contents)->last_window_start),
-		       XWINDOW (w)->contents);
-	w = Fnext_window (w, Qt, Qt);
-      }
-  }
-
-  if (current_buffer->text->intervals)
-    (eassert (EQ (current_buffer->text->intervals->up.obj, buffer)),
-     XSETBUFFER (current_buffer->text->intervals->up.obj, current_buffer));
-  if (other_buffer->text->intervals)
-    (eassert (EQ (other_buffer->text->intervals->up.obj, Fcurrent_buffer ())),
-     XSETBUFFER (other_buffer->text->intervals->up.obj, other_buffer));
-
-  return Qnil;
-}
-
-DEFUN ("set-buffer-multibyte", Fset_buffer_multibyte, Sset_buffer_multibyte,
-       1, 1, 0,
-       doc: /* Set the multibyte flag of the current buffer to FLAG.
-If FLAG is t, this makes the buffer a multibyte buffer.
-If FLAG is nil, this makes the buffer a single-byte buffer.
-In these cases, the buffer contents remain unchanged as a sequence of
-bytes but the contents viewed as characters do change.
-If FLAG is `to', this makes the buffer a multibyte buffer by changing
-all eight-bit bytes to eight-bit characters.
-If the multibyte flag was really changed, undo information of the
-current buffer is cleared.  */)
-  (Lisp_Object flag)
-{
-  struct Lisp_Marker *tail, *markers;
-  struct buffer *other;
-  ptrdiff_t begv, zv;
-  bool narrowed = (BEG != BEGV || Z != ZV);
-  bool modified_p = !NILP (Fbuffer_modified_p (Qnil));
-  Lisp_Object old_undo = BVAR (current_buffer, undo_list);
-
-  if (current_buffer->base_buffer)
-    error ("Cannot do `set-buffer-multibyte' on an indirect buffer");
-
-  /* Do nothing if nothing actually changes.  */
-  if (NILP (flag) == NILP (BVAR (current_buffer, enable_multibyte_characters)))
-    return flag;
-
-  /* Don't record these buffer changes.  We will put a special undo entry
-     instead.  */
-  bset_undo_list (current_buffer, Qt);
-
-  /* If the cached position is for this buffer, clear it out.  */
-  clear_charpos_cache (current_buffer);
-
-  if (NILP (flag))
-    begv = BEGV_BYTE, zv = ZV_BYTE;
-  else
-    begv = BEGV, zv = ZV;
-
-  if (narrowed)
-    error ("Changing multibyteness in a narrowed buffer");
-
-  invalidate_buffer_caches (current_buffer, BEGV, ZV);
-
-  if (NILP (flag))
-    {
-      ptrdiff_t pos, stop;
-      unsigned char *p;
-
-      /* Do this first, so it can use CHAR_TO_BYTE
-	 to calculate the old correspondences.  */
-      set_intervals_multibyte (0);
-
-      bset_enable_multibyte_characters (current_buffer, Qnil);
-
-      Z = Z_BYTE;
-      BEGV = BEGV_BYTE;
-      ZV = ZV_BYTE;
-      GPT = GPT_BYTE;
-      TEMP_SET_PT_BOTH (PT_BYTE, PT_BYTE);
-
-
-      for (tail = BUF_MARKERS (current_buffer); tail; tail = tail->next)
-	tail->charpos = tail->bytepos;
-
-      /* Convert multibyte form of 8-bit characters to unibyte.  */
-      pos = BEG;
-      stop = GPT;
-      p = BEG_ADDR;
-      while (1)
-	{
-	  int c, bytes;
-
-	  if (pos == stop)
-	    {
-	      if (pos == Z)
-		break;
-	      p = GAP_END_ADDR;
-	      stop = Z;
-	    }
-	  if (ASCII_CHAR_P (*p))
-	    p++, pos++;
-	  else if (CHAR_BYTE8_HEAD_P (*p))
-	    {
-	      c = STRING_CHAR_AND_LENGTH (p, bytes);
-	      /* Delete all bytes for this 8-bit character but the
-		 last one, and change the last one to the character
-		 code.  */
-	      bytes--;
-	      del_range_2 (pos, pos, pos + bytes, pos + bytes, 0);
-	      p = GAP_END_ADDR;
-	      *p++ = c;
-	      pos++;
-	      if (begv > pos)
-		begv -= bytes;
-	      if (zv > pos)
-		zv -= bytes;
-	      stop = Z;
-	    }
-	  else
-	    {
-	      bytes = BYTES_BY_CHAR_HEAD (*p);
-	      p += bytes, pos += bytes;
-	    }
-	}
-      if (narrowed)
-	Fnarrow_to_region (make_number (begv), make_number (zv));
-    }
-  else
-    {
-      ptrdiff_t pt = PT;
-      ptrdiff_t pos, stop;
-      unsigned char *p, *pend;
-
-      /* Be sure not to have a multibyte sequence striding over the GAP.
-	 Ex: We change this: "...abc\302 _GAP_ \241def..."
-	     to: "...abc _GAP_ \302\241def..."  */
-
-      if (EQ (flag, Qt)
-	  && GPT_BYTE > 1 && GPT_BYTE < Z_BYTE
-	  && ! CHAR_HEAD_P (*(GAP_END_ADDR)))
-	{
-	  unsigned char *q = GPT_ADDR - 1;
-
-	  while (! CHAR_HEAD_P (*q) && q > BEG_ADDR) q--;
-	  if (LEADING_CODE_P (*q))
-	    {
-	      ptrdiff_t new_gpt = GPT_BYTE - (GPT_ADDR - q);
-
-	      move_gap_both (new_gpt, new_gpt);
-	    }
-	}
-
-      /* Make the buffer contents valid as multibyte by converting
-	 8-bit characters to multibyte form.  */
-      pos = BEG;
-      stop = GPT;
-      p = BEG_ADDR;
-      pend = GPT_ADDR;
-      while (1)
-	{
-	  int bytes;
-
-	  if (pos == stop)
-	    {
-	      if (pos == Z)
-		break;
-	      p = GAP_END_ADDR;
-	      pend = Z_ADDR;
-	      stop = Z;
-	    }
-
-	  if (ASCII_CHAR_P (*p))
-	    p++, pos++;
-	  else if (EQ (flag, Qt)
-		   && ! CHAR_BYTE8_HEAD_P (*p)
-		   && (bytes = MULTIBYTE_LENGTH (p, pend)) > 0)
-	    p += bytes, pos += bytes;
-	  else
-	    {
-	      unsigned char tmp[MAX_MULTIBYTE_LENGTH];
-	      int c;
-
-	      c = BYTE8_TO_CHAR (*p);
-	      bytes = CHAR_STRING (c, tmp);
-	      *p = tmp[0];
-	      TEMP_SET_PT_BOTH (pos + 1, pos + 1);
-	      bytes--;
-	      insert_1_both ((char *) tmp + 1, bytes, bytes, 1, 0, 0);
-	      /* Now the gap is after the just inserted data.  */
-	      pos = GPT;
-	      p = GAP_END_ADDR;
-	      if (pos <= begv)
-		begv += bytes;
-	      if (pos <= zv)
-		zv += bytes;
-	      if (pos <= pt)
-		pt += bytes;
-	      pend = Z_ADDR;
-	      stop = Z;
-	    }
-	}
-
-      if (pt != PT)
-	TEMP_SET_PT (pt);
-
-      if (narrowed)
-	Fnarrow_to_region (make_number (begv), make_number (zv));
-
-      /* Do this first, so that chars_in_text asks the right question.
-	 set_intervals_multibyte needs it too.  */
-      bset_enable_multibyte_characters (current_buffer, Qt);
-
-      GPT_BYTE = advance_to_char_boundary (GPT_BYTE);
-      GPT = chars_in_text (BEG_ADDR, GPT_BYTE - BEG_BYTE) + BEG;
-
-      Z = chars_in_text (GAP_END_ADDR, Z_BYTE - GPT_BYTE) + GPT;
-
-      BEGV_BYTE = advance_to_char_boundary (BEGV_BYTE);
-      if (BEGV_BYTE > GPT_BYTE)
-	BEGV = chars_in_text (GAP_END_ADDR, BEGV_BYTE - GPT_BYTE) + GPT;
-      else
-	BEGV = chars_in_text (BEG_ADDR, BEGV_BYTE - BEG_BYTE) + BEG;
-
-      ZV_BYTE = advance_to_char_boundary (ZV_BYTE);
-      if (ZV_BYTE > GPT_BYTE)
-	ZV = chars_in_text (GAP_END_ADDR, ZV_BYTE - GPT_BYTE) + GPT;
-      else
-	ZV = chars_in_text (BEG_ADDR, ZV_BYTE - BEG_BYTE) + BEG;
-
-      {
-	ptrdiff_t byte = advance_to_char_boundary (PT_BYTE);
-	ptrdiff_t position;
-
-	if (byte > GPT_BYTE)
-	  position = chars_in_text (GAP_END_ADDR, byte - GPT_BYTE) + GPT;
-	else
-	  position = chars_in_text (BEG_ADDR, byte - BEG_BYTE) + BEG;
-	TEMP_SET_PT_BOTH (position, byte);
-      }
-
-      tail = markers = BUF_MARKERS (current_buffer);
-
-      /* This prevents BYTE_TO_CHAR (that is, buf_bytepos_to_charpos) from
-	 getting confused by the markers that have not yet been updated.
-	 It is also a signal that it should never create a marker.  */
-      BUF_MARKERS (current_buffer) = NULL;
-
-      for (; tail; tail = tail->next)
-	{
-	  tail->bytepos = advance_to_char_boundary (tail->bytepos);
-	  tail->charpos = BYTE_TO_CHAR (tail->bytepos);
-	}
-
-      /* Make sure no markers were put on the chain
-	 while the chain value was incorrect.  */
-      if (BUF_MARKERS (current_buffer))
-	emacs_abort ();
-
-      BUF_MARKERS (current_buffer) = markers;
-
-      /* Do this last, so it can calculate the new correspondences
-	 between chars and bytes.  */
-      set_intervals_multibyte (1);
-    }
-
-  if (!EQ (old_undo, Qt))
-    {
-      /* Represent all the above changes by a special undo entry.  */
-      bset_undo_list (current_buffer,
-		      Fcons (list3 (Qapply,
-				    intern ("set-buffer-multibyte"),
-				    NILP (flag) ? Qt : Qnil),
-			     old_undo));
-    }
-
-  current_buffer->prevent_redisplay_optimizations_p = 1;
-
-  /* If buffer is shown in a window, let redisplay consider other windows.  */
-  if (buffer_window_count (current_buffer))
-    windows_or_buffers_changed = 10;
-
-  /* Copy this buffer's new multibyte status
-     into all of its indirect buffers.  */
-  FOR_EACH_BUFFER (other)
-    if (other->base_buffer == current_buffer && BUFFER_LIVE_P (other))
-      {
-	BVAR (other, enable_multibyte_characters)
-	  = BVAR (current_buffer, enable_multibyte_characters);
-	other->prevent_redisplay_optimizations_p = 1;
-      }
-
-  /* Restore the modifiedness of the buffer.  */
-  if (!modified_p && !NILP (Fbuffer_modified_p (Qnil)))
-    Fset_buffer_modified_p (Qnil);
-
-  /* Update coding systems of this buffer's process (if any).  */
-  {
-    Lisp_Object process;
-
-    process = Fget_buffer_process (Fcurrent_buffer ());
-    if (PROCESSP (process))
-      setup_process_coding_systems (process);
-  }
-
-  return flag;
-}
-
 DEFUN ("kill-all-local-variables", Fkill_all_local_variables,
        Skill_all_local_variables, 0, 0, 0,
        doc: /* Switch to Fundamental mode by killing current buffer's local variables.
@@ -2401,7 +1284,7 @@ the normal hook `change-major-mode-hook'.  */)
 /* Make sure no local variables remain set up with buffer B
    for their current values.  */
 
-static void
+void
 swap_out_buffer_local_variables (struct buffer *b)
 {
   Lisp_Object oalist, alist, buffer;
@@ -3559,32 +2442,16 @@ for the rear of the overlay advance when text is inserted there
     }
   /* This puts it in the right list, and in the right order.  */
   recenter_overlay_lists (b, b->overlay_center);
+  invalidate_overlay_index (b);
 
   /* We don't need to redisplay the region covered by the overlay, because
-     the overlay has no properties at the moment.  */
+     the overlay has no properties at the moment.  But record that the
+     set of overlays has changed.  */
+  ++BUF_OVERLAY_MODIFF (b);
 
   return overlay;
 }
 
-/* Mark a section of BUF as needing redisplay because of overlays changes.  */
-
-static void
-modify_overlay (struct buffer *buf, ptrdiff_t start, ptrdiff_t end)
-{
-  if (start > end)
-    {
-      ptrdiff_t temp = start;
-      start = end;
-      end = temp;
-    }
-
-  BUF_COMPUTE_UNCHANGED (buf, start, end);
-
-  bset_redisplay (buf);
-
-  ++BUF_OVERLAY_MODIFF (buf);
-}
-
 /* Remove OVERLAY from LIST.  */
 
 static struct Lisp_Overlay *
@@ -3614,263 +2481,6 @@ unchain_both (struct buffer *b, Lisp_Object overlay)
   eassert (XOVERLAY (overlay)->next == NULL);
 }
 
-DEFUN ("move-overlay", Fmove_overlay, Smove_overlay, 3, 4, 0,
-       doc: /* Set the endpoints of OVERLAY to BEG and END in BUFFER.
-If BUFFER is omitted, leave OVERLAY in the same buffer it inhabits now.
-If BUFFER is omitted, and OVERLAY is in no buffer, put it in the current
-buffer.  */)
-  (Lisp_Object overlay, Lisp_Object beg, Lisp_Object end, Lisp_Object buffer)
-{
-  struct buffer *b, *ob = 0;
-  Lisp_Object obuffer;
-  ptrdiff_t count = SPECPDL_INDEX ();
-  ptrdiff_t n_beg, n_end;
-  ptrdiff_t o_beg UNINIT, o_end UNINIT;
-
-  CHECK_OVERLAY (overlay);
-  if (NILP (buffer))
-    buffer = Fmarker_buffer (OVERLAY_START (overlay));
-  if (NILP (buffer))
-    XSETBUFFER (buffer, current_buffer);
-  CHECK_BUFFER (buffer);
-
-  if (NILP (Fbuffer_live_p (buffer)))
-    error ("Attempt to move overlay to a dead buffer");
-
-  if (MARKERP (beg) && !EQ (Fmarker_buffer (beg), buffer))
-    signal_error ("Marker points into wrong buffer", beg);
-  if (MARKERP (end) && !EQ (Fmarker_buffer (end), buffer))
-    signal_error ("Marker points into wrong buffer", end);
-
-  CHECK_NUMBER_COERCE_MARKER (beg);
-  CHECK_NUMBER_COERCE_MARKER (end);
-
-  if (XINT (beg) > XINT (end))
-    {
-      Lisp_Object temp;
-      temp = beg; beg = end; end = temp;
-    }
-
-  specbind (Qinhibit_quit, Qt);
-
-  obuffer = Fmarker_buffer (OVERLAY_START (overlay));
-  b = XBUFFER (buffer);
-
-  if (!NILP (obuffer))
-    {
-      ob = XBUFFER (obuffer);
-
-      o_beg = OVERLAY_POSITION (OVERLAY_START (overlay));
-      o_end = OVERLAY_POSITION (OVERLAY_END (overlay));
-
-      unchain_both (ob, overlay);
-    }
-
-  /* Set the overlay boundaries, which may clip them.  */
-  Fset_marker (OVERLAY_START (overlay), beg, buffer);
-  Fset_marker (OVERLAY_END (overlay), end, buffer);
-
-  n_beg = marker_position (OVERLAY_START (overlay));
-  n_end = marker_position (OVERLAY_END (overlay));
-
-  /* If the overlay has changed buffers, do a thorough redisplay.  */
-  if (!EQ (buffer, obuffer))
-    {
-      /* Redisplay where the overlay was.  */
-      if (ob)
-        modify_overlay (ob, o_beg, o_end);
-
-      /* Redisplay where the overlay is going to be.  */
-      modify_overlay (b, n_beg, n_end);
-    }
-  else
-    /* Redisplay the area the overlay has just left, or just enclosed.  */
-    {
-      if (o_beg == n_beg)
-	modify_overlay (b, o_end, n_end);
-      else if (o_end == n_end)
-	modify_overlay (b, o_beg, n_beg);
-      else
-	modify_overlay (b, min (o_beg, n_beg), max (o_end, n_end));
-    }
-
-  /* Delete the overlay if it is empty after clipping and has the
-     evaporate property.  */
-  if (n_beg == n_end && !NILP (Foverlay_get (overlay, Qevaporate)))
-    return unbind_to (count, Fdelete_overlay (overlay));
-
-  /* Put the overlay into the new buffer's overlay lists, first on the
-     wrong list.  */
-  if (n_end < b->overlay_center)
-    {
-      XOVERLAY (overlay)->next = b->overlays_after;
-      set_buffer_overlays_after (b, XOVERLAY (overlay));
-    }
-  else
-    {
-      XOVERLAY (overlay)->next = b->overlays_before;
-      set_buffer_overlays_before (b, XOVERLAY (overlay));
-    }
-
-  /* This puts it in the right list, and in the right order.  */
-  recenter_overlay_lists (b, b->overlay_center);
-
-  return unbind_to (count, overlay);
-}
-
-
-/* Overlay dissection functions.  */
-
-
-DEFUN ("overlays-at", Foverlays_at, Soverlays_at, 1, 2, 0,
-       doc: /* Return a list of the overlays that contain the character at POS.
-If SORTED is non-nil, then sort them by decreasing priority.  */)
-  (Lisp_Object pos, Lisp_Object sorted)
-{
-  ptrdiff_t len, noverlays;
-  Lisp_Object *overlay_vec;
-  Lisp_Object result;
-
-  CHECK_NUMBER_COERCE_MARKER (pos);
-
-  if (!buffer_has_overlays ())
-    return Qnil;
-
-  len = 10;
-  /* We can't use alloca here because overlays_at can call xrealloc.  */
-  overlay_vec = xmalloc (len * sizeof *overlay_vec);
-
-  /* Put all the overlays we want in a vector in overlay_vec.
-     Store the length in len.  */
-  noverlays = overlays_at (XINT (pos), 1, &overlay_vec, &len,
-			   NULL, NULL, 0);
-
-  if (!NILP (sorted))
-    noverlays = sort_overlays (overlay_vec, noverlays,
-			       WINDOWP (sorted) ? XWINDOW (sorted) : NULL);
-
-  /* Make a list of them all.  */
-  result = Flist (noverlays, overlay_vec);
-
-  /* The doc string says the list should be in decreasing order of
-     priority, so we reverse the list, because sort_overlays sorts in
-     the increasing order of priority.  */
-  if (!NILP (sorted))
-    result = Fnreverse (result);
-
-  xfree (overlay_vec);
-  return result;
-}
-
-DEFUN ("overlays-in", Foverlays_in, Soverlays_in, 2, 2, 0,
-       doc: /* Return a list of the overlays that overlap the region BEG ... END.
-Overlap means that at least one character is contained within the overlay
-and also contained within the specified region.
-Empty overlays are included in the result if they are located at BEG,
-between BEG and END, or at END provided END denotes the position at the
-end of the buffer.  */)
-  (Lisp_Object beg, Lisp_Object end)
-{
-  ptrdiff_t len, noverlays;
-  Lisp_Object *overlay_vec;
-  Lisp_Object result;
-
-  CHECK_NUMBER_COERCE_MARKER (beg);
-  CHECK_NUMBER_COERCE_MARKER (end);
-
-  if (!buffer_has_overlays ())
-    return Qnil;
-
-  len = 10;
-  overlay_vec = xmalloc (len * sizeof *overlay_vec);
-
-  /* Put all the overlays we want in a vector in overlay_vec.
-     Store the length in len.  */
-  noverlays = overlays_in (XINT (beg), XINT (end), 1, &overlay_vec, &len,
-			   NULL, NULL);
-
-  /* Make a list of them all.  */
-  result = Flist (noverlays, overlay_vec);
-
-  xfree (overlay_vec);
-  return result;
-}
-
-DEFUN ("next-overlay-change", Fnext_overlay_change, Snext_overlay_change,
-       1, 1, 0,
-       doc: /* Return the next position after POS where an overlay starts or ends.
-If there are no overlay boundaries from POS to (point-max),
-the value is (point-max).  */)
-  (Lisp_Object pos)
-{
-  ptrdiff_t i, len, noverlays;
-  ptrdiff_t endpos;
-  Lisp_Object *overlay_vec;
-
-  CHECK_NUMBER_COERCE_MARKER (pos);
-
-  if (!buffer_has_overlays ())
-    return make_number (ZV);
-
-  len = 10;
-  overlay_vec = xmalloc (len * sizeof *overlay_vec);
-
-  /* Put all the overlays we want in a vector in overlay_vec.
-     Store the length in len.
-     endpos gets the position where the next overlay starts.  */
-  noverlays = overlays_at (XINT (pos), 1, &overlay_vec, &len,
-			   &endpos, 0, 1);
-
-  /* If any of these overlays ends before endpos,
-     use its ending point instead.  */
-  for (i = 0; i < noverlays; i++)
-    {
-      Lisp_Object oend;
-      ptrdiff_t oendpos;
-
-      oend = OVERLAY_END (overlay_vec[i]);
-      oendpos = OVERLAY_POSITION (oend);
-      if (oendpos < endpos)
-	endpos = oendpos;
-    }
-
-  xfree (overlay_vec);
-  return make_number (endpos);
-}
-
-DEFUN ("previous-overlay-change", Fprevious_overlay_change,
-       Sprevious_overlay_change, 1, 1, 0,
-       doc: /* Return the previous position before POS where an overlay starts or ends.
-If there are no overlay boundaries from (point-min) to POS,
-the value is (point-min).  */)
-  (Lisp_Object pos)
-{
-  ptrdiff_t prevpos;
-  Lisp_Object *overlay_vec;
-  ptrdiff_t len;
-
-  CHECK_NUMBER_COERCE_MARKER (pos);
-
-  if (!buffer_has_overlays ())
-    return make_number (BEGV);
-
-  /* At beginning of buffer, we know the answer;
-     avoid bug subtracting 1 below.  */
-  if (XINT (pos) == BEGV)
-    return pos;
-
-  len = 10;
-  overlay_vec = xmalloc (len * sizeof *overlay_vec);
-
-  /* Put all the overlays we want in a vector in overlay_vec.
-     Store the length in len.
-     prevpos gets the position of the previous change.  */
-  overlays_at (XINT (pos), 1, &overlay_vec, &len,
-	       0, &prevpos, 1);
-
-  xfree (overlay_vec);
-  return make_number (prevpos);
-}
 
 /* These functions are for debugging overlays.  */
 
@@ -3888,55 +2498,6 @@ for positions far away from POS).  */)
   return Qnil;
 }
 
-DEFUN ("overlay-get", Foverlay_get, Soverlay_get, 2, 2, 0,
-       doc: /* Get the property of overlay OVERLAY with property name PROP.  */)
-  (Lisp_Object overlay, Lisp_Object prop)
-{
-  CHECK_OVERLAY (overlay);
-  return lookup_char_property (XOVERLAY (overlay)->plist, prop, 0);
-}
-
-DEFUN ("overlay-put", Foverlay_put, Soverlay_put, 3, 3, 0,
-       doc: /* Set one property of overlay OVERLAY: give property PROP value VALUE.
-VALUE will be returned.*/)
-  (Lisp_Object overlay, Lisp_Object prop, Lisp_Object value)
-{
-  Lisp_Object tail, buffer;
-  bool changed;
-
-  CHECK_OVERLAY (overlay);
-
-  buffer = Fmarker_buffer (OVERLAY_START (overlay));
-
-  for (tail = XOVERLAY (overlay)->plist;
-       CONSP (tail) && CONSP (XCDR (tail));
-       tail = XCDR (XCDR (tail)))
-    if (EQ (XCAR (tail), prop))
-      {
-	changed = !EQ (XCAR (XCDR (tail)), value);
-	XSETCAR (XCDR (tail), value);
-	goto found;
-      }
-  /* It wasn't in the list, so add it to the front.  */
-  changed = !NILP (value);
-  set_overlay_plist
-    (overlay, Fcons (prop, Fcons (value, XOVERLAY (overlay)->plist)));
- found:
-  if (! NILP (buffer))
-    {
-      if (changed)
-	modify_overlay (XBUFFER (buffer),
-			marker_position (OVERLAY_START (overlay)),
-			marker_position (OVERLAY_END   (overlay)));
-      if (EQ (prop, Qevaporate) && ! NILP (value)
-	  && (OVERLAY_POSITION (OVERLAY_START (overlay))
-	      == OVERLAY_POSITION (OVERLAY_END (overlay))))
-	Fdelete_overlay (overlay);
-    }
-
-  return value;
-}
-
 /* Subroutine of report_overlay_modification.  */
 
 /* Lisp vector holding overlay hook functions to call.
@@ -4542,7 +3103,7 @@ mmap_realloc (void **var, size_t nbytes)
 
 /* Allocate NBYTES bytes for buffer B's text buffer.  */
 
-static void
+void
 alloc_buffer_text (struct buffer *b, ptrdiff_t nbytes)
 {
   void *p;
@@ -5800,31 +4361,15 @@ Functions running this hook are, `get-buffer-create',
   Vbuffer_list_update_hook = Qnil;
   DEFSYM (Qbuffer_list_update_hook, "buffer-list-update-hook");
 
-  defsubr (&Sget_buffer_create);
-  defsubr (&Smake_indirect_buffer);
-  defsubr (&Sgenerate_new_buffer_name);
   defsubr (&Sbuffer_local_variables);
-  defsubr (&Sset_buffer_modified_p);
-  defsubr (&Srename_buffer);
   defsubr (&Sother_buffer);
   defsubr (&Sbuffer_enable_undo);
-  defsubr (&Skill_buffer);
   defsubr (&Sbury_buffer_internal);
   defsubr (&Sset_buffer_major_mode);
-  defsubr (&Sbuffer_swap_text);
-  defsubr (&Sset_buffer_multibyte);
   defsubr (&Skill_all_local_variables);
 
   defsubr (&Smake_overlay);
-  defsubr (&Smove_overlay);
-  defsubr (&Soverlays_at);
-  defsubr (&Soverlays_in);
-  defsubr (&Snext_overlay_change);
-  defsubr (&Sprevious_overlay_change);
   defsubr (&Soverlay_recenter);
-  defsubr (&Soverlay_get);
-  defsubr (&Soverlay_put);
-  defsubr (&Srestore_buffer_modified_p);
 
   Fput (intern_c_string ("erase-buffer"), Qdisabled, Qt);
 }
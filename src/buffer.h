@@ -476,6 +476,11 @@ struct buffer_text
        end_unchanged contain no useful information.  */
     EMACS_INT overlay_unchanged_modified;
 
+    /* Earliest position changed since `buffer-redisplay-start-hint' last
+       consumed it, as recorded by signal_after_change; zero if nothing
+       has changed since.  */
+    ptrdiff_t redisplay_start_hint;
+
     /* Properties of this buffer's text.  */
     INTERVAL intervals;
 
@@ -903,6 +908,25 @@ struct buffer
 
   bool is_cached;
   EMACS_INT cached_modiff;
+
+  /* Bounds of the text affected by the most recent change to this
+     buffer, as recorded by signal_after_change.  Both are zero if the
+     buffer has not been changed since it was created.  */
+  ptrdiff_t last_change_beg;
+  ptrdiff_t last_change_end;
+
+  /* Lazily built index of this buffer's overlays, owned by the Rust
+     side (see buffers.rs).  The overlay lists above remain the
+     authoritative record; this is only a cache used to speed up
+     `overlays-at' and `overlays-in' in buffers with many overlays.  */
+  void *overlay_index;
+
+  /* Checksum of this buffer's text, kept up to date by the insertion
+     and deletion primitives once `buffer-text-checksum' has asked for
+     it; owned by the Rust side (see buffers.rs).  Only used in buffers
+     that are not indirect.  Freed by invalidate_incremental_hash, which
+     kill-buffer and reset_buffer call.  */
+  void *text_hash;
 };
 
 INLINE bool
@@ -1116,6 +1140,12 @@ extern struct buffer buffer_local_symbols;
 
 extern void delete_all_overlays (struct buffer *);
 extern void reset_buffer (struct buffer *);
+extern void reset_buffer_local_variables (struct buffer *, bool);
+extern void clone_per_buffer_values (struct buffer *, struct buffer *);
+extern void alloc_buffer_text (struct buffer *, ptrdiff_t);
+extern void swap_out_buffer_local_variables (struct buffer *);
+extern void kill_buffer_attachments (Lisp_Object);
+extern void release_buffer_text (struct buffer *);
 extern void compact_buffer (struct buffer *);
 extern void evaporate_overlays (ptrdiff_t);
 extern ptrdiff_t overlays_at (EMACS_INT, bool, Lisp_Object **,
@@ -1463,71 +1463,6 @@ insert1 (Lisp_Object arg)
 }
 
 
-DEFUN ("insert", Finsert, Sinsert, 0, MANY, 0,
-       doc: /* Insert the arguments, either strings or characters, at point.
-Point and after-insertion markers move forward to end up
- after the inserted text.
-Any other markers at the point of insertion remain before the text.
-
-If the current buffer is multibyte, unibyte strings are converted
-to multibyte for insertion (see `string-make-multibyte').
-If the current buffer is unibyte, multibyte strings are converted
-to unibyte for insertion (see `string-make-unibyte').
-
-When operating on binary data, it may be necessary to preserve the
-original bytes of a unibyte string when inserting it into a multibyte
-buffer; to accomplish this, apply `string-as-multibyte' to the string
-and insert the result.
-
-usage: (insert &rest ARGS)  */)
-  (ptrdiff_t nargs, Lisp_Object *args)
-{
-  general_insert_function (insert, insert_from_string, 0, nargs, args);
-  return Qnil;
-}
-
-DEFUN ("insert-and-inherit", Finsert_and_inherit, Sinsert_and_inherit,
-   0, MANY, 0,
-       doc: /* Insert the arguments at point, inheriting properties from adjoining text.
-Point and after-insertion markers move forward to end up
- after the inserted text.
-Any other markers at the point of insertion remain before the text.
-
-If the current buffer is multibyte, unibyte strings are converted
-to multibyte for insertion (see `unibyte-char-to-multibyte').
-If the current buffer is unibyte, multibyte strings are converted
-to unibyte for insertion.
-
-usage: (insert-and-inherit &rest ARGS)  */)
-  (ptrdiff_t nargs, Lisp_Object *args)
-{
-  general_insert_function (insert_and_inherit, insert_from_string, 1,
-			   nargs, args);
-  return Qnil;
-}
-
-DEFUN ("insert-before-markers", Finsert_before_markers, Sinsert_before_markers, 0, MANY, 0,
-       doc: /* Insert strings or characters at point, relocating markers after the text.
-Point and markers move forward to end up after the inserted text.
-
-If the current buffer is multibyte, unibyte strings are converted
-to multibyte for insertion (see `unibyte-char-to-multibyte').
-If the current buffer is unibyte, multibyte strings are converted
-to unibyte for insertion.
-
-If an overlay begins at the insertion point, the inserted text falls
-outside the overlay; if a nonempty overlay ends at the insertion
-point, the inserted text falls inside that overlay.
-
-usage: (insert-before-markers &rest ARGS)  */)
-  (ptrdiff_t nargs, Lisp_Object *args)
-{
-  general_insert_function (insert_before_markers,
-			   insert_from_string_before_markers, 0,
-			   nargs, args);
-  return Qnil;
-}
-
 DEFUN ("insert-before-markers-and-inherit", Finsert_and_inherit_before_markers,
   Sinsert_and_inherit_before_markers, 0, MANY, 0,
        doc: /* Insert text at point, relocating markers and inheriting properties.
@@ -1663,42 +1598,6 @@ update_buffer_properties (ptrdiff_t start, ptrdiff_t end)
     }
 }
 
-DEFUN ("buffer-substring", Fbuffer_substring, Sbuffer_substring, 2, 2, 0,
-       doc: /* Return the contents of part of the current buffer as a string.
-The two arguments START and END are character positions;
-they can be in either order.
-The string returned is multibyte if the buffer is multibyte.
-
-This function copies the text properties of that part of the buffer
-into the result string; if you don't want the text properties,
-use `buffer-substring-no-properties' instead.  */)
-  (Lisp_Object start, Lisp_Object end)
-{
-  register ptrdiff_t b, e;
-
-  validate_region (&start, &end);
-  b = XINT (start);
-  e = XINT (end);
-
-  return make_buffer_string (b, e, 1);
-}
-
-DEFUN ("buffer-substring-no-properties", Fbuffer_substring_no_properties,
-       Sbuffer_substring_no_properties, 2, 2, 0,
-       doc: /* Return the characters of part of the buffer, without the text properties.
-The two arguments START and END are character positions;
-they can be in either order.  */)
-  (Lisp_Object start, Lisp_Object end)
-{
-  register ptrdiff_t b, e;
-
-  validate_region (&start, &end);
-  b = XINT (start);
-  e = XINT (end);
-
-  return make_buffer_string (b, e, 0);
-}
-
 DEFUN ("compare-buffer-substrings", Fcompare_buffer_substrings, Scompare_buffer_substrings,
        6, 6, 0,
        doc: /* Compare two substrings of two buffers; return result as number.
@@ -2060,198 +1959,6 @@ buffer_chars_equal (struct context *ctx,
 }
 
 
-static void
-subst_char_in_region_unwind (Lisp_Object arg)
-{
-  bset_undo_list (current_buffer, arg);
-}
-
-static void
-subst_char_in_region_unwind_1 (Lisp_Object arg)
-{
-  bset_filename (current_buffer, arg);
-}
-
-DEFUN ("subst-char-in-region", Fsubst_char_in_region,
-       Ssubst_char_in_region, 4, 5, 0,
-       doc: /* From START to END, replace FROMCHAR with TOCHAR each time it occurs.
-If optional arg NOUNDO is non-nil, don't record this change for undo
-and don't mark the buffer as really changed.
-Both characters must have the same length of multi-byte form.  */)
-  (Lisp_Object start, Lisp_Object end, Lisp_Object fromchar, Lisp_Object tochar, Lisp_Object noundo)
-{
-  register ptrdiff_t pos, pos_byte, stop, i, len, end_byte;
-  /* Keep track of the first change in the buffer:
-     if 0 we haven't found it yet.
-     if < 0 we've found it and we've run the before-change-function.
-     if > 0 we've actually performed it and the value is its position.  */
-  ptrdiff_t changed = 0;
-  unsigned char fromstr[MAX_MULTIBYTE_LENGTH], tostr[MAX_MULTIBYTE_LENGTH];
-  unsigned char *p;
-  ptrdiff_t count = SPECPDL_INDEX ();
-#define COMBINING_NO	 0
-#define COMBINING_BEFORE 1
-#define COMBINING_AFTER  2
-#define COMBINING_BOTH (COMBINING_BEFORE | COMBINING_AFTER)
-  int maybe_byte_combining = COMBINING_NO;
-  ptrdiff_t last_changed = 0;
-  bool multibyte_p
-    = !NILP (BVAR (current_buffer, enable_multibyte_characters));
-  int fromc, toc;
-
- restart:
-
-  validate_region (&start, &end);
-  CHECK_CHARACTER (fromchar);
-  CHECK_CHARACTER (tochar);
-  fromc = XFASTINT (fromchar);
-  toc = XFASTINT (tochar);
-
-  if (multibyte_p)
-    {
-      len = CHAR_STRING (fromc, fromstr);
-      if (CHAR_STRING (toc, tostr) != len)
-	error ("Characters in `subst-char-in-region' have different byte-lengths");
-      if (!ASCII_CHAR_P (*tostr))
-	{
-	  /* If *TOSTR is in the range 0x80..0x9F and TOCHAR is not a
-	     complete multibyte character, it may be combined with the
-	     after bytes.  If it is in the range 0xA0..0xFF, it may be
-	     combined with the before and after bytes.  */
-	  if (!CHAR_HEAD_P (*tostr))
-	    maybe_byte_combining = COMBINING_BOTH;
-	  else if (BYTES_BY_CHAR_HEAD (*tostr) > len)
-	    maybe_byte_combining = COMBINING_AFTER;
-	}
-    }
-  else
-    {
-      len = 1;
-      fromstr[0] = fromc;
-      tostr[0] = toc;
-    }
-
-  pos = XINT (start);
-  pos_byte = CHAR_TO_BYTE (pos);
-  stop = CHAR_TO_BYTE (XINT (end));
-  end_byte = stop;
-
-  /* If we don't want undo, turn off putting stuff on the list.
-     That's faster than getting rid of things,
-     and it prevents even the entry for a first change.
-     Also inhibit locking the file.  */
-  if (!changed && !NILP (noundo))
-    {
-      record_unwind_protect (subst_char_in_region_unwind,
-			     BVAR (current_buffer, undo_list));
-      bset_undo_list (current_buffer, Qt);
-      /* Don't do file-locking.  */
-      record_unwind_protect (subst_char_in_region_unwind_1,
-			     BVAR (current_buffer, filename));
-      bset_filename (current_buffer, Qnil);
-    }
-
-  if (pos_byte < GPT_BYTE)
-    stop = min (stop, GPT_BYTE);
-  while (1)
-    {
-      ptrdiff_t pos_byte_next = pos_byte;
-
-      if (pos_byte >= stop)
-	{
-	  if (pos_byte >= end_byte) break;
-	  stop = end_byte;
-	}
-      p = BYTE_POS_ADDR (pos_byte);
-      if (multibyte_p)
-	INC_POS (pos_byte_next);
-      else
-	++pos_byte_next;
-      if (pos_byte_next - pos_byte == len
-	  && p[0] == fromstr[0]
-	  && (len == 1
-	      || (p[1] == fromstr[1]
-		  && (len == 2 || (p[2] == fromstr[2]
-				 && (len == 3 || p[3] == fromstr[3]))))))
-	{
-	  if (changed < 0)
-	    /* We've already seen this and run the before-change-function;
-	       this time we only need to record the actual position. */
-	    changed = pos;
-	  else if (!changed)
-	    {
-	      changed = -1;
-	      modify_text (pos, XINT (end));
-
-	      if (! NILP (noundo))
-		{
-		  if (MODIFF - 1 == SAVE_MODIFF)
-		    SAVE_MODIFF++;
-		  if (MODIFF - 1 == BUF_AUTOSAVE_MODIFF (current_buffer))
-		    BUF_AUTOSAVE_MODIFF (current_buffer)++;
-		}
-
-	      /* The before-change-function may have moved the gap
-		 or even modified the buffer so we should start over. */
-	      goto restart;
-	    }
-
-	  /* Take care of the case where the new character
-	     combines with neighboring bytes.  */
-	  if (maybe_byte_combining
-	      && (maybe_byte_combining == COMBINING_AFTER
-		  ? (pos_byte_next < Z_BYTE
-		     && ! CHAR_HEAD_P (FETCH_BYTE (pos_byte_next)))
-		  : ((pos_byte_next < Z_BYTE
-		      && ! CHAR_HEAD_P (FETCH_BYTE (pos_byte_next)))
-		     || (pos_byte > BEG_BYTE
-			 && ! ASCII_CHAR_P (FETCH_BYTE (pos_byte - 1))))))
-	    {
-	      Lisp_Object tem, string;
-
-	      tem = BVAR (current_buffer, undo_list);
-
-	      /* Make a multibyte string containing this single character.  */
-	      string = make_multibyte_string ((char *) tostr, 1, len);
-	      /* replace_range is less efficient, because it moves the gap,
-		 but it handles combining correctly.  */
-	      replace_range (pos, pos + 1, string,
-			     0, 0, 1, 0);
-	      pos_byte_next = CHAR_TO_BYTE (pos);
-	      if (pos_byte_next > pos_byte)
-		/* Before combining happened.  We should not increment
-		   POS.  So, to cancel the later increment of POS,
-		   decrease it now.  */
-		pos--;
-	      else
-		INC_POS (pos_byte_next);
-
-	      if (! NILP (noundo))
-		bset_undo_list (current_buffer, tem);
-	    }
-	  else
-	    {
-	      if (NILP (noundo))
-		record_change (pos, 1);
-	      for (i = 0; i < len; i++) *p++ = tostr[i];
-	    }
-	  last_changed =  pos + 1;
-	}
-      pos_byte = pos_byte_next;
-      pos++;
-    }
-
-  if (changed > 0)
-    {
-      signal_after_change (changed,
-			   last_changed - changed, last_changed - changed);
-      update_compositions (changed, last_changed, CHECK_ALL);
-    }
-
-  unbind_to (count, Qnil);
-  return Qnil;
-}
-
 
 static Lisp_Object check_translation (ptrdiff_t, ptrdiff_t, ptrdiff_t,
 				      Lisp_Object);
@@ -2510,9 +2217,15 @@ DEFUN ("delete-and-extract-region", Fdelete_and_extract_region,
 
 DEFUN ("widen", Fwiden, Swiden, 0, 0, "",
        doc: /* Remove restrictions (narrowing) from current buffer.
-This allows the buffer's full text to be seen and edited.  */)
+This allows the buffer's full text to be seen and edited.
+A restriction made with `internal-labeled-narrow-to-region' is not
+removed; the buffer is only widened back to its bounds.  */)
   (void)
 {
+  Lisp_Object locked = labeled_restriction_bounds ();
+  if (!NILP (locked))
+    return Fnarrow_to_region (XCAR (locked), XCDR (locked));
+
   if (BEG != BEGV || Z != ZV)
     current_buffer->clip_changed = 1;
   BEGV = BEG;
@@ -2531,7 +2244,9 @@ text is included in the file.  \\[widen] makes all visible again.
 See also `save-restriction'.
 
 When calling from a program, pass two arguments; positions (integers
-or markers) bounding the text that should remain visible.  */)
+or markers) bounding the text that should remain visible.  While a
+restriction made with `internal-labeled-narrow-to-region' is in effect,
+the new bounds are limited to that restriction.  */)
   (register Lisp_Object start, Lisp_Object end)
 {
   CHECK_NUMBER_COERCE_MARKER (start);
@@ -2546,6 +2261,16 @@ or markers) bounding the text that should remain visible.  */)
   if (!(BEG <= XINT (start) && XINT (start) <= XINT (end) && XINT (end) <= Z))
     args_out_of_range (start, end);
 
+  /* Don't let the new bounds escape a labeled restriction.  */
+  Lisp_Object locked = labeled_restriction_bounds ();
+  if (!NILP (locked))
+    {
+      ptrdiff_t lock_beg = marker_position (XCAR (locked));
+      ptrdiff_t lock_end = marker_position (XCDR (locked));
+      XSETFASTINT (start, clip_to_bounds (lock_beg, XFASTINT (start), lock_end));
+      XSETFASTINT (end, clip_to_bounds (lock_beg, XFASTINT (end), lock_end));
+    }
+
   if (BEGV != XFASTINT (start) || ZV != XFASTINT (end))
     current_buffer->clip_changed = 1;
 
@@ -4023,6 +3748,8 @@ Transposing beyond buffer boundaries is an error.  */)
 }
 
 
+extern void rust_syms_of_editfns (void);
+
 void
 syms_of_editfns (void)
 {
@@ -4040,6 +3767,8 @@ Each function is called with two arguments which specify the range
 of the buffer being accessed.  */);
   Vbuffer_access_fontify_functions = Qnil;
 
+  rust_syms_of_editfns ();
+
   {
     Lisp_Object obuf;
     obuf = Fcurrent_buffer ();
@@ -4074,8 +3803,6 @@ functions if all the text being accessed has this property.  */);
   DEFVAR_LISP ("operating-system-release", Voperating_system_release,
 	       doc: /* The release of the operating system Emacs is running on.  */);
 
-  defsubr (&Sbuffer_substring);
-  defsubr (&Sbuffer_substring_no_properties);
   defsubr (&Sget_pos_property);
 
   /* Symbol for the text property used to mark fields.  */
@@ -4088,9 +3815,6 @@ functions if all the text being accessed has this property.  */);
   defsubr (&Sfield_string_no_properties);
   defsubr (&Sdelete_field);
 
-  defsubr (&Sinsert);
-  defsubr (&Sinsert_before_markers);
-  defsubr (&Sinsert_and_inherit);
   defsubr (&Sinsert_and_inherit_before_markers);
 
   defsubr (&Suser_login_name);
@@ -4114,7 +3838,6 @@ functions if all the text being accessed has this property.  */);
 
   defsubr (&Scompare_buffer_substrings);
   defsubr (&Sreplace_buffer_contents);
-  defsubr (&Ssubst_char_in_region);
   defsubr (&Stranslate_region_internal);
   defsubr (&Sdelete_region);
   defsubr (&Sdelete_and_extract_region);
@@ -311,6 +311,8 @@ adjust_markers_for_insert (ptrdiff_t from, ptrdiff_t from_byte,
       fix_start_end_in_overlays (from, to);
       fix_overlays_before (current_buffer, from, to);
     }
+
+  adjust_window_starts_after_insert (current_buffer, from_byte, nbytes);
 }
 
 /* Adjust point for an insertion of NBYTES bytes, which are NCHARS characters.
@@ -1983,8 +1985,15 @@ void
 prepare_to_modify_buffer (ptrdiff_t start, ptrdiff_t end,
 			  ptrdiff_t *preserve_ptr)
 {
+  EMACS_INT modiff = MODIFF;
+
   prepare_to_modify_buffer_1 (start, end, preserve_ptr);
   invalidate_buffer_caches (current_buffer, start, end);
+  /* If the hooks changed the text, START and END may be stale.  */
+  if (MODIFF == modiff)
+    incremental_hash_before_change (current_buffer, start, end);
+  else
+    invalidate_incremental_hash (current_buffer);
 }
 
 /* Invalidate the caches maintained by the buffer BUF, if any, for the
@@ -2113,7 +2122,7 @@ signal_before_change (ptrdiff_t start_int, ptrdiff_t end_int,
 
   /* If buffer is unmodified, run a special hook for that case.  The
    check for Vfirst_change_hook is just a minor optimization.  */
-  if (SAVE_MODIFF >= MODIFF
+  if (first_change_pending_p ()
       && !NILP (Vfirst_change_hook))
     {
       PRESERVE_VALUE;
@@ -2170,6 +2179,15 @@ signal_after_change (ptrdiff_t charpos, ptrdiff_t lendel, ptrdiff_t lenins)
   ptrdiff_t count = SPECPDL_INDEX ();
   struct rvoe_arg rvoe_arg;
 
+  /* Record the change even when the hooks are inhibited, so that
+     `buffer-last-change-region' sees every change.  */
+  current_buffer->last_change_beg = charpos;
+  current_buffer->last_change_end = charpos + lenins;
+  if (current_buffer->text->redisplay_start_hint == 0
+      || charpos < current_buffer->text->redisplay_start_hint)
+    current_buffer->text->redisplay_start_hint = charpos;
+  incremental_hash_after_change (current_buffer, charpos, lendel, lenins);
+
   if (inhibit_modification_hooks)
     return;
 
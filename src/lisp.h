@@ -4001,6 +4001,7 @@ extern Lisp_Object make_buffer_string_both (ptrdiff_t, ptrdiff_t, ptrdiff_t,
 					    ptrdiff_t, bool);
 extern void init_editfns (bool);
 extern void syms_of_editfns (void);
+extern Lisp_Object labeled_restriction_bounds (void);
 
 /* Defined in buffer.c.  */
 extern bool mouse_face_overlay_overlaps (Lisp_Object);
@@ -4018,6 +4019,18 @@ extern void init_buffer_once (void);
 extern void init_buffer (int);
 extern void syms_of_buffer (void);
 extern void keys_of_buffer (void);
+extern bool first_change_pending_p (void);
+extern bool run_kill_buffer_hooks (struct buffer *, bool);
+extern void init_buffer_name_defaults (struct buffer *);
+extern void run_buffer_list_update_hook (void);
+extern void invalidate_overlay_index (struct buffer *);
+extern Lisp_Object normalize_overlay_face (Lisp_Object);
+extern void modify_overlay (struct buffer *, ptrdiff_t, ptrdiff_t);
+extern void invalidate_incremental_hash (struct buffer *);
+extern void incremental_hash_before_change (struct buffer *, ptrdiff_t,
+					    ptrdiff_t);
+extern void incremental_hash_after_change (struct buffer *, ptrdiff_t,
+					   ptrdiff_t, ptrdiff_t);
 
 /* Defined in marker.c.  */
 
@@ -4031,6 +4044,7 @@ extern Lisp_Object set_marker_restricted (Lisp_Object, Lisp_Object, Lisp_Object)
 extern Lisp_Object set_marker_both (Lisp_Object, Lisp_Object, ptrdiff_t, ptrdiff_t);
 extern Lisp_Object set_marker_restricted_both (Lisp_Object, Lisp_Object,
                                                ptrdiff_t, ptrdiff_t);
+extern void check_marker_positions (struct buffer *);
 
 /* Defined in fileio.c.  */
 
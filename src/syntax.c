@@ -1467,42 +1467,6 @@ scan_words (ptrdiff_t from, EMACS_INT count)
   return from;
 }
 
-DEFUN ("forward-word", Fforward_word, Sforward_word, 0, 1, "^p",
-       doc: /* Move point forward ARG words (backward if ARG is negative).
-If ARG is omitted or nil, move point forward one word.
-Normally returns t.
-If an edge of the buffer or a field boundary is reached, point is
-left there and the function returns nil.  Field boundaries are not
-noticed if `inhibit-field-text-motion' is non-nil.
-
-The word boundaries are normally determined by the buffer's syntax
-table, but `find-word-boundary-function-table', such as set up
-by `subword-mode', can change that.  If a Lisp program needs to
-move by words determined strictly by the syntax table, it should
-use `forward-word-strictly' instead.  */)
-  (Lisp_Object arg)
-{
-  Lisp_Object tmp;
-  ptrdiff_t orig_val, val;
-
-  if (NILP (arg))
-    XSETFASTINT (arg, 1);
-  else
-    CHECK_NUMBER (arg);
-
-  val = orig_val = scan_words (PT, XINT (arg));
-  if (! orig_val)
-    val = XINT (arg) > 0 ? ZV : BEGV;
-
-  /* Avoid jumping out of an input field.  */
-  tmp = Fconstrain_to_field (make_number (val), make_number (PT),
-			     Qnil, Qnil, Qnil);
-  val = XFASTINT (tmp);
-
-  SET_PT (val);
-  return val == orig_val ? Qt : Qnil;
-}
-
 DEFUN ("skip-chars-forward", Fskip_chars_forward, Sskip_chars_forward, 1, 2, 0,
        doc: /* Move point forward, stopping before a char not in STRING, or at pos LIM.
 STRING is like the inside of a `[...]' in a regular expression
@@ -3667,7 +3631,6 @@ In both cases, LIMIT bounds the search. */);
   defsubr (&Smodify_syntax_entry);
   defsubr (&Sinternal_describe_syntax_value);
 
-  defsubr (&Sforward_word);
 
   defsubr (&Sskip_chars_forward);
   defsubr (&Sskip_chars_backward);
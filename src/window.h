@@ -1024,6 +1024,13 @@ void run_window_size_change_functions (Lisp_Object);
 void set_window_buffer (Lisp_Object window, Lisp_Object buffer,
                         bool run_hooks_p, bool keep_margins_p);
 
+/* Keep windows showing BUFFER that start where NBYTES bytes were just
+   inserted at FROM_BYTE from shifting their text down.  */
+
+extern void adjust_window_starts_after_insert (struct buffer *buffer,
+                                               ptrdiff_t from_byte,
+                                               ptrdiff_t nbytes);
+
 /* This is the window where the echo area message was displayed.  It
    is always a minibuffer window, but it may not be the same window
    currently active as a minibuffer.  */
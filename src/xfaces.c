@@ -5792,7 +5792,8 @@ face_at_buffer_position (struct window *w, ptrdiff_t pos,
 	  Lisp_Object oend;
 	  ptrdiff_t oendpos;
 
-	  prop = Foverlay_get (overlay_vec[i], propname);
+	  prop = normalize_overlay_face (Foverlay_get (overlay_vec[i],
+						       propname));
 	  if (!NILP (prop))
 	    {
 	      /* Overlays always take priority over text properties,
@@ -5815,7 +5816,8 @@ face_at_buffer_position (struct window *w, ptrdiff_t pos,
 	  Lisp_Object oend;
 	  ptrdiff_t oendpos;
 
-	  prop = Foverlay_get (overlay_vec[i], propname);
+	  prop = normalize_overlay_face (Foverlay_get (overlay_vec[i],
+						       propname));
 	  if (!NILP (prop))
 	    merge_face_ref (f, prop, attrs, true, 0);
 
@@ -63,6 +63,1391 @@
     (should (equal (delq nil (delete-dups the-buffers))
                    the-buffers))))
 
+(ert-deftest test-revert-needed-p-fresh ()
+  (let ((file (make-temp-file "revert-needed-p")))
+    (unwind-protect
+        (with-current-buffer (find-file-noselect file)
+          (unwind-protect
+              (should-not (revert-needed-p))
+            (kill-buffer)))
+      (delete-file file))))
+
+(ert-deftest test-revert-needed-p-stale ()
+  (let ((file (make-temp-file "revert-needed-p")))
+    (unwind-protect
+        (with-current-buffer (find-file-noselect file)
+          (unwind-protect
+              (progn
+                (set-file-times file (time-add (current-time) 60))
+                (should (revert-needed-p))
+                (should (revert-needed-p (current-buffer))))
+            (kill-buffer)))
+      (delete-file file))))
+
+(ert-deftest test-revert-needed-p-modified ()
+  (let ((file (make-temp-file "revert-needed-p")))
+    (unwind-protect
+        (with-current-buffer (find-file-noselect file)
+          (unwind-protect
+              (progn
+                (insert "modified")
+                (set-file-times file (time-add (current-time) 60))
+                (should-not (revert-needed-p)))
+            (set-buffer-modified-p nil)
+            (kill-buffer)))
+      (delete-file file))))
+
+(ert-deftest test-revert-needed-p-non-file-buffer ()
+  (with-temp-buffer
+    (should-not (revert-needed-p))))
+
+(ert-deftest test-overlay-at-point ()
+  (with-temp-buffer
+    (insert "some overlaid text")
+    (let ((low (make-overlay 1 15))
+          (high (make-overlay 3 10))
+          (other (make-overlay 4 8)))
+      (overlay-put low 'face 'bold)
+      (overlay-put low 'priority 1)
+      (overlay-put high 'face 'italic)
+      (overlay-put high 'priority 5)
+      (overlay-put other 'help-echo "no face")
+      (overlay-put other 'priority 10)
+      (goto-char 5)
+      (should (eq (overlay-at-point 'face) high))
+      (should (eq (overlay-at-point) other))
+      (goto-char 12)
+      (should (eq (overlay-at-point 'face) low))
+      (goto-char (point-max))
+      (should-not (overlay-at-point 'face)))))
+
+(ert-deftest test-overlay-at-point-ties ()
+  (with-temp-buffer
+    (insert "some overlaid text")
+    (let ((outer (make-overlay 1 15))
+          (inner (make-overlay 3 10)))
+      (overlay-put outer 'face 'bold)
+      (overlay-put inner 'face 'italic)
+      (goto-char 5)
+      (should (eq (overlay-at-point 'face) inner)))))
+
+(ert-deftest test-first-change-hook-runs-once-per-save ()
+  (with-temp-buffer
+    (let ((count 0))
+      (add-hook 'first-change-hook (lambda () (setq count (1+ count))) nil t)
+      (insert "a")
+      (insert "b")
+      (should (= count 1))
+      (set-buffer-modified-p nil)
+      (insert "c")
+      (should (= count 2))
+      (set-buffer-modified-p nil)
+      (set-buffer-modified-p t)
+      (should (= count 3))
+      (insert "d")
+      (should (= count 3)))))
+
+(ert-deftest test-per-buffer-char-table-defaults ()
+  (with-temp-buffer
+    (should (eq (syntax-table) (standard-syntax-table)))
+    (should (eq (category-table) (standard-category-table)))
+    (should (eq (current-case-table) (standard-case-table)))
+    (should-not buffer-display-table)
+    (let ((table (make-syntax-table)))
+      (set-syntax-table table)
+      (should (eq (syntax-table) table)))))
+
+(ert-deftest test-buffer-by-index ()
+  (should (eq (buffer-by-index 0) (car (buffer-list))))
+  (should (eq (buffer-by-index 1) (cadr (buffer-list))))
+  (should-not (buffer-by-index (length (buffer-list))))
+  (should-not (buffer-by-index -1)))
+
+(ert-deftest test-buffer-last-change-region ()
+  (with-temp-buffer
+    (should-not (buffer-last-change-region))
+    (insert "hello world")
+    (should (equal (buffer-last-change-region) '(1 . 12)))
+    (goto-char 7)
+    (insert "big ")
+    (should (equal (buffer-last-change-region) '(7 . 11)))
+    (delete-region 1 7)
+    (should (equal (buffer-last-change-region) '(1 . 1)))
+    (goto-char (point-min))
+    (let ((inhibit-modification-hooks t))
+      (insert "!"))
+    (should (equal (buffer-last-change-region (current-buffer)) '(1 . 2)))))
+
+(ert-deftest test-overlay-put-priority-normalization ()
+  (with-temp-buffer
+    (insert "abc")
+    (let ((ov (make-overlay 1 3)))
+      (overlay-put ov 'priority '(5 . 2))
+      (should (equal (overlay-get ov 'priority) '(5 . 2)))
+      (overlay-put ov 'priority '(nil . 3))
+      (should (equal (overlay-get ov 'priority) '(0 . 3)))
+      (overlay-put ov 'priority 7)
+      (should (eq (overlay-get ov 'priority) 7))
+      (should-error (overlay-put ov 'priority "high")
+                    :type 'wrong-type-argument)
+      (should-error (overlay-put ov 'priority '(high . 1))
+                    :type 'wrong-type-argument)
+      (should (eq (overlay-get ov 'priority) 7)))))
+
+(ert-deftest test-overlay-put-face-round-trip ()
+  ;; A list of faces is only normalized for display, so `overlay-get'
+  ;; returns what was put.
+  (with-temp-buffer
+    (insert "abc")
+    (let ((ov (make-overlay 1 3)))
+      (overlay-put ov 'face '(bold nil italic))
+      (should (equal (overlay-get ov 'face) '(bold nil italic)))
+      (overlay-put ov 'face '(bold))
+      (should (equal (overlay-get ov 'face) '(bold)))
+      (overlay-put ov 'face '(:weight bold))
+      (should (equal (overlay-get ov 'face) '(:weight bold)))
+      (overlay-put ov 'face '(foreground-color . "red"))
+      (should (equal (overlay-get ov 'face) '(foreground-color . "red"))))))
+
+(ert-deftest test-buffer-local-value-indirect-buffer ()
+  (let* ((base (generate-new-buffer "base"))
+         indirect)
+    (unwind-protect
+        (progn
+          (with-current-buffer base
+            (setq-local test-buffer-local-var 'base-value)
+            (insert "text"))
+          ;; A clone starts out with the base buffer's local bindings...
+          (setq indirect (make-indirect-buffer base "indirect" t))
+          (should (eq (buffer-local-value 'test-buffer-local-var indirect)
+                      'base-value))
+          ;; ...but keeps its own point and bindings afterwards.
+          (with-current-buffer indirect
+            (setq-local test-buffer-local-var 'indirect-value)
+            (goto-char (point-min)))
+          (should (eq (buffer-local-value 'test-buffer-local-var base)
+                      'base-value))
+          (should (= (with-current-buffer base (point)) 5))
+          ;; The undo list belongs to the shared text.
+          (with-current-buffer indirect
+            (insert "more"))
+          (should (equal (buffer-local-value 'buffer-undo-list base)
+                         (buffer-local-value 'buffer-undo-list indirect)))
+          (should (equal (buffer-local-value 'buffer-undo-list base)
+                         (with-current-buffer base buffer-undo-list))))
+      (when indirect (kill-buffer indirect))
+      (kill-buffer base))))
+
+(ert-deftest test-kill-buffer-query-functions-abort ()
+  (let* ((buf (generate-new-buffer "test-kill-buffer-query"))
+         (kill-buffer-query-functions (list (lambda () nil))))
+    (unwind-protect
+        (progn
+          (should-not (kill-buffer buf))
+          (should (buffer-live-p buf)))
+      (let ((kill-buffer-query-functions nil))
+        (kill-buffer buf)))))
+
+(ert-deftest test-kill-buffer-hook ()
+  (let* ((buf (generate-new-buffer "test-kill-buffer-hook"))
+         (seen nil)
+         (kill-buffer-hook
+          (list (lambda ()
+                  (setq seen (current-buffer))
+                  ;; A misbehaving hook may switch buffers.
+                  (set-buffer (get-buffer-create "*scratch*"))))))
+    (with-temp-buffer
+      (let ((outer (current-buffer)))
+        (should (kill-buffer buf))
+        (should (eq seen buf))
+        (should-not (buffer-live-p buf))
+        (should (eq (current-buffer) outer))))))
+
+(ert-deftest test-inhibit-modification-hooks ()
+  (with-temp-buffer
+    (let* ((changes 0)
+           (after-change-functions
+            (list (lambda (&rest _) (setq changes (1+ changes))))))
+      (insert "abc")
+      (should (= changes 1))
+      (let ((inhibit-modification-hooks t))
+        (insert "def")
+        (delete-region 1 3))
+      (should (= changes 1))
+      (should (equal (buffer-string) "cdef"))
+      ;; The binding is gone again once the scope exits.
+      (insert "g")
+      (should (= changes 2)))))
+
+(ert-deftest test-narrow-indirect-buffer ()
+  (let* ((base (generate-new-buffer "test-narrow-base"))
+         (indirect (make-indirect-buffer base "test-narrow-indirect")))
+    (unwind-protect
+        (progn
+          (with-current-buffer base
+            (insert "0123456789"))
+          (with-current-buffer indirect
+            (narrow-to-region 3 6)
+            (should (= (point-min) 3))
+            (should (= (point-max) 6)))
+          (with-current-buffer base
+            (should (= (point-min) 1))
+            (should (= (point-max) 11))
+            ;; Narrowing the base leaves the indirect buffer alone too.
+            (narrow-to-region 2 4))
+          (with-current-buffer indirect
+            (should (= (point-min) 3))
+            (should (= (point-max) 6))))
+      (kill-buffer indirect)
+      (kill-buffer base))))
+
+(ert-deftest test-buffer-superseded-p-fresh ()
+  (let ((file (make-temp-file "buffer-superseded-p")))
+    (unwind-protect
+        (with-current-buffer (find-file-noselect file)
+          (unwind-protect
+              (progn
+                (insert "modified")
+                (should-not (buffer-superseded-p)))
+            (set-buffer-modified-p nil)
+            (kill-buffer)))
+      (delete-file file))))
+
+(ert-deftest test-buffer-superseded-p-modified-on-disk ()
+  (let ((file (make-temp-file "buffer-superseded-p")))
+    (unwind-protect
+        (with-current-buffer (find-file-noselect file)
+          (unwind-protect
+              (progn
+                (insert "modified")
+                (set-file-times file (time-add (current-time) 60))
+                (should (buffer-superseded-p))
+                (should (buffer-superseded-p (current-buffer))))
+            (set-buffer-modified-p nil)
+            (kill-buffer)))
+      (delete-file file))))
+
+(ert-deftest test-replace-buffer-with-string ()
+  (with-temp-buffer
+    (insert "old contents")
+    (narrow-to-region 2 4)
+    (let* ((tick (buffer-modified-tick))
+           (before nil)
+           (after nil)
+           (before-change-functions
+            (list (lambda (beg end) (push (list beg end) before))))
+           (after-change-functions
+            (list (lambda (beg end len) (push (list beg end len) after)))))
+      (replace-buffer-with-string "new")
+      (should (equal (buffer-string) "new"))
+      (should (= (buffer-modified-tick) (1+ tick)))
+      (should (equal before '((2 4))))
+      (should (equal after '((2 5 2))))
+      (widen)
+      (should (equal (buffer-string) "onew contents"))
+      (erase-buffer)
+      (insert "old contents")
+      (setq before nil after nil tick (buffer-modified-tick))
+      (replace-buffer-with-string "new")
+      (should (equal (buffer-string) "new"))
+      (should (= (buffer-modified-tick) (1+ tick)))
+      (should (equal before '((1 13))))
+      (should (equal after '((1 4 12))))))
+  (with-temp-buffer
+    (insert "read only")
+    (setq buffer-read-only t)
+    (should-error (replace-buffer-with-string "new") :type 'buffer-read-only)
+    (should (equal (buffer-string) "read only"))))
+
+(ert-deftest test-get-buffer-create-with-content-fresh ()
+  (let ((name (generate-new-buffer-name "test-with-content")))
+    (with-temp-buffer
+      (let* ((outer (current-buffer))
+             (buf (get-buffer-create-with-content name "initial")))
+        (unwind-protect
+            (progn
+              (should (eq buf (get-buffer name)))
+              (should (eq (current-buffer) outer))
+              (with-current-buffer buf
+                (should (equal (buffer-string) "initial"))))
+          (kill-buffer buf))))))
+
+(ert-deftest test-get-buffer-create-with-content-read-only ()
+  (let ((name (generate-new-buffer-name "test-with-content"))
+        (default (default-value 'buffer-read-only))
+        buf)
+    (unwind-protect
+        (progn
+          (setq-default buffer-read-only t)
+          (setq buf (get-buffer-create-with-content name "initial"))
+          (setq-default buffer-read-only default)
+          (with-current-buffer buf
+            (should buffer-read-only)
+            (should (equal (buffer-string) "initial"))
+            (should-error (insert "more") :type 'buffer-read-only)))
+      (setq-default buffer-read-only default)
+      (when buf
+        (kill-buffer buf)))))
+
+(ert-deftest test-get-buffer-create-with-content-reuse ()
+  (let ((buf (generate-new-buffer "test-with-content")))
+    (unwind-protect
+        (progn
+          (with-current-buffer buf
+            (insert "existing"))
+          (should (eq (get-buffer-create-with-content (buffer-name buf) "ignored")
+                      buf))
+          (should (eq (get-buffer-create-with-content buf "ignored") buf))
+          (with-current-buffer buf
+            (should (equal (buffer-string) "existing"))))
+      (kill-buffer buf))))
+
+(defun buffers-tests--overlay-ids (overlays)
+  (sort (mapcar (lambda (ov) (overlay-get ov 'id)) overlays) #'<))
+
+(defun buffers-tests--make-many-overlays (count)
+  "Insert text and COUNT overlays of assorted sizes, some of them empty."
+  (insert (make-string 2000 ?x))
+  (dotimes (i count)
+    (let* ((beg (1+ (% (* i 7919) 2000)))
+           (len (% (* i 31) 40))
+           (ov (make-overlay beg (min (point-max) (+ beg len)))))
+      (overlay-put ov 'id i))))
+
+(ert-deftest test-overlay-index-matches-linear-search ()
+  (with-temp-buffer
+    (buffers-tests--make-many-overlays 3000)
+    (dolist (pos '(1 2 17 500 999 1000 1001 1960 2000 2001))
+      (let ((linear (let ((overlay-index-threshold most-positive-fixnum))
+                      (overlays-at pos)))
+            (indexed (let ((overlay-index-threshold 0))
+                       (overlays-at pos))))
+        (should (equal (buffers-tests--overlay-ids linear)
+                       (buffers-tests--overlay-ids indexed)))))
+    (dolist (range '((1 1) (1 50) (300 300) (700 760) (1990 2001) (2001 2001)))
+      (let ((linear (let ((overlay-index-threshold most-positive-fixnum))
+                      (apply #'overlays-in range)))
+            (indexed (let ((overlay-index-threshold 0))
+                       (apply #'overlays-in range))))
+        (should (equal (buffers-tests--overlay-ids linear)
+                       (buffers-tests--overlay-ids indexed)))))))
+
+(ert-deftest test-overlay-index-follows-changes ()
+  (with-temp-buffer
+    (let ((overlay-index-threshold 0))
+      (insert "0123456789")
+      (let ((ov (make-overlay 3 6)))
+        (should (equal (overlays-at 4) (list ov)))
+        ;; Editing the text moves the overlay.
+        (goto-char 1)
+        (insert "ab")
+        (should-not (overlays-at 4))
+        (should (equal (overlays-at 6) (list ov)))
+        ;; So does moving it explicitly.
+        (move-overlay ov 1 2)
+        (should-not (overlays-at 6))
+        (should (equal (overlays-in 1 2) (list ov)))
+        ;; New and deleted overlays are seen too.
+        (let ((new (make-overlay 8 9)))
+          (should (equal (overlays-at 8) (list new)))
+          (delete-overlay new)
+          (should-not (overlays-at 8)))))))
+
+(ert-deftest test-overlay-index-speed ()
+  (with-temp-buffer
+    (buffers-tests--make-many-overlays 20000)
+    (let ((overlay-index-threshold 0)
+          (start (float-time)))
+      (dotimes (i 2000)
+        (overlays-at (1+ (% (* i 13) 2000))))
+      ;; A generous bound; walking the lists 2000 times is far slower.
+      (should (< (- (float-time) start) 10)))))
+
+(ert-deftest test-stale-observer ()
+  (let ((file (make-temp-file "stale-observer"))
+        (internal--stale-observers nil)
+        (calls nil))
+    (unwind-protect
+        (let ((buf (find-file-noselect file)))
+          (unwind-protect
+              (progn
+                (register-stale-observer buf (lambda (b) (push b calls)))
+                (run-stale-checks)
+                (should-not calls)
+                (set-file-times file (time-add (current-time) 60))
+                (run-stale-checks)
+                (should (equal calls (list buf)))
+                ;; Still stale, but the observer was already told.
+                (run-stale-checks)
+                (should (equal calls (list buf))))
+            (kill-buffer buf))
+          ;; Observers of killed buffers are dropped.
+          (run-stale-checks)
+          (should-not internal--stale-observers))
+      (delete-file file))))
+
+(ert-deftest test-inhibit-read-only ()
+  (with-temp-buffer
+    (insert "abc")
+    (setq buffer-read-only t)
+    (should-error (insert "x") :type 'buffer-read-only)
+    (let ((inhibit-read-only t))
+      (insert "def")
+      (delete-region 1 2))
+    (should (equal (buffer-string) "bcdef"))
+    ;; The binding is gone again once the scope exits.
+    (should-error (insert "g") :type 'buffer-read-only)))
+
+(defvar buffers-tests--auto-local nil)
+(make-variable-buffer-local 'buffers-tests--auto-local)
+(defvar buffers-tests--global nil)
+
+(ert-deftest test-buffer-variable-documentation ()
+  (let ((doc (buffer-variable-documentation 'buffers-tests--auto-local)))
+    (should (plist-get doc :automatically-local))
+    (should-not (plist-get doc :permanent-local))
+    (should-not (plist-get doc :locus)))
+  (with-temp-buffer
+    (setq buffers-tests--auto-local t)
+    (should (eq (plist-get (buffer-variable-documentation
+                            'buffers-tests--auto-local)
+                           :locus)
+                (current-buffer))))
+  ;; Built-in per-buffer variables are automatically local, too.
+  (should (plist-get (buffer-variable-documentation 'fill-column)
+                     :automatically-local))
+  (should-not (plist-get (buffer-variable-documentation 'buffers-tests--global)
+                         :automatically-local))
+  (should (plist-get (buffer-variable-documentation 'kill-buffer-hook)
+                     :permanent-local)))
+
+(ert-deftest test-buffer-text-checksum ()
+  (with-temp-buffer
+    (insert "The quick brown fox jumps over the lazy dog.\n")
+    (let ((initial (buffer-text-checksum)))
+      (should (= initial (buffer-text-checksum nil t)))
+      (goto-char 5)
+      (insert "very ")
+      (should-not (= initial (buffer-text-checksum)))
+      (should (= (buffer-text-checksum) (buffer-text-checksum nil t)))
+      (delete-region 5 10)
+      (should (= initial (buffer-text-checksum)))
+      (goto-char (point-max))
+      (insert "ünïcödé")
+      (upcase-region 1 10)
+      (subst-char-in-region 1 (point-max) ?o ?0)
+      (let ((inhibit-modification-hooks t))
+        (delete-region 2 4)
+        (insert "xyz"))
+      (should (= (buffer-text-checksum) (buffer-text-checksum nil t)))
+      (erase-buffer)
+      (should (= (buffer-text-checksum) (buffer-text-checksum nil t))))))
+
+(ert-deftest test-buffer-text-checksum-indirect ()
+  (let* ((base (generate-new-buffer "base"))
+         (indirect (make-indirect-buffer base "indirect")))
+    (unwind-protect
+        (progn
+          (with-current-buffer base
+            (insert "shared text"))
+          (should (= (buffer-text-checksum base)
+                     (buffer-text-checksum indirect)))
+          (with-current-buffer indirect
+            (goto-char 7)
+            (insert "d"))
+          (should (= (buffer-text-checksum base)
+                     (buffer-text-checksum base t))))
+      (kill-buffer indirect)
+      (kill-buffer base))))
+
+(ert-deftest test-buffer-text-checksum-narrower-change ()
+  ;; These commands announce the whole region as about to change but
+  ;; report only the part that actually changed afterwards.
+  (with-temp-buffer
+    (insert "foo bar baz foo bar baz")
+    (buffer-text-checksum)
+    (subst-char-in-region 1 (point-max) ?b ?B)
+    (should (= (buffer-text-checksum) (buffer-text-checksum nil t)))
+    (capitalize-region 1 (point-max))
+    (should (= (buffer-text-checksum) (buffer-text-checksum nil t)))))
+
+(ert-deftest test-unibyte-buffer-stepping ()
+  (with-temp-buffer
+    (set-buffer-multibyte nil)
+    (with-syntax-table (standard-syntax-table)
+      ;; \303 would start a two-byte sequence in a multibyte buffer.
+      (insert "\303-x \351y")
+      (goto-char (point-min))
+      (forward-word)
+      (should (= (point) 2))
+      (forward-word)
+      (should (= (point) 4))
+      (goto-char (point-max))
+      (forward-word -1)
+      (should (= (point) 5))
+      (should (= (internal-count-words (point-min) (point-max)) 3))
+      (should (equal (point-line-and-column) '(1 . 4))))))
+
+(ert-deftest test-unibyte-buffer-stepping-speed ()
+  (with-temp-buffer
+    (set-buffer-multibyte nil)
+    (dotimes (_ 20000)
+      (insert "abc def\351 ghi\n"))
+    (let ((start (float-time)))
+      (dotimes (_ 5)
+        (should (= (internal-count-words (point-min) (point-max)) 60000))
+        (goto-char (point-min))
+        (forward-word 60000))
+      ;; A generous bound; this only guards against pathological slowness.
+      (should (< (- (float-time) start) 10)))))
+
+(ert-deftest test-buffer-matches-file-p ()
+  (let ((file (make-temp-file "buffer-matches-file-p" nil nil "some text\n")))
+    (unwind-protect
+        (with-current-buffer (find-file-noselect file)
+          (unwind-protect
+              (progn
+                (should (buffer-matches-file-p))
+                (goto-char (point-max))
+                (insert "more")
+                (should-not (buffer-matches-file-p (current-buffer)))
+                (delete-region (- (point-max) 4) (point-max))
+                (should (buffer-modified-p))
+                (should (buffer-matches-file-p))
+                ;; Narrowing doesn't hide the rest of the text.
+                (narrow-to-region 1 5)
+                (should (buffer-matches-file-p))
+                (should (= (point-max) 5)))
+            (set-buffer-modified-p nil)
+            (kill-buffer)))
+      (delete-file file))))
+
+(ert-deftest test-buffer-matches-file-p-non-file-buffer ()
+  (with-temp-buffer
+    (insert "text")
+    (should-not (buffer-matches-file-p))))
+
+(ert-deftest test-overlays-modified-since ()
+  (with-temp-buffer
+    (insert "some text")
+    (let ((tick (buffer-overlay-modified-tick))
+          ov)
+      (should-not (overlays-modified-since tick))
+      (insert " and more")
+      (delete-region 1 3)
+      (should-not (overlays-modified-since tick))
+      (setq ov (make-overlay 1 4))
+      (should (overlays-modified-since tick))
+      (setq tick (buffer-overlay-modified-tick))
+      (overlay-put ov 'face 'bold)
+      (should (overlays-modified-since tick))
+      (setq tick (buffer-overlay-modified-tick))
+      (move-overlay ov 2 5)
+      (should (overlays-modified-since tick))
+      (setq tick (buffer-overlay-modified-tick))
+      (delete-overlay ov)
+      (should (overlays-modified-since tick))
+      (should (= (buffer-overlay-modified-tick)
+                 (buffer-overlay-modified-tick (current-buffer)))))))
+
+(ert-deftest test-line-at-byte ()
+  (let ((buf (generate-new-buffer "line-at-byte")))
+    (unwind-protect
+        (with-current-buffer buf
+          (insert "one\ntwö\nthree")
+          ;; Move the gap into the middle of the text.
+          (goto-char 6)
+          (insert "x")
+          (delete-char -1)
+          (should (= 1 (line-at-byte 1)))
+          (should (= 1 (line-at-byte 4)))
+          (should (= 2 (line-at-byte 5)))
+          (should (= 2 (line-at-byte 9)))
+          (should (= 3 (line-at-byte 10)))
+          (should (= 3 (line-at-byte (position-bytes (point-max)))))
+          (narrow-to-region 6 8)
+          (should (= 3 (line-at-byte 12)))
+          (should-error (line-at-byte 100) :type 'args-out-of-range)
+          (with-temp-buffer
+            (should (= 2 (line-at-byte 5 buf)))))
+      (kill-buffer buf))))
+
+(ert-deftest test-get-buffer-create-special-buffer ()
+  (let ((special (get-buffer-create " *special test*"))
+        (normal (get-buffer-create "*normal test*")))
+    (unwind-protect
+        (progn
+          (should (eq t (buffer-local-value 'buffer-undo-list special)))
+          (should-not (buffer-local-value 'buffer-auto-save-file-name special))
+          (should-not (buffer-local-value 'buffer-undo-list normal))
+          (with-current-buffer special
+            (insert "text")
+            (should (eq t buffer-undo-list))))
+      (kill-buffer special)
+      (kill-buffer normal))))
+
+(ert-deftest test-get-buffer-create-runs-buffer-list-update-hook ()
+  (let* ((runs 0)
+         (buffer-list-update-hook (list (lambda () (setq runs (1+ runs)))))
+         (buffer (get-buffer-create "*hook test*")))
+    (unwind-protect
+        (progn
+          (should (= runs 1))
+          ;; Getting an existing buffer creates nothing.
+          (get-buffer-create "*hook test*")
+          (should (= runs 1)))
+      (let ((buffer-list-update-hook nil))
+        (kill-buffer buffer)))))
+
+(ert-deftest test-buffer-displayed-p ()
+  (let ((shown (generate-new-buffer "shown"))
+        (hidden (generate-new-buffer "hidden")))
+    (unwind-protect
+        (save-window-excursion
+          (set-window-buffer (selected-window) shown)
+          (should (buffer-displayed-p shown))
+          (should-not (buffer-displayed-p hidden))
+          (with-current-buffer hidden
+            (should-not (buffer-displayed-p))))
+      (kill-buffer shown)
+      (kill-buffer hidden))))
+
+(ert-deftest test-buffer-narrowing ()
+  (with-temp-buffer
+    (insert "some narrowed text")
+    (should-not (buffer-narrowing))
+    (narrow-to-region 6 14)
+    (should (equal (buffer-narrowing) '(6 . 14)))
+    (let ((buffer (current-buffer)))
+      (with-temp-buffer
+        (should-not (buffer-narrowing))
+        (should (equal (buffer-narrowing buffer) '(6 . 14)))))
+    (narrow-to-region 1 14)
+    (should (equal (buffer-narrowing) '(1 . 14)))
+    (widen)
+    (should-not (buffer-narrowing))))
+
+(ert-deftest test-buffer-narrowing-indirect ()
+  (with-temp-buffer
+    (insert "some narrowed text")
+    (let ((indirect (make-indirect-buffer (current-buffer) " *narrowing*")))
+      (unwind-protect
+          (progn
+            (with-current-buffer indirect
+              (narrow-to-region 3 8))
+            (goto-char 1)
+            (insert "new ")
+            (should (equal (buffer-narrowing indirect) '(7 . 12)))
+            (should-not (buffer-narrowing)))
+        (kill-buffer indirect)))))
+
+(ert-deftest test-make-indirect-buffer ()
+  (let* ((base (generate-new-buffer "base"))
+         (indirect (make-indirect-buffer base "indirect"))
+         (nested (make-indirect-buffer indirect "nested")))
+    (unwind-protect
+        (progn
+          (should (eq (buffer-base-buffer indirect) base))
+          ;; There is no double indirection.
+          (should (eq (buffer-base-buffer nested) base))
+          (with-current-buffer base
+            (insert "shared"))
+          (should (equal (with-current-buffer indirect (buffer-string)) "shared"))
+          (should (equal (with-current-buffer nested (buffer-string)) "shared"))
+          (let ((other (make-indirect-buffer nested "other")))
+            (should (eq (buffer-base-buffer other) base))
+            (kill-buffer other))
+          (should-error (make-indirect-buffer base "indirect"))
+          (should-error (make-indirect-buffer "no such buffer" "other"))
+          (should-error (make-indirect-buffer base "")))
+      (kill-buffer nested)
+      (kill-buffer indirect)
+      (kill-buffer base))))
+
+(ert-deftest test-make-indirect-buffer-clone ()
+  (let ((base (generate-new-buffer "base")))
+    (with-current-buffer base
+      (emacs-lisp-mode)
+      (setq-local fill-column 42)
+      (insert "text")
+      (set-marker (mark-marker) 2))
+    (let ((plain (make-indirect-buffer base "plain"))
+          (clone (make-indirect-buffer base "clone" t)))
+      (unwind-protect
+          (progn
+            (with-current-buffer plain
+              (should (eq major-mode 'fundamental-mode))
+              (should-not (local-variable-p 'fill-column)))
+            (with-current-buffer clone
+              (should (eq major-mode 'emacs-lisp-mode))
+              (should (= fill-column 42))
+              (should (eq (marker-buffer (mark-marker)) clone))
+              (should (= (marker-position (mark-marker)) 2))
+              (should-not buffer-file-name)))
+        (kill-buffer clone)
+        (kill-buffer plain)
+        (kill-buffer base)))))
+
+(ert-deftest test-rename-buffer ()
+  (let ((buffer (generate-new-buffer "rename-me"))
+        (other (generate-new-buffer "taken")))
+    (unwind-protect
+        (with-current-buffer buffer
+          (should (equal (rename-buffer "renamed") "renamed"))
+          (should (equal (buffer-name) "renamed"))
+          (should (eq (current-buffer) buffer))
+          (should (eq (get-buffer "renamed") buffer))
+          (should-not (get-buffer "rename-me"))
+          (should (eq (cdr (assoc "renamed" (mapcar (lambda (b) (cons (buffer-name b) b))
+                                                    (buffer-list))))
+                      buffer))
+          ;; Renaming to its own name is a no-op.
+          (should (equal (rename-buffer "renamed") "renamed"))
+          (should-error (rename-buffer "taken"))
+          (should-error (rename-buffer ""))
+          (should (equal (rename-buffer "taken" t) "taken<2>"))
+          (should (eq (get-buffer "taken<2>") buffer))
+          (should (eq (get-buffer "taken") other)))
+      (kill-buffer buffer)
+      (kill-buffer other))))
+
+(ert-deftest test-rename-buffer-runs-buffer-list-update-hook ()
+  (with-temp-buffer
+    (let* ((runs 0)
+           (buffer-list-update-hook (list (lambda () (setq runs (1+ runs))))))
+      (rename-buffer "hook-rename" t)
+      (should (= runs 1)))))
+
+(ert-deftest test-generate-new-buffer-name ()
+  (let ((name (make-temp-name "gnbn-test")))
+    (should (equal (generate-new-buffer-name name) name))
+    (let ((first (get-buffer-create name))
+          (second (get-buffer-create (concat name "<2>"))))
+      (unwind-protect
+          (progn
+            (should (equal (generate-new-buffer-name name)
+                           (concat name "<3>")))
+            (should (equal (generate-new-buffer-name name name) name))
+            (should (equal (generate-new-buffer-name name (concat name "<2>"))
+                           (concat name "<2>")))
+            (should (equal (generate-new-buffer-name
+                            (propertize name 'face 'bold))
+                           (concat name "<3>"))))
+        (kill-buffer first)
+        (kill-buffer second)))))
+
+(ert-deftest test-generate-new-buffer-name-hidden ()
+  (let* ((name (make-temp-name " gnbn-hidden"))
+         (buffer (get-buffer-create name)))
+    (unwind-protect
+        (let ((new (generate-new-buffer-name name)))
+          (should-not (equal new name))
+          (should (string-prefix-p name new))
+          (should-not (get-buffer new)))
+      (kill-buffer buffer))))
+
+(ert-deftest test-buffer-text-encoding-hint ()
+  (with-temp-buffer
+    (should (eq (buffer-text-encoding-hint) 'ascii))
+    (insert "plain ascii text\n")
+    (should (eq (buffer-text-encoding-hint) 'ascii))
+    (insert "naïve café")
+    (goto-char 20)
+    (insert "→")
+    (should (eq (buffer-text-encoding-hint (current-buffer)) 'utf-8))
+    (insert (unibyte-string #xff))
+    (should (eq (buffer-text-encoding-hint) 'raw)))
+  (with-temp-buffer
+    (set-buffer-multibyte nil)
+    (insert "abc")
+    (should (eq (buffer-text-encoding-hint) 'ascii))
+    (insert "\303z\251")
+    ;; Leave the gap in the middle of the two-byte sequence.
+    (delete-region 5 6)
+    (should (eq (buffer-text-encoding-hint) 'utf-8))
+    (goto-char (point-max))
+    (insert "\351")
+    (should (eq (buffer-text-encoding-hint) 'raw))))
+
+(ert-deftest test-get-buffer-create ()
+  (let* ((name (make-temp-name "gbc-test"))
+         (buffer (get-buffer-create (propertize name 'face 'bold))))
+    (unwind-protect
+        (progn
+          (should (buffer-live-p buffer))
+          (should (equal (buffer-name buffer) name))
+          (should-not (text-properties-at 0 (buffer-name buffer)))
+          (should (eq (get-buffer-create name) buffer))
+          (should (eq (get-buffer-create buffer) buffer))
+          (should (memq buffer (buffer-list)))
+          (with-current-buffer buffer
+            (should (equal (buffer-string) ""))
+            (should (= (point) 1))
+            (should-not (buffer-modified-p))
+            (should (stringp default-directory))
+            (should-not (eq buffer-undo-list t))))
+      (kill-buffer buffer))
+    (should (eq (get-buffer-create buffer) buffer)))
+  (should-error (get-buffer-create "")))
+
+(ert-deftest test-get-buffer-create-hidden ()
+  (let ((buffer (get-buffer-create (make-temp-name " gbc-hidden"))))
+    (unwind-protect
+        (with-current-buffer buffer
+          (should (eq buffer-undo-list t)))
+      (kill-buffer buffer))))
+
+(ert-deftest test-kill-buffer ()
+  (let* ((buf (generate-new-buffer "test-kill-buffer"))
+         (name (buffer-name buf))
+         marker overlay)
+    (with-current-buffer buf
+      (insert "some text")
+      (setq marker (copy-marker 3))
+      (setq overlay (make-overlay 2 5)))
+    (should (kill-buffer name))
+    (should-not (buffer-live-p buf))
+    (should-not (buffer-name buf))
+    (should-not (get-buffer name))
+    (should-not (memq buf (buffer-list)))
+    (should-not (marker-buffer marker))
+    (should-not (overlay-buffer overlay))
+    ;; Killing a dead buffer does nothing.
+    (should-not (kill-buffer buf))))
+
+(ert-deftest test-kill-buffer-current ()
+  (let ((buf (generate-new-buffer "test-kill-buffer-current")))
+    (with-current-buffer buf
+      (should (kill-buffer))
+      (should-not (eq (current-buffer) buf))
+      (should (buffer-live-p (current-buffer))))))
+
+(ert-deftest test-kill-buffer-kills-indirect-buffers ()
+  (let* ((base (generate-new-buffer "test-kill-buffer-base"))
+         (indirect (make-indirect-buffer base "test-kill-buffer-indirect"))
+         (base-marker (with-current-buffer base
+                        (insert "shared text")
+                        (copy-marker 2))))
+    (should (kill-buffer indirect))
+    (should (buffer-live-p base))
+    ;; Killing the indirect buffer leaves the base's markers alone.
+    (should (eq (marker-buffer base-marker) base))
+    (should (equal (with-current-buffer base (buffer-string)) "shared text"))
+    (setq indirect (make-indirect-buffer base "test-kill-buffer-indirect"))
+    (should (kill-buffer base))
+    (should-not (buffer-live-p indirect))
+    (should-not (marker-buffer base-marker))))
+
+(ert-deftest test-set-buffer-modified-p ()
+  (with-temp-buffer
+    ;; A buffer that has never been modified can be marked modified.
+    (should-not (buffer-modified-p))
+    (should-not (set-buffer-modified-p t))
+    (should (buffer-modified-p))
+    (set-buffer-modified-p nil)
+    (should-not (buffer-modified-p))
+    (insert "text")
+    (should (buffer-modified-p))
+    (set-buffer-modified-p t)
+    (should (buffer-modified-p))
+    (set-buffer-modified-p nil)
+    (should-not (buffer-modified-p))
+    (should (equal (buffer-string) "text"))))
+
+(ert-deftest test-buffer-redisplay-start-hint ()
+  (with-temp-buffer
+    (should-not (buffer-redisplay-start-hint))
+    (insert "0123456789")
+    (should (= (buffer-redisplay-start-hint) 1))
+    ;; The hint is reset once it has been consumed.
+    (should-not (buffer-redisplay-start-hint))
+    (goto-char 8)
+    (insert "x")
+    (goto-char 4)
+    (delete-char 1)
+    (goto-char 10)
+    (insert "y")
+    (should (= (buffer-redisplay-start-hint (current-buffer)) 4))
+    (should-not (buffer-redisplay-start-hint))))
+
+(ert-deftest test-buffer-redisplay-start-hint-whole-text ()
+  (with-temp-buffer
+    (insert "text")
+    (buffer-redisplay-start-hint)
+    (set-buffer-multibyte nil)
+    (should (= (buffer-redisplay-start-hint) 1))
+    (let ((other (current-buffer)))
+      (with-temp-buffer
+        (buffer-swap-text other)
+        (should (= (buffer-redisplay-start-hint) 1))
+        (should (= (buffer-redisplay-start-hint other) 1))))))
+
+(ert-deftest test-restore-buffer-modified-p ()
+  (with-temp-buffer
+    (should (restore-buffer-modified-p t))
+    (should (buffer-modified-p))
+    (should-not (restore-buffer-modified-p nil))
+    (should-not (buffer-modified-p))
+    (let ((tick (buffer-modified-tick)))
+      (insert "text")
+      (should (buffer-modified-p))
+      (restore-buffer-modified-p (buffer-modified-tick))
+      (should-not (buffer-modified-p))
+      (restore-buffer-modified-p tick)
+      (should (buffer-modified-p))
+      ;; A tick from the future can't make the buffer unmodified
+      ;; beyond its current state.
+      (restore-buffer-modified-p (+ (buffer-modified-tick) 10))
+      (should-not (buffer-modified-p))
+      (insert "more")
+      (should (buffer-modified-p)))))
+
+(ert-deftest test-restore-buffer-modified-p-unlocks ()
+  (skip-unless create-lockfiles)
+  (let ((file (make-temp-file "restore-buffer-modified-p")))
+    (unwind-protect
+        (with-current-buffer (find-file-noselect file)
+          (unwind-protect
+              (progn
+                (insert "text")
+                (should (file-locked-p file))
+                (restore-buffer-modified-p nil)
+                (should-not (file-locked-p file))
+                ;; Marking the buffer modified doesn't lock the file.
+                (restore-buffer-modified-p t)
+                (should-not (file-locked-p file)))
+            (restore-buffer-modified-p nil)
+            (kill-buffer)))
+      (delete-file file))))
+
+(ert-deftest test-buffer-swap-text ()
+  (let ((a (generate-new-buffer "test-swap-a"))
+        (b (generate-new-buffer "test-swap-b"))
+        marker-a marker-b)
+    (unwind-protect
+        (progn
+          (with-current-buffer a
+            (insert "text of a")
+            (setq marker-a (copy-marker 6)))
+          (with-current-buffer b
+            (insert "b's")
+            (setq marker-b (copy-marker 2))
+            (goto-char 1)
+            (buffer-swap-text a)
+            (should (equal (buffer-string) "text of a"))
+            (should (= (point) 10)))
+          (with-current-buffer a
+            (should (equal (buffer-string) "b's"))
+            (should (= (point) 1)))
+          ;; Markers follow the text they point into.
+          (should (eq (marker-buffer marker-a) b))
+          (should (= marker-a 6))
+          (should (eq (marker-buffer marker-b) a))
+          (should (= marker-b 2))
+          (let ((indirect (make-indirect-buffer a "test-swap-indirect")))
+            (unwind-protect
+                (with-current-buffer b
+                  (should-error (buffer-swap-text indirect))
+                  (should-error (buffer-swap-text a)))
+              (kill-buffer indirect))))
+      (kill-buffer a)
+      (kill-buffer b))))
+
+(ert-deftest test-erase-buffer-indirect ()
+  (let* ((base (generate-new-buffer "test-erase-base"))
+         (indirect (make-indirect-buffer base "test-erase-indirect"))
+         (sibling (make-indirect-buffer base "test-erase-sibling")))
+    (unwind-protect
+        (progn
+          (dolist (buffer (list base indirect sibling))
+            (with-current-buffer buffer
+              (setq buffer-saved-size 100)))
+          (with-current-buffer base
+            (insert "shared contents"))
+          (with-current-buffer indirect
+            (erase-buffer))
+          (dolist (buffer (list base indirect sibling))
+            (with-current-buffer buffer
+              (should (equal (buffer-string) ""))
+              (should (= buffer-saved-size 0)))))
+      (kill-buffer base))))
+
+(ert-deftest test-set-buffer-multibyte-ascii-round-trip ()
+  (with-temp-buffer
+    (insert "hello\nworld")
+    (goto-char 4)
+    (let ((marker (copy-marker 8)))
+      (set-buffer-multibyte nil)
+      (should-not enable-multibyte-characters)
+      (set-buffer-multibyte t)
+      (should enable-multibyte-characters)
+      (should (equal (buffer-string) "hello\nworld"))
+      (should (= (point) 4))
+      (should (= (marker-position marker) 8)))))
+
+(ert-deftest test-set-buffer-multibyte-non-ascii ()
+  (with-temp-buffer
+    (insert "aé")
+    (let ((marker (copy-marker (point-max))))
+      (set-buffer-multibyte nil)
+      (should (= (buffer-size) 3))
+      (should (= (marker-position marker) 4))
+      (should (equal (buffer-string) (encode-coding-string "aé" 'utf-8)))
+      (set-buffer-multibyte t)
+      (should (equal (buffer-string) "aé"))
+      (should (= (marker-position marker) 3)))))
+
+(ert-deftest test-set-buffer-multibyte-indirect-overlays ()
+  (let* ((base (generate-new-buffer "base"))
+         (indirect (make-indirect-buffer base "indirect"))
+         (overlay-index-threshold 0))
+    (unwind-protect
+        (progn
+          (with-current-buffer base
+            (insert "éx"))
+          (with-current-buffer indirect
+            (let ((overlay (make-overlay 2 3)))
+              (should (equal (overlays-at 2) (list overlay)))
+              (with-current-buffer base
+                (set-buffer-multibyte nil))
+              ;; The "x" is now at 3, after the two bytes of the "é".
+              (should (= (overlay-start overlay) 3))
+              (should-not (overlays-at 2))
+              (should (equal (overlays-at 3) (list overlay))))))
+      (kill-buffer indirect)
+      (kill-buffer base))))
+
+(ert-deftest test-set-buffer-multibyte-eight-bit ()
+  (with-temp-buffer
+    (set-buffer-multibyte nil)
+    (insert "a\351b")
+    (set-buffer-multibyte 'to)
+    (should (= (buffer-size) 3))
+    (should (eq (char-after 2) (unibyte-char-to-multibyte ?\351)))
+    (set-buffer-multibyte nil)
+    (should (equal (buffer-string) "a\351b"))))
+
+(ert-deftest test-set-buffer-multibyte-narrowed ()
+  (with-temp-buffer
+    (insert "abc")
+    (narrow-to-region 2 3)
+    (should-error (set-buffer-multibyte nil))
+    (should enable-multibyte-characters)))
+
+(ert-deftest test-move-overlay ()
+  (with-temp-buffer
+    (insert "0123456789")
+    (let ((overlay (make-overlay 2 4)))
+      (should (eq (move-overlay overlay 8 5) overlay))
+      (should (= (overlay-start overlay) 5))
+      (should (= (overlay-end overlay) 8))
+      (should (equal (overlays-at 6) (list overlay)))
+      (should-not (overlays-at 2))
+      ;; Clipped to the buffer.
+      (move-overlay overlay 3 100)
+      (should (= (overlay-end overlay) (point-max))))))
+
+(ert-deftest test-move-overlay-other-buffer ()
+  (let ((overlay (with-temp-buffer
+                   (insert "abc")
+                   (make-overlay 1 2))))
+    ;; The buffer is gone, so the overlay is in no buffer.
+    (should-not (overlay-buffer overlay))
+    (with-temp-buffer
+      (insert "defgh")
+      (move-overlay overlay 2 4)
+      (should (eq (overlay-buffer overlay) (current-buffer)))
+      (should (equal (overlays-in 1 6) (list overlay)))
+      (let ((other (current-buffer)))
+        (with-temp-buffer
+          (insert "ijk")
+          (move-overlay overlay 1 2 (current-buffer))
+          (should (eq (overlay-buffer overlay) (current-buffer)))
+          (should-not (with-current-buffer other (overlays-in 1 6))))))))
+
+(ert-deftest test-move-overlay-evaporate ()
+  (with-temp-buffer
+    (insert "abc")
+    (let ((overlay (make-overlay 1 3)))
+      (overlay-put overlay 'evaporate t)
+      (should-not (move-overlay overlay 2 2))
+      (should-not (overlay-buffer overlay)))))
+
+(ert-deftest test-move-overlay-errors ()
+  (with-temp-buffer
+    (let ((overlay (make-overlay 1 1))
+          (marker (with-temp-buffer (point-marker))))
+      (should-error (move-overlay overlay marker 1))
+      (should-error (move-overlay "foo" 1 1) :type 'wrong-type-argument))))
+
+(ert-deftest test-overlay-get ()
+  (with-temp-buffer
+    (insert "abc")
+    (let ((overlay (make-overlay 1 2)))
+      (should-not (overlay-get overlay 'face))
+      (overlay-put overlay 'face 'bold)
+      (should (eq (overlay-get overlay 'face) 'bold))
+      (should-error (overlay-get "abc" 'face) :type 'wrong-type-argument))))
+
+(ert-deftest test-overlay-get-category ()
+  (with-temp-buffer
+    (insert "abc")
+    (let ((overlay (make-overlay 1 2)))
+      (put 'test-overlay-category 'face 'italic)
+      ;; A category pointing back at itself must not loop.
+      (put 'test-overlay-category 'category 'test-overlay-category)
+      (overlay-put overlay 'category 'test-overlay-category)
+      (should (eq (overlay-get overlay 'face) 'italic))
+      (should (eq (overlay-get overlay 'category) 'test-overlay-category))
+      (should-not (overlay-get overlay 'mouse-face))
+      ;; The overlay's own properties take precedence.
+      (overlay-put overlay 'face 'bold)
+      (should (eq (overlay-get overlay 'face) 'bold)))))
+
+(ert-deftest test-overlay-get-alias ()
+  (with-temp-buffer
+    (insert "abc")
+    (let ((overlay (make-overlay 1 2))
+          (char-property-alias-alist '((test-prop test-alias))))
+      (overlay-put overlay 'test-alias 'value)
+      (should (eq (overlay-get overlay 'test-prop) 'value)))))
+
+(ert-deftest test-region-multibyte-p ()
+  (with-temp-buffer
+    (insert "plain ascii")
+    (should-not (region-multibyte-p (point-min) (point-max)))
+    (insert " naïve")
+    (should (region-multibyte-p (point-min) (point-max)))
+    (should (region-multibyte-p (point-max) 14))
+    (should-not (region-multibyte-p 1 14))
+    ;; Move the gap into the middle of the region.
+    (goto-char 3)
+    (insert "x")
+    (should (region-multibyte-p 2 (point-max)))
+    (should-error (region-multibyte-p 0 3) :type 'args-out-of-range)))
+
+(ert-deftest test-overlay-put ()
+  (with-temp-buffer
+    (insert "abcdef")
+    (let ((overlay (make-overlay 2 4)))
+      (should (eq (overlay-put overlay 'test 'one) 'one))
+      (should (eq (overlay-get overlay 'test) 'one))
+      (should (eq (overlay-put overlay 'test 'two) 'two))
+      (should (equal (overlay-properties overlay) '(test two)))
+      ;; Setting the same value again still returns it.
+      (should (eq (overlay-put overlay 'test 'two) 'two))
+      (should-error (overlay-put 'foo 'test 1) :type 'wrong-type-argument))))
+
+(ert-deftest test-overlay-put-normalizes ()
+  (with-temp-buffer
+    (insert "abc")
+    (let ((overlay (make-overlay 1 2)))
+      (overlay-put overlay 'priority '(nil . 3))
+      (should (equal (overlay-get overlay 'priority) '(0 . 3)))
+      (should-error (overlay-put overlay 'priority "high")
+                    :type 'wrong-type-argument)
+      (should (equal (overlay-put overlay 'face '(nil bold nil)) '(nil bold nil)))
+      (should (equal (overlay-get overlay 'face) '(nil bold nil)))
+      (overlay-put overlay 'face '(:weight bold))
+      (should (equal (overlay-get overlay 'face) '(:weight bold))))))
+
+(ert-deftest test-overlay-put-evaporate ()
+  (with-temp-buffer
+    (insert "abc")
+    (let ((overlay (make-overlay 2 2)))
+      (overlay-put overlay 'evaporate t)
+      (should-not (overlay-buffer overlay)))))
+
+(ert-deftest test-overlays-at ()
+  (with-temp-buffer
+    (insert "0123456789")
+    (let ((outer (make-overlay 2 8))
+          (inner (make-overlay 4 6))
+          (empty (make-overlay 5 5)))
+      (should (equal (overlays-at 1) nil))
+      (should (equal (overlays-at 2) (list outer)))
+      ;; Overlays don't contain the character at their end.
+      (should (equal (overlays-at 6) (list outer)))
+      (should-not (overlays-at 8))
+      (should-not (memq empty (overlays-at 5)))
+      ;; Sorted by priority, then innermost first.
+      (should (equal (overlays-at 5 t) (list inner outer)))
+      (overlay-put outer 'priority 10)
+      (should (equal (overlays-at 5 t) (list outer inner)))
+      (should (equal (overlays-at (copy-marker 5) t) (list outer inner))))))
+
+(ert-deftest test-overlays-at-secondary-priority ()
+  (with-temp-buffer
+    (insert "0123456789")
+    (let ((outer (make-overlay 2 8))
+          (inner (make-overlay 4 6))
+          overlapping)
+      (overlay-put outer 'priority '(nil . 10))
+      ;; A nested overlay beats a secondary priority...
+      (should (equal (overlays-at 5 t) (list inner outer)))
+      ;; ...but among overlapping ones, the secondary priority decides.
+      (setq overlapping (make-overlay 5 9))
+      (overlay-put overlapping 'priority '(nil . 20))
+      (should (equal (overlays-at 5 t) (list overlapping inner outer)))
+      (overlay-put outer 'priority '(1 . 0))
+      (should (equal (car (overlays-at 5 t)) outer)))))
+
+(ert-deftest test-overlays-at-window ()
+  (with-temp-buffer
+    (insert "abc")
+    (let ((mine (make-overlay 1 3))
+          (others (make-overlay 1 3)))
+      (overlay-put others 'window 'not-this-window)
+      (should (= (length (overlays-at 2)) 2))
+      (should (equal (overlays-at 2 (selected-window)) (list mine))))))
+
+(ert-deftest test-overlays-in-region ()
+  (with-temp-buffer
+    (insert "0123456789")
+    (let ((left (make-overlay 1 3))
+          (right (make-overlay 5 8))
+          (empty-beg (make-overlay 3 3))
+          (empty-inside (make-overlay 4 4))
+          (empty-end (make-overlay 5 5))
+          (sort-ovs (lambda (ovs)
+                      (sort ovs (lambda (a b)
+                                  (or (< (overlay-start a) (overlay-start b))
+                                      (and (= (overlay-start a) (overlay-start b))
+                                           (< (overlay-end a) (overlay-end b)))))))))
+      ;; LEFT ends at BEG and RIGHT starts at END, so neither overlaps.
+      (should (equal (funcall sort-ovs (overlays-in 3 5))
+                     (list empty-beg empty-inside)))
+      (should (equal (funcall sort-ovs (overlays-in (copy-marker 2) 6))
+                     (list left empty-beg empty-inside empty-end right))))))
+
+(ert-deftest test-overlays-in-empty-region ()
+  (with-temp-buffer
+    (insert "0123456789")
+    (let ((around (make-overlay 2 6))
+          (empty (make-overlay 4 4)))
+      (make-overlay 4 8)
+      (should (memq empty (overlays-in 4 4)))
+      (should (memq around (overlays-in 4 4)))
+      (should (= (length (overlays-in 4 4)) 2))
+      (let ((at-end (make-overlay (point-max) (point-max))))
+        (should (equal (overlays-in (point-max) (point-max)) (list at-end)))))))
+
+(ert-deftest test-buffer-overlay-summary ()
+  (with-temp-buffer
+    (insert "0123456789")
+    (should-not (buffer-overlay-summary))
+    (let ((ov (make-overlay 4 9)))
+      (overlay-put ov 'face 'bold)
+      (overlay-put ov 'priority 5))
+    (overlay-put (make-overlay 2 6) 'face 'italic)
+    (make-overlay 2 3)
+    (should (equal (buffer-overlay-summary)
+                   '((2 3 . 0) (2 6 . 1) (4 9 . 2))))
+    (let ((buf (current-buffer)))
+      (with-temp-buffer
+        (should-not (buffer-overlay-summary))
+        (should (= (length (buffer-overlay-summary buf)) 3))))))
+
+(ert-deftest test-next-overlay-change ()
+  (with-temp-buffer
+    (insert "0123456789")
+    (should (= (next-overlay-change 1) (point-max)))
+    (make-overlay 3 7)
+    (make-overlay 5 9)
+    (should (= (next-overlay-change 1) 3))
+    (should (= (next-overlay-change 3) 5))
+    (should (= (next-overlay-change 5) 7))
+    (should (= (next-overlay-change (copy-marker 7)) 9))
+    (should (= (next-overlay-change 9) (point-max)))
+    (make-overlay 2 2)
+    (should (= (next-overlay-change 1) 2))
+    (narrow-to-region 1 6)
+    (should (= (next-overlay-change 9) 6))))
+
+(ert-deftest test-previous-overlay-change ()
+  (with-temp-buffer
+    (insert "0123456789")
+    (should (= (previous-overlay-change (point-max)) (point-min)))
+    (make-overlay 3 7)
+    (make-overlay 5 9)
+    (should (= (previous-overlay-change 1) 1))
+    (should (= (previous-overlay-change (point-max)) 9))
+    (should (= (previous-overlay-change 9) 7))
+    (should (= (previous-overlay-change (copy-marker 7)) 5))
+    (should (= (previous-overlay-change 5) 3))
+    (should (= (previous-overlay-change 3) (point-min)))))
+
+(ert-deftest test-overlay-change-round-trip ()
+  (with-temp-buffer
+    (insert "0123456789")
+    (make-overlay 2 6)
+    (make-overlay 4 4)
+    (make-overlay 5 10)
+    (let ((forward (list (point-min)))
+          (backward (list (point-max))))
+      (while (< (car forward) (point-max))
+        (push (next-overlay-change (car forward)) forward))
+      (while (> (car backward) (point-min))
+        (push (previous-overlay-change (car backward)) backward))
+      (should (equal forward '(11 10 6 5 4 2 1)))
+      (should (equal (reverse forward) backward)))))
+
+(ert-deftest test-buffer-save-needed-p ()
+  (let ((file (make-temp-file "buffer-save-needed-p")))
+    (unwind-protect
+        (with-current-buffer (find-file-noselect file)
+          (unwind-protect
+              (progn
+                (should-not (buffer-save-needed-p))
+                (insert "modified")
+                (should (buffer-save-needed-p))
+                (let ((buf (current-buffer)))
+                  (with-temp-buffer
+                    (should (buffer-save-needed-p buf)))))
+            (set-buffer-modified-p nil)
+            (kill-buffer)))
+      (delete-file file))))
+
+(ert-deftest test-buffer-save-needed-p-non-file-buffer ()
+  (with-temp-buffer
+    (insert "modified")
+    (should (buffer-modified-p))
+    (should-not (buffer-save-needed-p))))
+
+(ert-deftest test-buffer-header-line-format ()
+  (with-temp-buffer
+    (should-not (buffer-header-line-format))
+    (setq header-line-format "header")
+    (should (equal (buffer-header-line-format) "header"))
+    (let ((buf (current-buffer)))
+      (with-temp-buffer
+        (should-not (buffer-header-line-format))
+        (should (equal (buffer-header-line-format buf) "header"))))))
+
+(ert-deftest test-buffer-mode-line-format ()
+  (with-temp-buffer
+    (should (equal (buffer-mode-line-format)
+                   (default-value 'mode-line-format)))
+    (setq mode-line-format '("%b"))
+    (let ((buf (current-buffer)))
+      (with-temp-buffer
+        (should (equal (buffer-mode-line-format buf) '("%b")))))))
+
+(ert-deftest test-find-overlay-by-property ()
+  (with-temp-buffer
+    (insert "0123456789")
+    (let ((overlays (mapcar (lambda (i)
+                              (let ((ov (make-overlay i (1+ i))))
+                                (overlay-put ov 'id (intern (format "ov-%d" i)))
+                                ov))
+                            '(1 3 5 7))))
+      (should (eq (find-overlay-by-property 'id 'ov-5) (nth 2 overlays)))
+      (should (eq (find-overlay-by-property 'id 'ov-1) (car overlays)))
+      (should-not (find-overlay-by-property 'id 'ov-2))
+      (should-not (find-overlay-by-property 'other 'ov-5))
+      (delete-overlay (nth 2 overlays))
+      (should-not (find-overlay-by-property 'id 'ov-5)))))
+
 (provide 'buffers-tests)
 
 ;;; buffers-tests.el ends here
@@ -0,0 +1,49 @@
+;;; casefiddle-tests.el --- tests for casefiddle.rs functions
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest test-capitalize-region ()
+  (with-temp-buffer
+    (insert "hELLO wORLD, this is a MiXeD-case line")
+    (capitalize-region (point-min) (point-max))
+    (should (equal (buffer-string) "Hello World, This Is A Mixed-Case Line"))))
+
+(ert-deftest test-capitalize-region-partial ()
+  (with-temp-buffer
+    (insert "foo bAR bAZ qUUX")
+    (capitalize-region 13 6)
+    (should (equal (buffer-string) "foo bAr Baz qUUX"))))
+
+(ert-deftest test-capitalize-region-non-ascii ()
+  (with-temp-buffer
+    (insert "éCOLE ÜBER straße")
+    (capitalize-region (point-min) (point-max))
+    (should (equal (buffer-string) "École Über Straße"))))
+
+(ert-deftest test-capitalize-region-titlecase ()
+  (with-temp-buffer
+    (insert "ǆemal ǄEMAL")
+    (capitalize-region (point-min) (point-max))
+    (should (equal (buffer-string) "ǅemal ǅemal"))))
+
+(ert-deftest test-capitalize-region-markers ()
+  (with-temp-buffer
+    (insert "foo bar baz")
+    (capitalize-region (copy-marker 5) (copy-marker 8))
+    (should (equal (buffer-string) "foo Bar baz"))))
+
+(ert-deftest test-capitalize-region-noncontiguous ()
+  (with-temp-buffer
+    (insert "one two three")
+    (let ((region-extract-function
+           (lambda (method)
+             (when (eq method 'bounds)
+               '((1 . 4) (9 . 14))))))
+      (capitalize-region 1 14 t))
+    (should (equal (buffer-string) "One two Three"))))
+
+(provide 'casefiddle-tests)
+
+;;; casefiddle-tests.el ends here
@@ -50,5 +50,61 @@
   (let ((last-command-event ?a))
     (should-error (self-insert-command -1))))
 
+(ert-deftest delete-indentation-joins-previous-line ()
+  (with-temp-buffer
+    (insert "first  \n    second")
+    (delete-indentation)
+    (should (equal (buffer-string) "first second"))
+    (should (= (point) 6))))
+
+(ert-deftest delete-indentation-with-arg-joins-following-line ()
+  (with-temp-buffer
+    (insert "one\n\t\t two\nthree")
+    (goto-char (point-min))
+    (delete-indentation t)
+    (should (equal (buffer-string) "one two\nthree"))
+    ;; No space is left inside parentheses.
+    (erase-buffer)
+    (insert "(foo\n   )")
+    (delete-indentation)
+    (should (equal (buffer-string) "(foo)"))
+    (erase-buffer)
+    (insert "(\n  bar")
+    (delete-indentation)
+    (should (equal (buffer-string) "(bar"))))
+
+(ert-deftest delete-indentation-fill-prefix ()
+  (with-temp-buffer
+    (insert ";; first\n;; second")
+    (let ((fill-prefix ";; "))
+      (delete-indentation))
+    (should (equal (buffer-string) ";; first second"))))
+
+(ert-deftest delete-indentation-first-line ()
+  (with-temp-buffer
+    (insert "  only line")
+    (delete-indentation)
+    (should (equal (buffer-string) "  only line"))))
+
+(ert-deftest open-line-keeps-point ()
+  (with-temp-buffer
+    (insert "abcdef")
+    (goto-char 4)
+    (open-line 3)
+    (should (equal (buffer-string) "abc\n\n\ndef"))
+    (should (= (point) 4))
+    (open-line -2)
+    (should (equal (buffer-string) "abc\n\n\ndef"))
+    (should (= (point) 4))))
+
+(ert-deftest open-line-fill-prefix ()
+  (with-temp-buffer
+    (insert "text")
+    (goto-char (point-min))
+    (let ((fill-prefix "> "))
+      (open-line 1))
+    (should (equal (buffer-string) "> \ntext"))
+    (should (= (point) 3))))
+
 (provide 'cmds-tests)
 ;;; cmds-tests.el ends here
@@ -110,7 +110,7 @@
   ;; `rename-buffer' is a primitive function that is advised by
   ;; default (by `uniquify'), confusing `subr-lang'.
   :expected-result :failed
-  (should (equal "C" (subr-lang (symbol-function 'rename-buffer)))))
+  (should (equal "Rust" (subr-lang (symbol-function 'rename-buffer)))))
 
 (ert-deftest data-test--describe-function-smoke-fail ()
   ;; `describe-function' relies on `subr-lang' in its implementation,
@@ -136,5 +136,17 @@
   ;; Defined in Rust
   (should (consp (find-definition-noselect 'post-self-insert-hook 'defvar))))
 
+(defvar data-test--set-buffer-local-var 'default)
+
+(ert-deftest data-test--set-buffer-local ()
+  (with-temp-buffer
+    (should (eq (set-buffer-local 'data-test--set-buffer-local-var 'local)
+                'local))
+    (should (local-variable-p 'data-test--set-buffer-local-var))
+    (should (eq data-test--set-buffer-local-var 'local))
+    (should (eq (default-value 'data-test--set-buffer-local-var) 'default)))
+  (should (eq data-test--set-buffer-local-var 'default))
+  (should-error (set-buffer-local "not a symbol" 1) :type 'wrong-type-argument))
+
 (provide 'data-tests)
 ;;; data-tests.el ends here
@@ -41,3 +41,506 @@
 
 (ert-deftest test-message-box ()
   (should-error (message-box)))
+
+(ert-deftest test-insert-strings-and-chars ()
+  (with-temp-buffer
+    (insert "ab" ?c (propertize "d" 'face 'bold))
+    (should (equal (buffer-substring-no-properties (point-min) (point-max)) "abcd"))
+    (should (= (point) 5))
+    (should (eq (get-text-property 4 'face) 'bold))
+    (should-error (insert 'foo) :type 'wrong-type-argument)))
+
+(ert-deftest test-insert-multibyte-into-unibyte ()
+  (with-temp-buffer
+    (set-buffer-multibyte nil)
+    (insert "ε" ?é)
+    (should (= (buffer-size) 2))
+    (should-not (multibyte-string-p (buffer-string)))))
+
+(ert-deftest test-insert-and-inherit ()
+  (with-temp-buffer
+    (insert (propertize "ab" 'face 'bold) (propertize "cd" 'face 'italic))
+    (goto-char 3)
+    (insert-and-inherit "X" ?Y)
+    (should (equal (buffer-substring-no-properties (point-min) (point-max))
+                   "abXYcd"))
+    (should (eq (get-text-property 3 'face) 'bold))
+    (should (eq (get-text-property 4 'face) 'bold))
+    (goto-char 3)
+    (insert "Z")
+    (should-not (get-text-property 3 'face))))
+
+(ert-deftest test-position-bytes ()
+  (with-temp-buffer
+    (set-buffer-multibyte nil)
+    (insert "abc")
+    (should (= (position-bytes 3) 3)))
+  (with-temp-buffer
+    (insert "aεb")
+    (should (= (position-bytes 1) 1))
+    (should (= (position-bytes 3) 4))
+    (should (> (position-bytes 4) 4))
+    (should-not (position-bytes 10))
+    (should-not (position-bytes 0))))
+
+(ert-deftest test-byte-to-position ()
+  (with-temp-buffer
+    (insert "aεb")
+    (should (= (byte-to-position 1) 1))
+    (should (= (byte-to-position 2) 2))
+    ;; Byte 3 is in the middle of "ε", which starts at byte 2.
+    (should (= (byte-to-position 3) 2))
+    (should (= (byte-to-position 4) 3))
+    (should (= (byte-to-position (position-bytes (point-max))) (point-max)))
+    (should-not (byte-to-position 0))
+    (should-not (byte-to-position 100))))
+
+(ert-deftest test-region-bounds-single-region ()
+  (with-temp-buffer
+    (insert "one two three")
+    (setq-local transient-mark-mode t)
+    (set-mark 5)
+    (goto-char 8)
+    (activate-mark)
+    (upcase-region (point-min) (point-max) t)
+    (should (equal (buffer-string) "one TWO three"))))
+
+(ert-deftest test-labeled-narrowing-survives-widen ()
+  (with-temp-buffer
+    (insert "0123456789")
+    (internal-labeled-narrow-to-region 3 8 'test)
+    (should (equal (buffer-string) "23456"))
+    (widen)
+    (should (equal (buffer-string) "23456"))
+    ;; Narrowing further and widening again stops at the label.
+    (narrow-to-region 4 6)
+    (widen)
+    (should (equal (buffer-string) "23456"))
+    ;; A different label does not lift the restriction.
+    (internal-labeled-widen 'other)
+    (should (equal (buffer-string) "23456"))
+    (internal-labeled-widen 'test)
+    (should (equal (buffer-string) "0123456789"))))
+
+(ert-deftest test-labeled-narrowing-nests ()
+  (with-temp-buffer
+    (insert "0123456789")
+    (internal-labeled-narrow-to-region 2 10 'outer)
+    (internal-labeled-narrow-to-region 4 6 'inner)
+    (should (equal (buffer-string) "34"))
+    (internal-labeled-widen 'inner)
+    (should (equal (buffer-string) "12345678"))
+    (internal-labeled-widen 'outer)
+    (should (equal (buffer-string) "0123456789"))))
+
+(ert-deftest test-labeled-narrowing-bounds-narrow-to-region ()
+  (with-temp-buffer
+    (insert "0123456789")
+    (internal-labeled-narrow-to-region 3 8 'test)
+    (narrow-to-region 1 (1+ (buffer-size)))
+    (should (equal (buffer-string) "23456"))
+    (narrow-to-region 5 11)
+    (should (equal (buffer-string) "456"))
+    (narrow-to-region 9 11)
+    (should (= (point-min) 8))
+    (should (= (point-max) 8))))
+
+(ert-deftest test-labeled-narrowing-forgotten-on-kill ()
+  (let ((buffer (generate-new-buffer "labeled")))
+    (with-current-buffer buffer
+      (insert "0123456789")
+      (internal-labeled-narrow-to-region 3 8 'test))
+    (should (assq buffer internal--labeled-restrictions))
+    (kill-buffer buffer)
+    (should-not (assq buffer internal--labeled-restrictions))))
+
+(ert-deftest test-buffer-string-opt ()
+  (with-temp-buffer
+    (insert "plain " (propertize "bold" 'face 'bold))
+    (let ((with-props (buffer-string-opt))
+          (stripped (buffer-string-opt t)))
+      (should (equal with-props (buffer-string)))
+      (should (equal-including-properties with-props (buffer-string)))
+      (should (equal stripped "plain bold"))
+      (should-not (text-properties-at 6 stripped))
+      (should (eq (get-text-property 6 'face with-props) 'bold)))
+    (narrow-to-region 1 6)
+    (should (equal (buffer-string-opt t) "plain"))))
+
+(ert-deftest test-prepend-to-current-buffer ()
+  (with-temp-buffer
+    (insert "world")
+    (goto-char 3)
+    (let ((stay (copy-marker (point-min)))
+          (advance (copy-marker (point-min) t)))
+      (prepend-to-current-buffer "hello ")
+      (should (equal (buffer-string) "hello world"))
+      ;; Point is still before the "r" of "world".
+      (should (= (point) 9))
+      (should (eq (char-after) ?r))
+      (should (= stay 1))
+      (should (= advance 7))
+      (prepend-to-current-buffer ?>)
+      (should (equal (buffer-string) ">hello world"))
+      (should (= (point) 10)))
+    ;; Only the accessible portion is affected.
+    (narrow-to-region 2 7)
+    (goto-char (point-max))
+    (prepend-to-current-buffer "ε")
+    (widen)
+    (should (equal (buffer-string) ">εhello world"))
+    (should (eq (char-before) ?\s))))
+
+(ert-deftest test-prepend-to-current-buffer-read-only ()
+  (with-temp-buffer
+    (insert "world")
+    (goto-char 3)
+    (setq buffer-read-only t)
+    (should-error (prepend-to-current-buffer "hello ") :type 'buffer-read-only)
+    (should (= (point) 3))
+    (should (equal (buffer-string) "world"))))
+
+(ert-deftest test-accessible-buffer-string ()
+  (with-temp-buffer
+    (insert "hidden " (propertize "visible" 'face 'bold) " hidden")
+    (should (equal (accessible-buffer-string) (buffer-string)))
+    (narrow-to-region 8 15)
+    (let ((str (accessible-buffer-string)))
+      (should (equal str "visible"))
+      (should (eq (get-text-property 0 'face str) 'bold)))))
+
+(ert-deftest test-widen-to-region ()
+  (with-temp-buffer
+    (insert "0123456789abcdefghij")
+    (narrow-to-region 8 12)
+    ;; Reveal a neighbouring region before the current one.
+    (widen-to-region 4 9)
+    (should (= (point-min) 4))
+    (should (= (point-max) 12))
+    ;; Bounds that are already wider are kept.
+    (widen-to-region 6 15)
+    (should (= (point-min) 4))
+    (should (= (point-max) 15))
+    (widen-to-region 20 18)
+    (should (= (point-max) 20))
+    (should-error (widen-to-region 0 5) :type 'args-out-of-range)
+    ;; A labeled restriction still bounds the result.
+    (widen)
+    (internal-labeled-narrow-to-region 5 10 'test)
+    (narrow-to-region 6 8)
+    (widen-to-region 1 21)
+    (should (= (point-min) 5))
+    (should (= (point-max) 10))
+    (internal-labeled-widen 'test)))
+
+(ert-deftest test-buffer-lines ()
+  (with-temp-buffer
+    (insert "one\ntwo\n\nfour\n")
+    (should (equal (buffer-lines (point-min) (point-max))
+                   '("one" "two" "" "four")))
+    (should (equal (buffer-lines 1 1) nil))
+    (should (equal (buffer-lines 5 8) '("two")))
+    (should-error (buffer-lines 0 3) :type 'args-out-of-range)))
+
+(ert-deftest test-buffer-lines-partial-last-line ()
+  (with-temp-buffer
+    (insert "alpha\nbeta\ngamma")
+    (should (equal (buffer-lines 3 15) '("pha" "beta" "gam")))
+    (should (equal (buffer-lines (point-max) 1) '("alpha" "beta" "gamma")))))
+
+(ert-deftest test-insert-before-markers ()
+  (with-temp-buffer
+    (insert "abef")
+    (let ((stay (copy-marker 3))
+          (advance (copy-marker 3 t)))
+      (goto-char 3)
+      (insert-before-markers "cd" ?!)
+      (should (equal (buffer-string) "abcd!ef"))
+      (should (= (point) 6))
+      ;; Both markers end up after the text, whatever their type.
+      (should (= stay 6))
+      (should (= advance 6))
+      (goto-char 3)
+      (insert "x")
+      (should (= stay 7)))))
+
+(ert-deftest test-internal-delete-trailing-whitespace ()
+  (with-temp-buffer
+    (insert "one  \ntwo\t \t\nthree\n \t\nfour \t")
+    (internal-delete-trailing-whitespace)
+    (should (equal (buffer-string) "one\ntwo\nthree\n\nfour"))))
+
+(ert-deftest test-internal-delete-trailing-whitespace-region ()
+  (with-temp-buffer
+    (insert "a  \nb  \nc  \nd  ")
+    ;; Only the second and third lines end in the region.
+    (internal-delete-trailing-whitespace 5 12)
+    (should (equal (buffer-string) "a  \nb\nc\nd  "))
+    (setq buffer-read-only t)
+    (should-error (internal-delete-trailing-whitespace)
+                  :type 'buffer-read-only)
+    (let ((inhibit-read-only t))
+      (internal-delete-trailing-whitespace))
+    (should (equal (buffer-string) "a\nb\nc\nd"))))
+
+(ert-deftest test-transform-region-identity ()
+  (with-temp-buffer
+    (insert (propertize "hello" 'face 'bold) " wörld")
+    (let ((marker (copy-marker 8)))
+      (internal--transform-region (point-min) (point-max) #'identity)
+      (should (equal (buffer-string) "hello wörld"))
+      (should (eq (get-text-property 1 'face) 'bold))
+      (should (= marker 8))
+      (should (= (point) (point-max))))))
+
+(ert-deftest test-transform-region-width-change ()
+  (with-temp-buffer
+    (insert "abcabc")
+    (put-text-property 1 3 'face 'bold)
+    (let ((marker (copy-marker 5))
+          (bytes (position-bytes (point-max))))
+      (goto-char 6)
+      (internal--transform-region 1 6 (lambda (c) (if (eq c ?a) ?ä c)))
+      (should (equal (buffer-string) "äbcäbc"))
+      (should (= (position-bytes (point-max)) (+ bytes 2)))
+      (should (eq (get-text-property 1 'face) 'bold))
+      (should (= marker 5))
+      (should (= (point) 6))
+      (internal--transform-region 1 4 (lambda (c) (if (eq c ?ä) ?a c)))
+      (should (equal (buffer-string) "abcäbc")))))
+
+(ert-deftest test-subst-char-in-region-undo ()
+  (with-temp-buffer
+    (buffer-enable-undo)
+    (insert "banana split")
+    (undo-boundary)
+    (subst-char-in-region 3 13 ?a ?o)
+    (should (equal (buffer-string) "banono split"))
+    (should (consp (car buffer-undo-list)))
+    (primitive-undo 1 buffer-undo-list)
+    (should (equal (buffer-string) "banana split"))))
+
+(ert-deftest test-subst-char-in-region-width-change-undo ()
+  (with-temp-buffer
+    (buffer-enable-undo)
+    (insert "banana")
+    (undo-boundary)
+    (subst-char-in-region 1 7 ?a ?ä)
+    (should (equal (buffer-string) "bänänä"))
+    (should (= (position-bytes (point-max)) 10))
+    (primitive-undo 1 buffer-undo-list)
+    (should (equal (buffer-string) "banana"))
+    (should (= (position-bytes (point-max)) 7))))
+
+(ert-deftest test-subst-char-in-region-noundo ()
+  (with-temp-buffer
+    (buffer-enable-undo)
+    (insert "banana")
+    (set-buffer-modified-p nil)
+    (let ((undo-list buffer-undo-list))
+      (subst-char-in-region 1 7 ?n ?m t)
+      (should (equal (buffer-string) "bamama"))
+      (should (eq buffer-undo-list undo-list))
+      (should-not (buffer-modified-p)))))
+
+(ert-deftest replace-region-contents-string ()
+  (with-temp-buffer
+    (insert "head (foo bar baz) tail")
+    (let ((before (copy-marker 3))
+          (inside (copy-marker 16))
+          (after (copy-marker 22))
+          (buffers (length (buffer-list))))
+      (goto-char 2)
+      (replace-region-contents
+       6 19 (lambda ()
+              (should (= (point) (point-min)))
+              (replace-regexp-in-string "bar" "quux" (buffer-string))))
+      (should (equal (buffer-string) "head (foo quux baz) tail"))
+      (should (= (point) 2))
+      (should (= (point-min) 1))
+      (should (= (point-max) (1+ (buffer-size))))
+      (should (= before 3))
+      (should (= inside 17))
+      (should (= after 23))
+      ;; The temporary buffer has been killed.
+      (should (= (length (buffer-list)) buffers)))))
+
+(ert-deftest replace-region-contents-buffer ()
+  (let ((source (generate-new-buffer " *source*")))
+    (unwind-protect
+        (with-temp-buffer
+          (with-current-buffer source (insert "ONE"))
+          (insert "zero one two")
+          (let ((marker (copy-marker 10)))
+            (replace-region-contents 6 9 (lambda () source))
+            (should (equal (buffer-string) "zero ONE two"))
+            (should (= marker 10))))
+      (kill-buffer source))))
+
+(ert-deftest replace-region-contents-temp-buffer-cleanup ()
+  (with-temp-buffer
+    (insert "abc")
+    (let ((buffer (current-buffer))
+          (buffers (length (buffer-list))))
+      (replace-region-contents 1 4 (lambda () "aXc"))
+      (should (eq (current-buffer) buffer))
+      (should (equal (buffer-string) "aXc"))
+      (should (= (length (buffer-list)) buffers))
+      ;; The scratch buffer is killed and BUFFER restored even when
+      ;; the replacement can't be inserted.
+      (should-error (replace-region-contents 1 4 (lambda () 'not-text)))
+      (should (eq (current-buffer) buffer))
+      (should (= (length (buffer-list)) buffers))
+      (should (equal (buffer-string) "aXc")))))
+
+(ert-deftest test-goto-char-recording ()
+  (with-temp-buffer
+    (insert "0123456789")
+    (goto-char 3)
+    (should (= (goto-char-recording 8) 8))
+    (should (= (point) 8))
+    (should (= (mark t) 3))
+    (goto-char-recording (copy-marker 1))
+    (should (= (point) 1))
+    (should (= (mark t) 8))
+    (should (equal (mapcar #'marker-position mark-ring) '(3)))
+    (set-mark-command t)
+    (should (= (point) 8))))
+
+(ert-deftest test-goto-char-recording-wrong-type ()
+  (with-temp-buffer
+    (insert "abc")
+    (should-error (goto-char-recording "x") :type 'wrong-type-argument)
+    (should-not (mark t))))
+
+(ert-deftest test-ensure-final-newline ()
+  (with-temp-buffer
+    (insert "foo")
+    (should (eq (ensure-final-newline) t))
+    (should (equal (buffer-string) "foo\n"))
+    (should (= (point) 4))
+    (should-not (ensure-final-newline))
+    (should (equal (buffer-string) "foo\n"))))
+
+(ert-deftest test-ensure-final-newline-empty ()
+  (with-temp-buffer
+    (should-not (ensure-final-newline))
+    (should (equal (buffer-string) ""))))
+
+(ert-deftest test-ensure-final-newline-read-only ()
+  (with-temp-buffer
+    (insert "foo")
+    (goto-char 2)
+    (setq buffer-read-only t)
+    (should-error (ensure-final-newline) :type 'buffer-read-only)
+    (should (= (point) 2))
+    (should (equal (buffer-string) "foo"))))
+
+(ert-deftest test-ensure-final-newline-signal-keeps-point ()
+  (with-temp-buffer
+    (insert (propertize "foo" 'read-only t))
+    (goto-char 2)
+    (should-error (ensure-final-newline) :type 'text-read-only)
+    (should (= (point) 2))
+    (should (equal (buffer-string) "foo"))))
+
+(ert-deftest test-char-before-point-eq ()
+  (with-temp-buffer
+    (insert "(é")
+    (should (char-before-point-eq ?é))
+    (should-not (char-before-point-eq ?\())
+    (backward-char)
+    (should (char-before-point-eq ?\())
+    (backward-char)
+    (should-not (char-before-point-eq ?\())))
+
+(ert-deftest test-char-before-point-eq-narrowed ()
+  (with-temp-buffer
+    (insert "ab")
+    (narrow-to-region 2 3)
+    (goto-char (point-min))
+    (should-not (char-before-point-eq ?a))))
+
+(ert-deftest test-delete-region-undoable ()
+  (with-temp-buffer
+    (buffer-enable-undo)
+    (insert "hello world")
+    (undo-boundary)
+    (delete-region-undoable 6 (point-max))
+    (should (equal (buffer-string) "hello"))
+    (should (equal (car-safe (car buffer-undo-list)) " world"))
+    (primitive-undo 1 buffer-undo-list)
+    (should (equal (buffer-string) "hello world"))))
+
+(ert-deftest test-delete-region-undoable-read-only ()
+  (with-temp-buffer
+    (buffer-enable-undo)
+    (insert "hello world")
+    (undo-boundary)
+    (let ((undo-list buffer-undo-list))
+      (setq buffer-read-only t)
+      (should-error (delete-region-undoable 1 6) :type 'buffer-read-only)
+      (should (equal (buffer-string) "hello world"))
+      (should (eq buffer-undo-list undo-list)))))
+
+(ert-deftest test-delete-region-undoable-undo-disabled ()
+  (with-temp-buffer
+    (insert "hello world")
+    (should (eq buffer-undo-list t))
+    (delete-region-undoable 1 7)
+    (should (equal (buffer-string) "world"))
+    (should (eq buffer-undo-list t))))
+
+(ert-deftest test-exchange-point-and-mark ()
+  (with-temp-buffer
+    (insert "hello world")
+    (set-mark 3)
+    (goto-char 9)
+    (let ((transient-mark-mode t))
+      (exchange-point-and-mark)
+      (should (= (point) 3))
+      (should (= (mark) 9))
+      (should (region-active-p))
+      ;; A prefix argument deactivates an active mark.
+      (exchange-point-and-mark t)
+      (should (= (point) 9))
+      (should (= (mark t) 3))
+      (should-not (region-active-p)))))
+
+(ert-deftest test-exchange-point-and-mark-no-mark ()
+  (with-temp-buffer
+    (insert "hello")
+    (should-error (exchange-point-and-mark) :type 'user-error)))
+
+(ert-deftest test-buffer-substring ()
+  (with-temp-buffer
+    (insert "héllo " (propertize "wörld" 'face 'bold))
+    (should (equal (buffer-substring 1 6) "héllo"))
+    (should (equal (buffer-substring 6 1) "héllo"))
+    (should-not (text-properties-at 0 (buffer-substring 1 6)))
+    (let ((s (buffer-substring 5 12)))
+      (should (equal s "o wörld"))
+      (should-not (get-text-property 0 'face s))
+      (should (eq (get-text-property 2 'face s) 'bold)))
+    (should (equal (buffer-substring 3 3) ""))
+    (should (multibyte-string-p (buffer-substring 1 3)))
+    (should-error (buffer-substring 0 3) :type 'args-out-of-range)))
+
+(ert-deftest test-buffer-substring-across-gap ()
+  (with-temp-buffer
+    (insert "abcdef")
+    ;; Move the gap into the middle of the text.
+    (goto-char 4)
+    (insert "XY")
+    (delete-char -2)
+    (should (equal (buffer-substring 2 7) "bcdef"))
+    (should (equal (buffer-substring-no-properties 1 7) "abcdef"))))
+
+(ert-deftest test-buffer-substring-no-properties ()
+  (with-temp-buffer
+    (insert (propertize "bold" 'face 'bold) "ünibyte")
+    (let ((s (buffer-substring-no-properties 1 (point-max))))
+      (should (equal s "boldünibyte"))
+      (should-not (next-property-change 0 s)))
+    (set-buffer-multibyte nil)
+    (should-not (multibyte-string-p (buffer-substring-no-properties 1 3)))))
@@ -27,3 +27,119 @@
       (should (directory-name-p "./"))
       (should-not (directory-name-p ""))
       (should-not (directory-name-p ".\\")))))
+
+(ert-deftest test-bufferpos-to-filepos-latin-1 ()
+  (with-temp-buffer
+    (insert "aéb\nc")
+    (should (= (bufferpos-to-filepos 3 'exact 'latin-1-unix) 2))
+    (should (= (bufferpos-to-filepos 6 nil 'latin-1-unix) 5))
+    ;; Each CR of a CRLF pair takes a byte of its own.
+    (should (= (bufferpos-to-filepos 6 'approximate 'latin-1-dos) 6))))
+
+(ert-deftest test-bufferpos-to-filepos-utf-16 ()
+  (with-temp-buffer
+    (insert "aé😀b")
+    ;; `utf-16' writes a 2-byte BOM.
+    (should (= (bufferpos-to-filepos 3 'approximate 'utf-16) 6))
+    (should (= (bufferpos-to-filepos 3 'exact 'utf-16) 6))
+    ;; The emoji lies outside the BMP and takes 4 bytes, which only
+    ;; the exact computation notices.
+    (should (= (bufferpos-to-filepos 4 'approximate 'utf-16) 8))
+    (should (= (bufferpos-to-filepos 4 'exact 'utf-16) 10))
+    (should (= (bufferpos-to-filepos 4 'rounded 'utf-16) 8))))
+
+(ert-deftest test-bufferpos-to-filepos-utf-16-dos ()
+  (with-temp-buffer
+    (insert "a\nb\nc")
+    ;; Each CR of a CRLF pair takes a code unit of its own.
+    (should (= (bufferpos-to-filepos 5 'approximate 'utf-16le-dos) 12))
+    (should (= (bufferpos-to-filepos 5 'rounded 'utf-16le-dos) 12))
+    (should (= (bufferpos-to-filepos 5 'exact 'utf-16le-dos) 12))))
+
+(ert-deftest test-bufferpos-to-filepos-encoded ()
+  ;; EUC-JP is variable-width, so only encoding the text gives the
+  ;; exact answer.
+  (with-temp-buffer
+    (insert "aあb\nc")
+    (should (= (bufferpos-to-filepos 3 'exact 'japanese-iso-8bit-unix) 3))
+    (should (= (bufferpos-to-filepos 5 'exact 'japanese-iso-8bit-dos) 6))
+    ;; The approximation counts the bytes of the buffer's own encoding.
+    (should (= (bufferpos-to-filepos 3 'approximate 'japanese-iso-8bit-unix) 4))
+    (should-not (bufferpos-to-filepos 3 nil 'japanese-iso-8bit-unix))))
+
+(ert-deftest test-buffer-needs-auto-save-p ()
+  (with-temp-buffer
+    (should-not (buffer-needs-auto-save-p))
+    (insert "text")
+    ;; Auto-saving is not enabled yet.
+    (should-not (buffer-needs-auto-save-p))
+    (setq buffer-auto-save-file-name
+          (expand-file-name "#test-auto-save#" temporary-file-directory))
+    (should (buffer-needs-auto-save-p))
+    (should (buffer-needs-auto-save-p (current-buffer)))
+    (set-buffer-modified-p nil)
+    (should-not (buffer-needs-auto-save-p))))
+
+(ert-deftest test-mark-buffer-auto-saved ()
+  (with-temp-buffer
+    (setq buffer-auto-save-file-name
+          (expand-file-name "#test-auto-save#" temporary-file-directory))
+    (insert "text")
+    (should (buffer-needs-auto-save-p))
+    (mark-buffer-auto-saved)
+    (should-not (buffer-needs-auto-save-p))
+    (should (recent-auto-save-p))
+    (insert "more")
+    (should (buffer-needs-auto-save-p))))
+
+(ert-deftest test-insert-file-contents-lite ()
+  (let ((file (make-temp-file "fileio-tests")))
+    (unwind-protect
+        (progn
+          (with-temp-file file
+            (set-buffer-multibyte nil)
+            (insert "hello\n" (encode-coding-string "héllo" 'utf-8)))
+          (with-temp-buffer
+            (insert "<>")
+            (goto-char 2)
+            (should (equal (insert-file-contents-lite file)
+                           (list (expand-file-name file) 11)))
+            (should (= (point) 2))
+            (should (equal (buffer-string) "<hello\nhéllo>"))
+            (should-not buffer-file-name))
+          (with-temp-buffer
+            (set-buffer-multibyte nil)
+            (insert-file-contents-lite file)
+            (should (= (buffer-size) 12))))
+      (delete-file file))))
+
+(ert-deftest test-insert-file-contents-lite-range ()
+  (let ((file (make-temp-file "fileio-tests" nil nil "0123456789")))
+    (unwind-protect
+        (with-temp-buffer
+          (should (equal (insert-file-contents-lite file nil 2 5)
+                         (list (expand-file-name file) 3)))
+          (should (equal (buffer-string) "234"))
+          (erase-buffer)
+          (insert-file-contents-lite file nil 7)
+          (should (equal (buffer-string) "789"))
+          (should-error (insert-file-contents-lite file nil 5 2)
+                        :type 'args-out-of-range))
+      (delete-file file))))
+
+(ert-deftest test-insert-file-contents-lite-visit ()
+  (let ((file (make-temp-file "fileio-tests" nil nil "contents")))
+    (unwind-protect
+        (with-temp-buffer
+          (insert-file-contents-lite file t)
+          (should (equal (buffer-string) "contents"))
+          (should (equal buffer-file-name (expand-file-name file)))
+          (should-not (buffer-modified-p))
+          (should (verify-visited-file-modtime (current-buffer)))
+          (should-error (insert-file-contents-lite file t)))
+      (delete-file file))))
+
+(ert-deftest test-insert-file-contents-lite-missing ()
+  (with-temp-buffer
+    (should-error (insert-file-contents-lite "/nonexistent/fileio-tests")
+                  :type 'file-missing)))
@@ -14,4 +14,25 @@
   (insert "\tsome more text")
   (should (equal (current-indentation) 8)))
 
+(ert-deftest test-point-line-and-column ()
+  (with-temp-buffer
+    (setq tab-width 8)
+    (insert "first line\n    indented\n\tx = 1;\n")
+    (goto-char (point-min))
+    (should (equal (point-line-and-column) '(1 . 0)))
+    (forward-line 1)
+    (forward-char 6)
+    (should (equal (point-line-and-column) '(2 . 6)))
+    (forward-line 1)
+    (forward-char 3)
+    (should (equal (point-line-and-column) '(3 . 10)))
+    (should (equal (point-line-and-column)
+                   (cons (line-number-at-pos) (current-column))))
+    (goto-char (point-max))
+    (should (equal (point-line-and-column) '(4 . 0)))
+    ;; Lines are counted from the start of the accessible portion.
+    (narrow-to-region 12 (point-max))
+    (goto-char (point-max))
+    (should (equal (point-line-and-column) '(3 . 0)))))
+
 ;;; indent-tests.el ends here
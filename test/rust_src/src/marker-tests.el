@@ -57,4 +57,57 @@
     (set-marker marker-2 marker-1)
     (should (goto-char marker-2))))
 
+(ert-deftest marker-charpos-bytepos-conversion-matches-naive-walk ()
+  "Cached char/byte conversions agree with a walk from `point-min'."
+  (with-temp-buffer
+    (dotimes (i 200)
+      (insert (if (zerop (% i 3)) "ε" "a") (if (zerop (% i 7)) "日本" "")))
+    (goto-char (point-min))
+    (dolist (pos '(150 151 149 10 300 299 1 2 250 251 120))
+      (when (<= pos (point-max))
+        (let ((naive (1+ (string-bytes (buffer-substring (point-min) pos)))))
+          (should (= (position-bytes pos) naive))
+          (should (= (byte-to-position naive) pos)))))))
+
+(ert-deftest marker-charpos-bytepos-conversion-near-anchor ()
+  "Many conversions close to one another stay cheap."
+  (with-temp-buffer
+    (dotimes (_ 20000)
+      (insert "εa"))
+    (goto-char (point-min))
+    (let ((start (float-time))
+          (mid (/ (point-max) 2)))
+      (dotimes (i 20000)
+        (position-bytes (+ mid (% i 50))))
+      (should (< (- (float-time) start) 5.0)))))
+
+(ert-deftest marker-swap-text-keeps-markers-in-their-text ()
+  (let ((short (generate-new-buffer "swap-short"))
+        (long (generate-new-buffer "swap-long")))
+    (unwind-protect
+        (let (short-end long-end long-mid)
+          (with-current-buffer short
+            (insert "abc")
+            (setq short-end (point-marker)))
+          (with-current-buffer long
+            (insert "0123456789")
+            (setq long-end (point-marker))
+            (setq long-mid (copy-marker 6)))
+          (with-current-buffer short
+            (buffer-swap-text long))
+          ;; Markers follow the text they pointed into.
+          (should (eq (marker-buffer short-end) long))
+          (should (eq (marker-buffer long-end) short))
+          (should (eq (marker-buffer long-mid) short))
+          (with-current-buffer short
+            (should (equal (buffer-string) "0123456789"))
+            (should (= long-end (point-max)))
+            (should (= long-mid 6))
+            (should (eq (char-after long-mid) ?5)))
+          (with-current-buffer long
+            (should (equal (buffer-string) "abc"))
+            (should (= short-end (point-max)))))
+      (kill-buffer short)
+      (kill-buffer long))))
+
 ;;; marker-tests.el ends here.
@@ -11,4 +11,105 @@
   (with-temp-buffer
     (let ((st (make-syntax-table)))
       (set-syntax-table st)
-      (should (eq st (syntax-table))))))
\ No newline at end of file
+      (should (eq st (syntax-table))))))
+
+(ert-deftest test-char-syntax-class-at ()
+  (with-temp-buffer
+    (with-syntax-table (standard-syntax-table)
+      (insert "a ,")
+      (should (= 2 (char-syntax-class-at 1)))
+      (should (= 0 (char-syntax-class-at 2)))
+      (should (= 1 (char-syntax-class-at 3)))
+      (goto-char 1)
+      (should (= 2 (char-syntax-class-at)))
+      (should-error (char-syntax-class-at 4) :type 'args-out-of-range))))
+
+(ert-deftest test-forward-word ()
+  (with-temp-buffer
+    (with-syntax-table (standard-syntax-table)
+      (insert "foo, bar.baz  qux")
+      (goto-char (point-min))
+      (should (forward-word))
+      (should (= (point) 4))
+      (should (forward-word 2))
+      (should (= (point) 13))
+      (should (forward-word -1))
+      (should (= (point) 10))
+      (should (forward-word -2))
+      (should (= (point) 1))
+      ;; Running into the edges of the buffer returns nil.
+      (should-not (forward-word -1))
+      (should (= (point) 1))
+      (should-not (forward-word 10))
+      (should (= (point) (point-max))))))
+
+(ert-deftest test-forward-word-narrowing ()
+  (with-temp-buffer
+    (with-syntax-table (standard-syntax-table)
+      (insert "one two three")
+      (narrow-to-region 5 8)
+      (goto-char (point-min))
+      (should (forward-word))
+      (should (= (point) 8))
+      (should-not (forward-word))
+      (should (= (point) 8)))))
+
+(ert-deftest test-forward-word-syntax-table-property ()
+  (with-temp-buffer
+    (with-syntax-table (standard-syntax-table)
+      (insert "foo-bar baz")
+      (put-text-property 4 5 'syntax-table (string-to-syntax "w"))
+      (setq-local parse-sexp-lookup-properties t)
+      (goto-char (point-min))
+      (should (forward-word))
+      (should (= (point) 8)))))
+
+(ert-deftest test-internal-count-words ()
+  (with-temp-buffer
+    (with-syntax-table (standard-syntax-table)
+      (insert "Hello, world! It's a  fine day...")
+      (should (= 7 (internal-count-words (point-min) (point-max))))
+      (should (= 0 (internal-count-words 6 8)))
+      ;; A region that splits a word still counts it.
+      (should (= 2 (internal-count-words 3 10)))
+      (should (= 2 (internal-count-words 10 3)))
+      (should (= 2 (internal-count-words (copy-marker 3) (copy-marker 10))))
+      (should (= 7 (count-words (point-min) (point-max))))
+      (should (= 2 (count-words (copy-marker 3) 10)))
+      ;; The region may extend past the current narrowing, which is
+      ;; left alone, as is point.
+      (narrow-to-region 1 13)
+      (goto-char 5)
+      (should (= 4 (internal-count-words 1 20)))
+      (should (equal (list (point-min) (point-max) (point)) '(1 13 5))))))
+
+(ert-deftest test-internal-count-words-scripts ()
+  (with-temp-buffer
+    ;; Words of different scripts are separate words, as for
+    ;; `forward-word-strictly'.
+    (insert "abcдеж")
+    (should (= 2 (internal-count-words (point-min) (point-max))))
+    (should (= 2 (count-words (point-min) (point-max))))))
+
+(ert-deftest test-scan-balanced ()
+  (with-temp-buffer
+    (insert "(a (b c) (d)) e")
+    (should (= 14 (scan-balanced 1 0)))
+    (should (= 9 (scan-balanced 4 0)))
+    (should (= 9 (scan-balanced 5 1)))
+    (should (= 14 (scan-balanced 5 2)))
+    (should (= 5 (scan-balanced 2 -1)))
+    (should (= 1 (scan-balanced 14 0 t)))
+    (should (= 4 (scan-balanced 7 1 t)))
+    (should-not (scan-balanced 14 0))
+    (should-error (scan-balanced 20 0) :type 'args-out-of-range)))
+
+(ert-deftest test-scan-balanced-unbalanced ()
+  (with-temp-buffer
+    (insert "((a)")
+    (should-not (scan-balanced 1 0))
+    (should (= 5 (scan-balanced 2 0)))
+    (erase-buffer)
+    (insert "a) (b)")
+    (should-not (scan-balanced 1 0))
+    (should (= 7 (scan-balanced 3 0)))))
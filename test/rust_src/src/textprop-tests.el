@@ -0,0 +1,60 @@
+;;; textprop-tests.el --- tests for textprop.rs functions
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest textprop-tests--char-properties-at ()
+  (with-temp-buffer
+    (insert (propertize "abc" 'face 'bold 'help-echo "text"))
+    (should (equal (char-properties-at 2) '(face bold help-echo "text")))
+    (let ((low (make-overlay 1 3))
+          (high (make-overlay 2 4)))
+      (overlay-put low 'face 'italic)
+      (overlay-put high 'face 'underline)
+      (overlay-put high 'priority 10)
+      ;; The overlay wins over the text property; other text
+      ;; properties are kept.
+      (let ((props (char-properties-at 1)))
+        (should (eq (plist-get props 'face) 'italic))
+        (should (equal (plist-get props 'help-echo) "text")))
+      ;; Of the two overlays, the higher priority one wins.
+      (should (eq (plist-get (char-properties-at 2) 'face) 'underline))
+      ;; The text's own properties are not modified.
+      (should (eq (get-text-property 2 'face) 'bold)))))
+
+(ert-deftest textprop-tests--property-runs ()
+  (with-temp-buffer
+    (insert (propertize "aaa" 'face 'bold)
+            (propertize "bbb" 'face 'italic)
+            "ccc")
+    (should (equal (property-runs 'face)
+                   '((1 4 bold) (4 7 italic) (7 10 nil))))
+    (should (equal (property-runs 'face 2 5)
+                   '((2 4 bold) (4 5 italic))))
+    (should-not (property-runs 'face 5 5))
+    ;; An overlay splits the run it covers.
+    (overlay-put (make-overlay 2 3) 'face 'underline)
+    (should (equal (property-runs 'face 1 4)
+                   '((1 2 bold) (2 3 underline) (3 4 bold))))
+    (should-error (property-runs 'face 0 5) :type 'args-out-of-range)))
+
+(ert-deftest textprop-tests--region-has-property ()
+  (with-temp-buffer
+    (insert "aaa" (propertize "bbb" 'read-only t) "ccc")
+    (should (region-has-property 1 10 'read-only))
+    (should (region-has-property 6 2 'read-only))
+    (should-not (region-has-property 1 4 'read-only))
+    (should-not (region-has-property 7 10 'read-only))
+    (should-not (region-has-property 1 10 'invisible))
+    (should-not (region-has-property 4 4 'read-only))
+    ;; Overlay properties count too.
+    (overlay-put (make-overlay 8 9) 'invisible t)
+    (should (region-has-property 7 10 'invisible))
+    (should-not (region-has-property 1 8 'invisible))
+    (should-error (region-has-property 0 5 'read-only)
+                  :type 'args-out-of-range)))
+
+(provide 'textprop-tests)
+
+;;; textprop-tests.el ends here
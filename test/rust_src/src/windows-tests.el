@@ -57,3 +57,52 @@
     (delete-window w2)
     (select-window w1)
     (should (eq (get-mru-window) w1))))
+
+(ert-deftest test-buffer-window-list ()
+  (let ((buf (generate-new-buffer "shown-twice")))
+    (unwind-protect
+        (save-window-excursion
+          (delete-other-windows)
+          (should-not (buffer-window-list buf))
+          (set-window-buffer (selected-window) buf)
+          (let ((w1 (selected-window))
+                (w2 (split-window)))
+            (should (eq (window-buffer w2) buf))
+            (let ((windows (buffer-window-list buf)))
+              (should (= (length windows) 2))
+              (should (memq w1 windows))
+              (should (memq w2 windows)))
+            (set-window-buffer w2 (get-buffer-create "*scratch*"))
+            (should (equal (buffer-window-list buf) (list w1)))))
+      (kill-buffer buf))))
+
+(ert-deftest test-window-start-stable-after-insert-at-start ()
+  (let ((buf (generate-new-buffer "stable-start")))
+    (unwind-protect
+        (save-window-excursion
+          (delete-other-windows)
+          (with-current-buffer buf
+            (dotimes (i 50) (insert (format "line %d\n" i))))
+          (let* ((w1 (selected-window))
+                 (w2 (split-window)))
+            (set-window-buffer w2 buf)
+            (set-window-start w2 11)
+            (set-window-point w2 30)
+            ;; Inserting at the window start inside the text is an
+            ;; ordinary insertion; the new text scrolls into view.
+            (with-current-buffer buf
+              (save-excursion
+                (goto-char 11)
+                (insert "new\n")))
+            (should (= (window-start w2) 11))
+            ;; Prepending to the accessible portion keeps W2 showing the
+            ;; text it showed before.
+            (with-current-buffer buf
+              (save-restriction
+                (narrow-to-region 11 (point-max))
+                (save-excursion
+                  (goto-char (point-min))
+                  (insert "new\n"))))
+            (should (= (window-start w2) 15))
+            (should-not (eq (window-buffer w1) buf))))
+      (kill-buffer buf))))